@@ -0,0 +1,112 @@
+//! Baselines for the encode and parse hot paths. Run with `cargo bench`; pass
+//! a name to run only the benchmarks containing it.
+
+use bytes::BytesMut;
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use moqt::{
+    ControlMessage, Deserializer, FilterType, FullSequence, MessageFramer, MessageParser,
+    MessageParserEvent, ObjectHeader, Parameters, Serializer, Subscribe, VarInt,
+};
+
+// One value for each varint size class: 1, 2, 4 and 8 bytes.
+const VARINTS: [u64; 4] = [37, 15_293, 494_878_333, 151_288_809_941_952_652];
+
+const OBJECT_SIZE: usize = 1 << 20;
+const CHUNK_SIZE: usize = 16 << 10;
+
+fn varint_encode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("varint_encode");
+    let mut buffer = Vec::with_capacity(8);
+    for value in VARINTS {
+        let varint = VarInt::new(value);
+        group.bench_function(varint.encoded_size().unwrap().to_string(), |b| {
+            b.iter(|| {
+                buffer.clear();
+                black_box(varint).serialize(&mut buffer).unwrap();
+                black_box(&buffer);
+            })
+        });
+    }
+    group.finish();
+}
+
+fn varint_decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("varint_decode");
+    for value in VARINTS {
+        let wire = VarInt::new(value).to_bytes().unwrap();
+        group.bench_function(wire.len().to_string(), |b| {
+            b.iter(|| {
+                let mut r = black_box(&wire[..]);
+                black_box(VarInt::deserialize(&mut r).unwrap());
+            })
+        });
+    }
+    group.finish();
+}
+
+fn subscribe_round_trip(c: &mut Criterion) {
+    let message = ControlMessage::Subscribe(Subscribe {
+        subscribe_id: 1,
+        track_alias: 2,
+        track_namespace: "foo".into(),
+        track_name: "abcd".to_string(),
+        filter_type: FilterType::AbsoluteStart(FullSequence::new(4, 1)),
+        authorization_info: Some("bar".into()),
+        parameters: Parameters::new(),
+    });
+    let mut buffer = BytesMut::with_capacity(64);
+    c.bench_function("subscribe_round_trip", |b| {
+        b.iter(|| {
+            buffer.clear();
+            MessageFramer::serialize_control_message(black_box(message.clone()), &mut buffer)
+                .unwrap();
+            let mut parser = MessageParser::new(false);
+            parser.process_data(&mut &buffer[..], false);
+            match parser.poll_event() {
+                Some(MessageParserEvent::ControlMessage(message)) => {
+                    black_box(message);
+                }
+                _ => panic!("expected SUBSCRIBE"),
+            }
+        })
+    });
+}
+
+fn object_delivery(c: &mut Criterion) {
+    let header = ObjectHeader::builder()
+        .subscribe_id(3)
+        .track_alias(4)
+        .group_id(5)
+        .object_id(6)
+        .build()
+        .unwrap();
+    let wire = MessageFramer::object_stream(&header, &vec![0xab; OBJECT_SIZE]).unwrap();
+    let mut group = c.benchmark_group("object_delivery");
+    group.throughput(Throughput::Bytes(OBJECT_SIZE as u64));
+    group.bench_function("1MiB_16KiB", |b| {
+        b.iter(|| {
+            let mut parser = MessageParser::new(false);
+            let mut delivered = 0;
+            let mut chunks = wire.chunks(CHUNK_SIZE).peekable();
+            while let Some(mut chunk) = chunks.next() {
+                parser.process_data(&mut chunk, chunks.peek().is_none());
+                while let Some(event) = parser.poll_event() {
+                    if let MessageParserEvent::ObjectMessage(_, payload, _) = event {
+                        delivered += payload.len();
+                    }
+                }
+            }
+            assert_eq!(delivered, OBJECT_SIZE);
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    varint_encode,
+    varint_decode,
+    subscribe_round_trip,
+    object_delivery
+);
+criterion_main!(benches);
@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok((_, len)) = moqt::try_decode_control(data) {
+        assert!(len <= data.len());
+    }
+});
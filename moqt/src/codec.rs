@@ -0,0 +1,286 @@
+use crate::message::message_framer::MessageFramer;
+use crate::message::message_parser::{MessageParser, MessageParserEvent};
+use crate::message::{ControlMessage, Version};
+use crate::{Error, Result};
+use bytes::{Buf, BytesMut};
+use futures_core::Stream;
+use futures_sink::Sink;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+const BUFFER_SIZE: usize = 4096;
+
+/// Reads a MoQT stream and yields the events produced by its parser. The
+/// stream ends once every event has been yielded after the reader reaches EOF
+/// or the parser reports a `ParsingError`.
+pub struct MessageReader<R> {
+    reader: R,
+    parser: MessageParser,
+    read_buffer: Box<[u8]>,
+    eof: bool,
+}
+
+impl<R> MessageReader<R> {
+    pub fn new(reader: R, use_web_transport: bool) -> Self {
+        Self {
+            reader,
+            parser: MessageParser::new(use_web_transport),
+            read_buffer: vec![0; BUFFER_SIZE].into_boxed_slice(),
+            eof: false,
+        }
+    }
+
+    /// As `new`, decoding in the layout of the negotiated `version`.
+    pub fn with_version(reader: R, use_web_transport: bool, version: Version) -> Self {
+        let mut message_reader = Self::new(reader, use_web_transport);
+        message_reader.set_version(version);
+        message_reader
+    }
+
+    /// Sets the negotiated version; see [`MessageParser::set_version`].
+    pub fn set_version(&mut self, version: Version) {
+        self.parser.set_version(version);
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: AsyncRead + Unpin> Stream for MessageReader<R> {
+    type Item = Result<MessageParserEvent>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(event) = this.parser.poll_event() {
+                if let MessageParserEvent::ParsingError(..) = event {
+                    // The parser accepts no more data, so stop reading.
+                    this.eof = true;
+                }
+                return Poll::Ready(Some(Ok(event)));
+            }
+            if this.eof {
+                return Poll::Ready(None);
+            }
+
+            let mut read_buf = ReadBuf::new(&mut this.read_buffer);
+            if let Err(err) = ready!(Pin::new(&mut this.reader).poll_read(cx, &mut read_buf)) {
+                return Poll::Ready(Some(Err(Error::ErrOther(err.to_string()))));
+            }
+            let mut data = read_buf.filled();
+            this.eof = data.is_empty();
+            this.parser.process_data(&mut data, this.eof);
+        }
+    }
+}
+
+/// Frames control messages onto a MoQT control stream. Messages are buffered
+/// by `start_send` and written out by `poll_flush`.
+pub struct MessageWriter<W> {
+    writer: W,
+    write_buffer: BytesMut,
+    version: Version,
+}
+
+impl<W> MessageWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self::with_version(writer, Version::Draft04)
+    }
+
+    /// As `new`, encoding in the layout of the negotiated `version`.
+    pub fn with_version(writer: W, version: Version) -> Self {
+        Self {
+            writer,
+            write_buffer: BytesMut::new(),
+            version,
+        }
+    }
+
+    /// Sets the negotiated version for messages sent from now on.
+    pub fn set_version(&mut self, version: Version) {
+        self.version = version;
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: AsyncWrite + Unpin> MessageWriter<W> {
+    fn poll_write_buffer(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        while self.write_buffer.has_remaining() {
+            let n = match ready!(Pin::new(&mut self.writer).poll_write(cx, &self.write_buffer)) {
+                Ok(0) => return Poll::Ready(Err(Error::ErrStreamClosed)),
+                Ok(n) => n,
+                Err(err) => return Poll::Ready(Err(Error::ErrOther(err.to_string()))),
+            };
+            self.write_buffer.advance(n);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<W: AsyncWrite + Unpin> Sink<ControlMessage> for MessageWriter<W> {
+    type Error = Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        if self.write_buffer.len() >= BUFFER_SIZE {
+            return self.get_mut().poll_write_buffer(cx);
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: ControlMessage) -> Result<()> {
+        let this = self.get_mut();
+        MessageFramer::serialize_control_message_with_version(
+            item,
+            this.version,
+            &mut this.write_buffer,
+        )?;
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_write_buffer(cx))?;
+        Pin::new(&mut this.writer)
+            .poll_flush(cx)
+            .map_err(|err| Error::ErrOther(err.to_string()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_write_buffer(cx))?;
+        Pin::new(&mut this.writer)
+            .poll_shutdown(cx)
+            .map_err(|err| Error::ErrOther(err.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::message::announce::Announce;
+    use crate::message::subscribe::Subscribe;
+    use crate::message::subscribe_ok::SubscribeOk;
+    use crate::message::{FilterType, FullSequence, GroupOrder};
+    use crate::Parameters;
+    use std::future::poll_fn;
+    use tokio::io::AsyncWriteExt;
+
+    async fn send(writer: &mut MessageWriter<tokio::io::DuplexStream>, message: ControlMessage) {
+        let mut writer = Pin::new(writer);
+        poll_fn(|cx| writer.as_mut().poll_ready(cx)).await.unwrap();
+        writer.as_mut().start_send(message).unwrap();
+    }
+
+    async fn next<R: AsyncRead + Unpin>(
+        reader: &mut MessageReader<R>,
+    ) -> Option<Result<MessageParserEvent>> {
+        poll_fn(|cx| Pin::new(&mut *reader).poll_next(cx)).await
+    }
+
+    #[tokio::test]
+    async fn test_duplex_control_messages() -> Result<()> {
+        let (client, server) = tokio::io::duplex(16);
+        let mut writer = MessageWriter::new(client);
+        let mut reader = MessageReader::new(server, false);
+
+        let messages = vec![
+            ControlMessage::Subscribe(Subscribe {
+                subscribe_id: 1,
+                track_alias: 2,
+                track_namespace: "foo".into(),
+                track_name: "abcd".to_string(),
+                filter_type: FilterType::AbsoluteStart(FullSequence::new(4, 1)),
+                authorization_info: Some("bar".into()),
+                parameters: Parameters::new(),
+            }),
+            ControlMessage::Announce(Announce {
+                track_namespace: "foo".into(),
+                authorization_info: Some("bar".into()),
+                parameters: Parameters::new(),
+            }),
+        ];
+
+        // The duplex buffer is smaller than either message, so the reader has to
+        // run concurrently with the writer and reassemble split messages.
+        let sent = messages.clone();
+        let write = async move {
+            for message in sent {
+                send(&mut writer, message).await;
+            }
+            poll_fn(|cx| Pin::new(&mut writer).poll_close(cx)).await
+        };
+        let read = async {
+            let mut received = vec![];
+            while let Some(event) = next(&mut reader).await {
+                match event? {
+                    MessageParserEvent::ControlMessage(message) => received.push(message),
+                    MessageParserEvent::ParsingError(code, reason) => {
+                        return Err(Error::ErrParseError(code, reason))
+                    }
+                    MessageParserEvent::ObjectMessage(..)
+                    | MessageParserEvent::GroupStart(_)
+                    | MessageParserEvent::UnknownControlMessage { .. }
+                    | MessageParserEvent::CustomMessage { .. } => unreachable!(),
+                    MessageParserEvent::StreamClosed => {}
+                }
+            }
+            Ok(received)
+        };
+        let (written, received) = tokio::join!(write, read);
+        written?;
+        assert_eq!(received?, messages);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_duplex_with_version() -> Result<()> {
+        let (client, server) = tokio::io::duplex(64);
+        let mut writer = MessageWriter::with_version(client, Version::Draft05);
+        let mut reader = MessageReader::with_version(server, false, Version::Draft05);
+
+        // SUBSCRIBE_OK only carries the group order from Draft-05.
+        let subscribe_ok = ControlMessage::SubscribeOk(SubscribeOk {
+            subscribe_id: 1,
+            expires: 3,
+            group_order: Some(GroupOrder::Descending),
+            largest_group_object: None,
+        });
+        send(&mut writer, subscribe_ok.clone()).await;
+        poll_fn(|cx| Pin::new(&mut writer).poll_close(cx)).await?;
+
+        match next(&mut reader).await {
+            Some(Ok(MessageParserEvent::ControlMessage(message))) => {
+                assert_eq!(message, subscribe_ok)
+            }
+            _ => panic!("expected SUBSCRIBE_OK"),
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_parsing_error_ends_stream() -> Result<()> {
+        let (mut client, server) = tokio::io::duplex(64);
+        let mut reader = MessageReader::new(server, false);
+
+        // SUBSCRIBE_OK with content_exists = 2. The writer stays open, so the
+        // reader would block if it kept reading.
+        client
+            .write_all(&[0x04, 0x01, 0x03, 0x02, 0x02, 0x0c, 0x14])
+            .await
+            .unwrap();
+        assert!(matches!(
+            next(&mut reader).await,
+            Some(Ok(MessageParserEvent::ParsingError(..)))
+        ));
+        assert!(next(&mut reader).await.is_none());
+
+        Ok(())
+    }
+}
@@ -1,55 +1,162 @@
 use crate::message::message_parser::ErrorCode;
-use std::string::FromUtf8Error;
-use thiserror::Error;
+use alloc::boxed::Box;
+use alloc::string::{FromUtf8Error, String};
+use core::fmt::{Display, Formatter};
+use core::str::Utf8Error;
 
-pub type Result<T> = std::result::Result<T, Error>;
+pub type Result<T> = core::result::Result<T, Error>;
 
-#[derive(Error, Debug, PartialEq)]
+#[derive(Debug, PartialEq)]
 #[non_exhaustive]
 pub enum Error {
-    #[error("value too large for varint encoding")]
     ErrVarIntBoundsExceeded,
-    #[error("unexpected buffer end")]
+    /// Decoding ran out of input.
     ErrUnexpectedEnd,
-    #[error("malformed varint")]
     ErrMalformedVarInt,
-    #[error("buffer is too short")]
+    ErrNonCanonicalVarInt,
+    /// Encoding ran out of room in the output buffer.
     ErrBufferTooShort,
-    #[error("duplicate parameter")]
-    ErrDuplicateParameter,
-    #[error("missing parameter")]
+    ErrLengthOverflow,
+    ErrDuplicateParameter(u64),
+    ErrParameterLengthMismatch {
+        key: u64,
+        len: usize,
+    },
     ErrMissingParameter,
-    #[error("unsupported parameter: {0}")]
     ErrUnsupportedParameter(u64),
-    #[error("invalid message type: {0}")]
     ErrInvalidMessageType(u64),
-    #[error("invalid filter type: {0}")]
     ErrInvalidFilterType(u64),
-    #[error("invalid boolean value: {0}")]
+    ErrInvalidGroupOrder(u64),
+    ErrInvalidPriority(u64),
     ErrInvalidBooleanValue(u8),
-    #[error("unsupported version: {0}")]
     ErrUnsupportedVersion(u64),
-    #[error("invalid role: {0}")]
     ErrInvalidRole(u64),
-    #[error("invalid object type due to {0}")]
+    ErrInvalidErrorCode(u64),
     ErrInvalidObjectType(String),
-    #[error("track or group forward preference requires length")]
     ErrTrackGroupForwardPreferenceRequiresLength,
-    #[error("object status must be kNormal if payload is non-empty")]
     ErrNonEmptyPayloadMustBeWithNormalObjectStatus,
-    #[error("parse error with code: {0} and reason: {1}")]
+    /// A message is malformed or not allowed where it appeared on the wire.
+    /// Protocol violations are reported with [`ErrorCode::ProtocolViolation`].
     ErrParseError(ErrorCode, String),
-    #[error("frame error with reason: {0}")]
     ErrFrameError(String),
-    #[error("stream error with code: {0} and reason: {1}")]
+    /// A well-formed message violates the session state, such as SUBSCRIBE on
+    /// a data stream.
     ErrStreamError(ErrorCode, String),
-    #[error("{0}")]
     ErrOther(String),
-    #[error("stream not exist")]
     ErrStreamNotExisted,
-    #[error("stream closed")]
     ErrStreamClosed,
 
-    #[error("invalid string")]
-    ErrInvalidString(#[from] FromUtf8Error),
+    ErrInvalidString(FromUtf8Error),
+    /// Bytes read as a string, such as a [`crate::LazyString`], are not UTF-8.
+    ErrInvalidUtf8(Utf8Error),
+
+    ErrAt {
+        offset: usize,
+        source: Box<Error>,
+    },
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::ErrVarIntBoundsExceeded => write!(f, "value too large for varint encoding"),
+            Error::ErrUnexpectedEnd => write!(f, "unexpected buffer end"),
+            Error::ErrMalformedVarInt => write!(f, "malformed varint"),
+            Error::ErrNonCanonicalVarInt => write!(f, "varint not minimally encoded"),
+            Error::ErrBufferTooShort => write!(f, "buffer is too short"),
+            Error::ErrLengthOverflow => write!(f, "consumed length overflows usize"),
+            Error::ErrDuplicateParameter(p) => write!(f, "duplicate parameter: {}", p),
+            Error::ErrParameterLengthMismatch { key, len } => write!(
+                f,
+                "parameter {} length {} exceeds remaining bytes",
+                key, len
+            ),
+            Error::ErrMissingParameter => write!(f, "missing parameter"),
+            Error::ErrUnsupportedParameter(p) => write!(f, "unsupported parameter: {}", p),
+            Error::ErrInvalidMessageType(t) => write!(f, "invalid message type: {}", t),
+            Error::ErrInvalidFilterType(t) => write!(f, "invalid filter type: {}", t),
+            Error::ErrInvalidGroupOrder(o) => write!(f, "invalid group order: {}", o),
+            Error::ErrInvalidPriority(p) => write!(f, "invalid priority: {}", p),
+            Error::ErrInvalidBooleanValue(b) => write!(f, "invalid boolean value: {}", b),
+            Error::ErrUnsupportedVersion(v) => write!(f, "unsupported version: {}", v),
+            Error::ErrInvalidRole(r) => write!(f, "invalid role: {}", r),
+            Error::ErrInvalidErrorCode(c) => write!(f, "invalid error code: {}", c),
+            Error::ErrInvalidObjectType(reason) => {
+                write!(f, "invalid object type due to {}", reason)
+            }
+            Error::ErrTrackGroupForwardPreferenceRequiresLength => {
+                write!(f, "track or group forward preference requires length")
+            }
+            Error::ErrNonEmptyPayloadMustBeWithNormalObjectStatus => {
+                write!(f, "object status must be kNormal if payload is non-empty")
+            }
+            Error::ErrParseError(code, reason) => {
+                write!(f, "parse error with code: {} and reason: {}", code, reason)
+            }
+            Error::ErrFrameError(reason) => write!(f, "frame error with reason: {}", reason),
+            Error::ErrStreamError(code, reason) => {
+                write!(f, "stream error with code: {} and reason: {}", code, reason)
+            }
+            Error::ErrOther(reason) => write!(f, "{}", reason),
+            Error::ErrStreamNotExisted => write!(f, "stream not exist"),
+            Error::ErrStreamClosed => write!(f, "stream closed"),
+            Error::ErrInvalidString(_) => write!(f, "invalid string"),
+            Error::ErrInvalidUtf8(_) => write!(f, "invalid string"),
+            Error::ErrAt { offset, source } => write!(f, "{} at offset {}", source, offset),
+        }
+    }
+}
+
+impl core::error::Error for Error {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Error::ErrInvalidString(err) => Some(err),
+            Error::ErrInvalidUtf8(err) => Some(err),
+            Error::ErrAt { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<FromUtf8Error> for Error {
+    fn from(err: FromUtf8Error) -> Self {
+        Error::ErrInvalidString(err)
+    }
+}
+
+impl From<Utf8Error> for Error {
+    fn from(err: Utf8Error) -> Self {
+        Error::ErrInvalidUtf8(err)
+    }
+}
+
+impl Error {
+    /// Records the byte offset within a message at which decoding stopped.
+    pub fn at(self, offset: usize) -> Self {
+        match self {
+            Error::ErrAt { .. } => self,
+            _ => Error::ErrAt {
+                offset,
+                source: Box::new(self),
+            },
+        }
+    }
+
+    /// Returns the offset recorded by [`Error::at`], if any.
+    pub fn offset(&self) -> Option<usize> {
+        if let Error::ErrAt { offset, .. } = self {
+            Some(*offset)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the underlying error, stripping any recorded offset.
+    pub fn root(&self) -> &Error {
+        if let Error::ErrAt { source, .. } = self {
+            source.root()
+        } else {
+            self
+        }
+    }
 }
@@ -1,15 +1,54 @@
+#![cfg_attr(not(feature = "std"), no_std)]
 #![warn(rust_2018_idioms)]
 #![allow(dead_code)]
 
+extern crate alloc;
+
+#[cfg(feature = "tokio")]
+mod codec;
+#[cfg(feature = "std")]
 mod connection;
 mod error;
+#[cfg(feature = "std")]
 mod handler;
-mod message;
+pub mod message;
 mod serde;
+#[cfg(feature = "std")]
 mod session;
 
+#[cfg(feature = "tokio")]
+pub use codec::{MessageReader, MessageWriter};
 pub use error::{Error, Result};
-pub use serde::{parameters::Parameters, varint::VarInt, Deserializer, Serializer};
+pub use message::{
+    announce::Announce,
+    announce_cancel::AnnounceCancel,
+    announce_error::{AnnounceError, AnnounceErrorCode},
+    announce_ok::AnnounceOk,
+    client_setup::ClientSetup,
+    gap_detector::{Gap, GapDetector},
+    go_away::GoAway,
+    group_order_validator::{GroupOrderValidator, GroupOrderViolation},
+    message_framer::{MessageFramer, ObjectStreamWriter},
+    message_parser::{ErrorCode, MessageParser, MessageParserEvent},
+    object::{ObjectForwardingPreference, ObjectHeader, ObjectHeaderBuilder, ObjectStatus},
+    server_setup::ServerSetup,
+    subscribe::Subscribe,
+    subscribe_done::{SubscribeDone, SubscribeDoneCode},
+    subscribe_error::{SubscribeError, SubscribeErrorCode},
+    subscribe_ok::SubscribeOk,
+    subscribe_update::SubscribeUpdate,
+    track_status::{TrackStatus, TrackStatusCode},
+    track_status_request::TrackStatusRequest,
+    try_decode_control,
+    unannounce::UnAnnounce,
+    unsubscribe::UnSubscribe,
+    ControlMessage, FilterType, FullSequence, FullTrackName, GroupOrder, MessageType, Namespace,
+    Priority, Role, SendOrder, StreamType, TrackNamespace, Version,
+};
+pub use serde::{
+    counting_writer::CountingWriter, lazy_string::LazyString, parameters::Parameters,
+    varint::VarInt, Deserializer, LengthPrefixedBytes, Serializer,
+};
 
 /// match between client and server perspective, since there may be a proxy
 /// between them.
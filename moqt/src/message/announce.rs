@@ -1,77 +1,77 @@
 use crate::message::message_parser::ErrorCode;
+use crate::message::{Namespace, Version};
 use crate::serde::parameters::ParameterKey;
-use crate::{Deserializer, Error, Parameters, Result, Serializer};
+use crate::{Deserializer, Error, LazyString, Parameters, Result, Serializer};
+use alloc::string::ToString;
 use bytes::{Buf, BufMut};
 
+/// The track_namespace must be non-empty.
 #[derive(Default, Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct Announce {
-    pub track_namespace: String,
-    pub authorization_info: Option<String>,
+    pub track_namespace: Namespace,
+    /// Kept as received; its UTF-8 is only checked when it is read.
+    pub authorization_info: Option<LazyString>,
+    /// Parameters other than AUTHORIZATION_INFO, preserved as received.
+    pub parameters: Parameters,
 }
 
-impl Deserializer for Announce {
-    fn deserialize<R: Buf>(r: &mut R) -> Result<(Self, usize)> {
-        let (track_namespace, tnsl) = String::deserialize(r)?;
-
-        let mut authorization_info: Option<String> = None;
-        let (num_params, mut pl) = u64::deserialize(r)?;
-        // Parse parameters
-        for _ in 0..num_params {
-            let (key, kl) = u64::deserialize(r)?;
-            pl += kl;
-            let (size, sl) = usize::deserialize(r)?;
-            pl += sl;
-
-            if r.remaining() < size {
-                return Err(Error::ErrBufferTooShort);
-            }
-
-            if key == ParameterKey::AuthorizationInfo as u64 {
-                if authorization_info.is_some() {
-                    return Err(Error::ErrParseError(
-                        ErrorCode::ProtocolViolation,
-                        "AUTHORIZATION_INFO parameter appears twice in ANNOUNCE".to_string(),
-                    ));
-                }
-                let mut buf = vec![0; size];
-                r.copy_to_slice(&mut buf);
-                pl += size;
-
-                authorization_info = Some(String::from_utf8(buf)?);
-            }
+impl Announce {
+    /// Reads an ANNOUNCE in the layout used by `version`.
+    pub fn deserialize_with_version<R: Buf>(version: Version, r: &mut R) -> Result<(Self, usize)> {
+        let (track_namespace, tnsl) = Namespace::deserialize_with_version(version, r)?;
+        if track_namespace.is_empty() {
+            return Err(Error::ErrParseError(
+                ErrorCode::ProtocolViolation,
+                "ANNOUNCE has empty track_namespace".to_string(),
+            ));
         }
 
+        let (authorization_info, parameters, pl) =
+            Parameters::deserialize_with_authorization_info(r, "ANNOUNCE")?;
+
         Ok((
             Self {
                 track_namespace,
                 authorization_info,
+                parameters,
             },
             tnsl + pl,
         ))
     }
-}
 
-impl Serializer for Announce {
-    fn serialize<W: BufMut>(&self, w: &mut W) -> Result<usize> {
-        let mut l = self.track_namespace.serialize(w)?;
+    /// Writes an ANNOUNCE in the layout used by `version`.
+    pub fn serialize_with_version<W: BufMut>(&self, version: Version, w: &mut W) -> Result<usize> {
+        let mut l = self.track_namespace.serialize_with_version(version, w)?;
 
+        let mut parameters = self.parameters.clone();
         if let Some(authorization_info) = self.authorization_info.as_ref() {
-            let mut parameters = Parameters::new();
-            parameters.insert(
-                ParameterKey::AuthorizationInfo,
-                authorization_info.to_string(),
-            )?;
-            l += parameters.serialize(w)?;
+            parameters.insert(ParameterKey::AuthorizationInfo, authorization_info.clone())?;
         }
+        l += parameters.serialize(w)?;
 
         Ok(l)
     }
 }
 
+/// Uses the Draft-04 layout, with a single-string namespace.
+impl Deserializer for Announce {
+    fn deserialize<R: Buf>(r: &mut R) -> Result<(Self, usize)> {
+        Self::deserialize_with_version(Version::Draft04, r)
+    }
+}
+
+impl Serializer for Announce {
+    fn serialize<W: BufMut>(&self, w: &mut W) -> Result<usize> {
+        self.serialize_with_version(Version::Draft04, w)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::message::ControlMessage;
+    use crate::message::{ControlMessage, TrackNamespace};
+    use bytes::Bytes;
     use std::io::Cursor;
 
     #[test]
@@ -83,8 +83,86 @@ mod test {
         ];
 
         let expected_message = ControlMessage::Announce(Announce {
-            track_namespace: "foo".to_string(),
-            authorization_info: Some("bar".to_string()),
+            track_namespace: "foo".into(),
+            authorization_info: Some("bar".into()),
+            parameters: Parameters::new(),
+        });
+
+        let mut cursor: Cursor<&[u8]> = Cursor::new(expected_packet.as_ref());
+        let (actual_message, actual_len) = ControlMessage::deserialize(&mut cursor)?;
+        assert_eq!(expected_message, actual_message);
+        assert_eq!(expected_packet.len(), actual_len);
+
+        let mut actual_packet = vec![];
+        let _ = expected_message.serialize(&mut actual_packet)?;
+        assert_eq!(expected_packet, actual_packet);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_announce_without_authorization_info() -> Result<()> {
+        let expected_packet: Vec<u8> = vec![
+            0x06, 0x03, 0x66, 0x6f, 0x6f, // track_namespace = "foo"
+            0x00, // no parameters
+        ];
+
+        let expected_message = ControlMessage::Announce(Announce {
+            track_namespace: "foo".into(),
+            authorization_info: None,
+            parameters: Parameters::new(),
+        });
+
+        let mut cursor: Cursor<&[u8]> = Cursor::new(expected_packet.as_ref());
+        let (actual_message, actual_len) = ControlMessage::deserialize(&mut cursor)?;
+        assert_eq!(expected_message, actual_message);
+        assert_eq!(expected_packet.len(), actual_len);
+
+        let mut actual_packet = vec![];
+        let _ = expected_message.serialize(&mut actual_packet)?;
+        assert_eq!(expected_packet, actual_packet);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_announce_lazy_path() -> Result<()> {
+        let packet: Vec<u8> = vec![
+            0x06, 0x03, 0x66, 0x6f, 0x6f, // track_namespace = "foo"
+            0x01, // 1 parameter
+            0x01, 0x02, 0xc3, 0x28, // path = invalid UTF-8
+        ];
+
+        // The PATH is kept as received, so its UTF-8 is only checked if read.
+        let (message, _) = ControlMessage::deserialize(&mut packet.as_slice())?;
+        let ControlMessage::Announce(mut announce) = message else {
+            unreachable!();
+        };
+        let path = announce
+            .parameters
+            .remove_lazy_string(ParameterKey::Path)
+            .unwrap();
+        assert_eq!(path.as_bytes(), &[0xc3, 0x28]);
+        assert!(matches!(path.as_str(), Err(Error::ErrInvalidUtf8(_))));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_announce_unknown_parameter() -> Result<()> {
+        let expected_packet: Vec<u8> = vec![
+            0x06, 0x03, 0x66, 0x6f, 0x6f, // track_namespace = "foo"
+            0x02, // 2 parameters
+            0x02, 0x03, 0x62, 0x61, 0x72, // authorization_info = "bar"
+            0x20, 0x02, 0xbe, 0xef, // unknown parameter
+        ];
+
+        let mut parameters = Parameters::new();
+        parameters.0.insert(0x20, vec![0xbe, 0xef]);
+        let expected_message = ControlMessage::Announce(Announce {
+            track_namespace: "foo".into(),
+            authorization_info: Some("bar".into()),
+            parameters,
         });
 
         let mut cursor: Cursor<&[u8]> = Cursor::new(expected_packet.as_ref());
@@ -98,4 +176,82 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn test_announce_duplicate_authorization_info() -> Result<()> {
+        let packet: Vec<u8> = vec![
+            0x03, 0x66, 0x6f, 0x6f, // track_namespace = "foo"
+            0x02, // 2 parameters
+            0x02, 0x03, 0x62, 0x61, 0x72, // authorization_info = "bar"
+            0x02, 0x03, 0x62, 0x61, 0x72, // authorization_info = "bar"
+        ];
+
+        let mut cursor: Cursor<&[u8]> = Cursor::new(packet.as_ref());
+        let err = Announce::deserialize(&mut cursor).unwrap_err();
+        assert_eq!(
+            err,
+            Error::ErrParseError(
+                ErrorCode::ProtocolViolation,
+                "AUTHORIZATION_INFO parameter appears twice in ANNOUNCE".to_string()
+            )
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_announce_empty_namespace() {
+        let packet: &[u8] = &[
+            0x00, // track_namespace = ""
+            0x00, // no parameters
+        ];
+        assert_eq!(
+            Announce::deserialize(&mut &packet[..]),
+            Err(Error::ErrParseError(
+                ErrorCode::ProtocolViolation,
+                "ANNOUNCE has empty track_namespace".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_announce_namespace_tuple() -> Result<()> {
+        let expected_packet: Vec<u8> = vec![
+            0x06, 0x02, // 2 namespace elements
+            0x03, 0x66, 0x6f, 0x6f, // "foo"
+            0x03, 0x62, 0x61, 0x72, // "bar"
+            0x00, // no parameters
+        ];
+
+        let expected_message = ControlMessage::Announce(Announce {
+            track_namespace: TrackNamespace::new(vec![
+                Bytes::from_static(b"foo"),
+                Bytes::from_static(b"bar"),
+            ])
+            .into(),
+            authorization_info: None,
+            parameters: Parameters::new(),
+        });
+
+        let mut cursor: Cursor<&[u8]> = Cursor::new(expected_packet.as_ref());
+        let (actual_message, actual_len) =
+            ControlMessage::deserialize_with_version(Version::Draft06, &mut cursor)?;
+        assert_eq!(expected_message, actual_message);
+        assert_eq!(expected_packet.len(), actual_len);
+
+        let mut actual_packet = vec![];
+        let _ = expected_message.serialize_with_version(Version::Draft06, &mut actual_packet)?;
+        assert_eq!(expected_packet, actual_packet);
+
+        // Two elements do not fit the single-string layout.
+        assert!(expected_message.serialize(&mut vec![]).is_err());
+
+        // An empty tuple is as empty as an empty string.
+        assert!(matches!(
+            Announce::deserialize_with_version(Version::Draft06, &mut &[0x00, 0x00][..]),
+            Err(Error::ErrParseError(ErrorCode::ProtocolViolation, _))
+        ));
+
+        Ok(())
+    }
 }
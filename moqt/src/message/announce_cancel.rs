@@ -1,21 +1,36 @@
+use crate::message::{Namespace, Version};
 use crate::{Deserializer, Result, Serializer};
 use bytes::{Buf, BufMut};
 
 #[derive(Default, Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct AnnounceCancel {
-    pub track_namespace: String,
+    pub track_namespace: Namespace,
 }
 
+impl AnnounceCancel {
+    /// Reads an ANNOUNCE_CANCEL in the layout used by `version`.
+    pub fn deserialize_with_version<R: Buf>(version: Version, r: &mut R) -> Result<(Self, usize)> {
+        let (track_namespace, tnsl) = Namespace::deserialize_with_version(version, r)?;
+        Ok((Self { track_namespace }, tnsl))
+    }
+
+    /// Writes an ANNOUNCE_CANCEL in the layout used by `version`.
+    pub fn serialize_with_version<W: BufMut>(&self, version: Version, w: &mut W) -> Result<usize> {
+        self.track_namespace.serialize_with_version(version, w)
+    }
+}
+
+/// Uses the Draft-04 layout, with a single-string namespace.
 impl Deserializer for AnnounceCancel {
     fn deserialize<R: Buf>(r: &mut R) -> Result<(Self, usize)> {
-        let (track_namespace, tnsl) = String::deserialize(r)?;
-        Ok((Self { track_namespace }, tnsl))
+        Self::deserialize_with_version(Version::Draft04, r)
     }
 }
 
 impl Serializer for AnnounceCancel {
     fn serialize<W: BufMut>(&self, w: &mut W) -> Result<usize> {
-        self.track_namespace.serialize(w)
+        self.serialize_with_version(Version::Draft04, w)
     }
 }
 
@@ -32,7 +47,7 @@ mod test {
         ];
 
         let expected_message = ControlMessage::AnnounceCancel(AnnounceCancel {
-            track_namespace: "foo".to_string(),
+            track_namespace: "foo".into(),
         });
 
         let mut cursor: Cursor<&[u8]> = Cursor::new(expected_packet.as_ref());
@@ -46,4 +61,22 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn test_announce_cancel_try_from_slice() -> Result<()> {
+        let packet: &[u8] = &[
+            0x0c, 0x03, 0x66, 0x6f, 0x6f, // track_namespace = "foo"
+        ];
+        assert_eq!(
+            ControlMessage::try_from(packet)?,
+            ControlMessage::AnnounceCancel(AnnounceCancel {
+                track_namespace: "foo".into(),
+            })
+        );
+
+        let trailing: &[u8] = &[0x0c, 0x03, 0x66, 0x6f, 0x6f, 0x00];
+        assert!(ControlMessage::try_from(trailing).is_err());
+
+        Ok(())
+    }
 }
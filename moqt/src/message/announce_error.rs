@@ -1,29 +1,60 @@
-use crate::{Deserializer, Result, Serializer};
+use crate::message::{Namespace, Version};
+use crate::{Deserializer, Error, Result, Serializer};
+use alloc::string::String;
 use bytes::{Buf, BufMut};
+use core::fmt::{Display, Formatter};
 
 #[derive(Default, Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub enum AnnounceErrorCode {
     #[default]
     InternalError = 0,
     AnnounceNotSupported = 1,
 }
 
+impl TryFrom<u64> for AnnounceErrorCode {
+    type Error = Error;
+
+    fn try_from(value: u64) -> core::result::Result<Self, Self::Error> {
+        match value {
+            0x0 => Ok(AnnounceErrorCode::InternalError),
+            0x1 => Ok(AnnounceErrorCode::AnnounceNotSupported),
+            _ => Err(Error::ErrInvalidErrorCode(value)),
+        }
+    }
+}
+
+impl Display for AnnounceErrorCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:?}", *self)
+    }
+}
+
 #[derive(Default, Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct AnnounceErrorReason {
     pub error_code: AnnounceErrorCode,
     pub reason_phrase: String,
 }
 
 #[derive(Default, Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct AnnounceError {
-    pub track_namespace: String,
+    pub track_namespace: Namespace,
     pub error_code: u64,
     pub reason_phrase: String,
 }
 
-impl Deserializer for AnnounceError {
-    fn deserialize<R: Buf>(r: &mut R) -> Result<(Self, usize)> {
-        let (track_namespace, tnsl) = String::deserialize(r)?;
+impl AnnounceError {
+    /// Returns the typed error code, or None if the peer sent a code this
+    /// implementation does not know. The raw value is kept in |error_code|.
+    pub fn code(&self) -> Option<AnnounceErrorCode> {
+        AnnounceErrorCode::try_from(self.error_code).ok()
+    }
+
+    /// Reads an ANNOUNCE_ERROR in the layout used by `version`.
+    pub fn deserialize_with_version<R: Buf>(version: Version, r: &mut R) -> Result<(Self, usize)> {
+        let (track_namespace, tnsl) = Namespace::deserialize_with_version(version, r)?;
         let (error_code, ecl) = u64::deserialize(r)?;
         let (reason_phrase, rpl) = String::deserialize(r)?;
         Ok((
@@ -35,17 +66,29 @@ impl Deserializer for AnnounceError {
             tnsl + ecl + rpl,
         ))
     }
-}
 
-impl Serializer for AnnounceError {
-    fn serialize<W: BufMut>(&self, w: &mut W) -> Result<usize> {
-        let mut l = self.track_namespace.serialize(w)?;
+    /// Writes an ANNOUNCE_ERROR in the layout used by `version`.
+    pub fn serialize_with_version<W: BufMut>(&self, version: Version, w: &mut W) -> Result<usize> {
+        let mut l = self.track_namespace.serialize_with_version(version, w)?;
         l += self.error_code.serialize(w)?;
         l += self.reason_phrase.serialize(w)?;
         Ok(l)
     }
 }
 
+/// Uses the Draft-04 layout, with a single-string namespace.
+impl Deserializer for AnnounceError {
+    fn deserialize<R: Buf>(r: &mut R) -> Result<(Self, usize)> {
+        Self::deserialize_with_version(Version::Draft04, r)
+    }
+}
+
+impl Serializer for AnnounceError {
+    fn serialize<W: BufMut>(&self, w: &mut W) -> Result<usize> {
+        self.serialize_with_version(Version::Draft04, w)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -61,7 +104,7 @@ mod test {
         ];
 
         let expected_message = ControlMessage::AnnounceError(AnnounceError {
-            track_namespace: "foo".to_string(),
+            track_namespace: "foo".into(),
             error_code: 1,
             reason_phrase: "bar".to_string(),
         });
@@ -77,4 +120,31 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn test_announce_error_code() -> Result<()> {
+        let known_packet: Vec<u8> = vec![
+            0x03, 0x66, 0x6f, 0x6f, // track_namespace = "foo"
+            0x01, // error_code = AnnounceNotSupported
+            0x03, 0x62, 0x61, 0x72, // reason_phrase = "bar"
+        ];
+        let (known, _) = AnnounceError::deserialize(&mut known_packet.as_slice())?;
+        assert_eq!(known.code(), Some(AnnounceErrorCode::AnnounceNotSupported));
+        assert_eq!(known.code().unwrap().to_string(), "AnnounceNotSupported");
+
+        let unknown_packet: Vec<u8> = vec![
+            0x03, 0x66, 0x6f, 0x6f, // track_namespace = "foo"
+            0x07, // error_code = 7
+            0x03, 0x62, 0x61, 0x72, // reason_phrase = "bar"
+        ];
+        let (unknown, _) = AnnounceError::deserialize(&mut unknown_packet.as_slice())?;
+        assert_eq!(unknown.code(), None);
+        assert_eq!(unknown.error_code, 7);
+
+        let mut actual_packet = vec![];
+        let _ = unknown.serialize(&mut actual_packet)?;
+        assert_eq!(unknown_packet, actual_packet);
+
+        Ok(())
+    }
 }
@@ -1,21 +1,36 @@
+use crate::message::{Namespace, Version};
 use crate::{Deserializer, Result, Serializer};
 use bytes::{Buf, BufMut};
 
 #[derive(Default, Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct AnnounceOk {
-    pub track_namespace: String,
+    pub track_namespace: Namespace,
 }
 
+impl AnnounceOk {
+    /// Reads an ANNOUNCE_OK in the layout used by `version`.
+    pub fn deserialize_with_version<R: Buf>(version: Version, r: &mut R) -> Result<(Self, usize)> {
+        let (track_namespace, tnsl) = Namespace::deserialize_with_version(version, r)?;
+        Ok((Self { track_namespace }, tnsl))
+    }
+
+    /// Writes an ANNOUNCE_OK in the layout used by `version`.
+    pub fn serialize_with_version<W: BufMut>(&self, version: Version, w: &mut W) -> Result<usize> {
+        self.track_namespace.serialize_with_version(version, w)
+    }
+}
+
+/// Uses the Draft-04 layout, with a single-string namespace.
 impl Deserializer for AnnounceOk {
     fn deserialize<R: Buf>(r: &mut R) -> Result<(Self, usize)> {
-        let (track_namespace, tnsl) = String::deserialize(r)?;
-        Ok((Self { track_namespace }, tnsl))
+        Self::deserialize_with_version(Version::Draft04, r)
     }
 }
 
 impl Serializer for AnnounceOk {
     fn serialize<W: BufMut>(&self, w: &mut W) -> Result<usize> {
-        self.track_namespace.serialize(w)
+        self.serialize_with_version(Version::Draft04, w)
     }
 }
 
@@ -32,7 +47,7 @@ mod test {
         ];
 
         let expected_message = ControlMessage::AnnounceOk(AnnounceOk {
-            track_namespace: "foo".to_string(),
+            track_namespace: "foo".into(),
         });
 
         let mut cursor: Cursor<&[u8]> = Cursor::new(expected_packet.as_ref());
@@ -2,9 +2,12 @@ use crate::message::message_parser::ErrorCode;
 use crate::message::{Role, Version};
 use crate::serde::parameters::ParameterKey;
 use crate::{Deserializer, Error, Parameters, Result, Serializer};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
 use bytes::{Buf, BufMut};
 
 #[derive(Default, Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct ClientSetup {
     pub supported_versions: Vec<Version>,
     pub role: Option<Role>,
@@ -21,70 +24,54 @@ impl ClientSetup {
     }
 }
 
+/// Reads the ROLE parameter shared by CLIENT_SETUP and SERVER_SETUP.
+pub(crate) fn role_parameter(parameters: &Parameters) -> Result<Option<Role>> {
+    let Some(mut value) = parameters.get_bytes(ParameterKey::Role) else {
+        return Ok(None);
+    };
+    let role = match u64::deserialize(&mut value) {
+        Ok((role, _)) if value.is_empty() => role,
+        _ => {
+            return Err(Error::ErrParseError(
+                ErrorCode::ParameterLengthMismatch,
+                "Parameter length does not match varint encoding".to_string(),
+            ))
+        }
+    };
+    Ok(Some(role.try_into().map_err(|_| {
+        Error::ErrParseError(
+            ErrorCode::ProtocolViolation,
+            "Invalid ROLE parameter".to_string(),
+        )
+    })?))
+}
+
 impl Deserializer for ClientSetup {
     fn deserialize<R: Buf>(r: &mut R) -> Result<(Self, usize)> {
         let (number_supported_versions, mut tl) = usize::deserialize(r)?;
-        let mut supported_versions = Vec::with_capacity(number_supported_versions);
+        // Each version takes at least one byte, so a count beyond the input
+        // cannot be honest; it must not size the allocation.
+        let mut supported_versions =
+            Vec::with_capacity(number_supported_versions.min(r.remaining()));
         for _ in 0..number_supported_versions {
             let (version, vl) = Version::deserialize(r)?;
             supported_versions.push(version);
             tl += vl;
         }
 
-        let (num_params, npl) = u64::deserialize(r)?;
-        tl += npl;
-
-        let mut role: Option<Role> = None;
-        let mut path: Option<String> = None;
-
-        // Parse parameters
-        for _ in 0..num_params {
-            let (key, kl) = u64::deserialize(r)?;
-            tl += kl;
-            let (size, sl) = usize::deserialize(r)?;
-            tl += sl;
-
-            if r.remaining() < size {
-                return Err(Error::ErrBufferTooShort);
+        let (parameters, pl) = Parameters::deserialize(r).map_err(|err| match err {
+            Error::ErrDuplicateParameter(key) if key == ParameterKey::Path as u64 => {
+                Parameters::duplicate_parameter_error(err, "CLIENT_SETUP")
             }
-
-            if key == ParameterKey::Role as u64 {
-                if role.is_some() {
-                    return Err(Error::ErrParseError(
-                        ErrorCode::ProtocolViolation,
-                        "ROLE parameter appears twice in SETUP".to_string(),
-                    ));
-                }
-                let (r, rl) = u64::deserialize(r)?;
-                tl += rl;
-
-                if rl != size {
-                    return Err(Error::ErrParseError(
-                        ErrorCode::ParameterLengthMismatch,
-                        "Parameter length does not match varint encoding".to_string(),
-                    ));
-                }
-
-                role = Some(r.try_into().map_err(|_| {
-                    Error::ErrParseError(
-                        ErrorCode::ProtocolViolation,
-                        "Invalid ROLE parameter".to_string(),
-                    )
-                })?);
-            } else if key == ParameterKey::Path as u64 {
-                if path.is_some() {
-                    return Err(Error::ErrParseError(
-                        ErrorCode::ProtocolViolation,
-                        "PATH parameter appears twice in CLIENT_SETUP".to_string(),
-                    ));
-                }
-                let mut buf = vec![0; size];
-                r.copy_to_slice(&mut buf);
-                tl += size;
-
-                path = Some(String::from_utf8(buf)?);
-            }
-        }
+            err => Parameters::duplicate_parameter_error(err, "SETUP"),
+        })?;
+        tl += pl;
+
+        let role = role_parameter(&parameters)?;
+        let path = parameters
+            .get_bytes(ParameterKey::Path)
+            .map(|path| String::from_utf8(path.to_vec()))
+            .transpose()?;
 
         if role.is_none() {
             return Err(Error::ErrParseError(
@@ -0,0 +1,191 @@
+use crate::message::message_parser::MessageParserEvent;
+use crate::message::object::{ObjectHeader, ObjectStatus};
+use alloc::collections::BTreeMap;
+
+/// A run of object ids that were skipped within a group. Both ends are
+/// inclusive.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Gap {
+    pub group_id: u64,
+    pub from_object: u64,
+    pub to_object: u64,
+}
+
+/// Watches the objects coming out of a `MessageParser` and reports object ids
+/// that were skipped. It keeps no state in the parser itself; feed it every
+/// `ObjectMessage` event for a track.
+///
+/// The first object seen in a group is taken as its start, since a
+/// subscription may begin part way through one. An `EndOfGroup` marker whose
+/// id is past the next expected object reports the missing tail and closes the
+/// group. Status objects such as `ObjectDoesNotExist` count as received.
+///
+/// Only the newest group of each track is followed, so the state is bounded
+/// by the number of tracks. Starting a group drops the state of an older one,
+/// and objects of an older group that arrive late are ignored.
+#[derive(Default, Debug)]
+pub struct GapDetector {
+    /// Next expected object id, keyed by (track_alias, group_id).
+    next_object_ids: BTreeMap<(u64, u64), u64>,
+}
+
+impl GapDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a gap if `event` is an object that skips ahead of the next
+    /// expected object id. Other events are ignored.
+    pub fn on_event(&mut self, event: &MessageParserEvent) -> Option<Gap> {
+        match event {
+            MessageParserEvent::ObjectMessage(header, _, _) => self.on_object(header),
+            _ => None,
+        }
+    }
+
+    /// As `on_event`, for a single object header. Further fragments of an
+    /// object that was already seen do not change any state.
+    pub fn on_object(&mut self, header: &ObjectHeader) -> Option<Gap> {
+        let key = (header.track_alias, header.group_id);
+        match header.object_status {
+            ObjectStatus::GroupDoesNotExist => {
+                self.next_object_ids.remove(&key);
+                return None;
+            }
+            ObjectStatus::EndOfTrack => {
+                self.next_object_ids
+                    .retain(|&(track_alias, _), _| track_alias != header.track_alias);
+                return None;
+            }
+            _ => {}
+        }
+
+        if !self.next_object_ids.contains_key(&key) {
+            let newest = self
+                .next_object_ids
+                .range((header.track_alias, 0)..=(header.track_alias, u64::MAX))
+                .next_back()
+                .map(|(&key, _)| key);
+            match newest {
+                Some((_, group_id)) if group_id > header.group_id => return None,
+                Some(newest) => {
+                    self.next_object_ids.remove(&newest);
+                }
+                None => {}
+            }
+        }
+
+        let gap = match self.next_object_ids.get(&key) {
+            Some(&next) if header.object_id > next => Some(Gap {
+                group_id: header.group_id,
+                from_object: next,
+                to_object: header.object_id - 1,
+            }),
+            _ => None,
+        };
+
+        if header.object_status == ObjectStatus::EndOfGroup {
+            self.next_object_ids.remove(&key);
+        } else {
+            let next = self.next_object_ids.entry(key).or_insert(0);
+            *next = (*next).max(header.object_id.saturating_add(1));
+        }
+        gap
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::vec::Vec;
+
+    fn object(group_id: u64, object_id: u64, object_status: ObjectStatus) -> ObjectHeader {
+        ObjectHeader {
+            group_id,
+            object_id,
+            object_status,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_contiguous_run() {
+        let mut detector = GapDetector::new();
+        let gaps: Vec<Gap> = [
+            object(1, 0, ObjectStatus::Normal),
+            object(1, 1, ObjectStatus::Normal),
+            object(1, 1, ObjectStatus::Normal), // a further fragment of object 1
+            object(1, 2, ObjectStatus::ObjectDoesNotExist),
+            object(1, 3, ObjectStatus::EndOfGroup),
+            object(2, 5, ObjectStatus::Normal), // joined group 2 part way through
+            object(2, 6, ObjectStatus::Normal),
+        ]
+        .iter()
+        .filter_map(|header| detector.on_object(header))
+        .collect();
+        assert!(gaps.is_empty());
+    }
+
+    #[test]
+    fn test_missing_object() {
+        let mut detector = GapDetector::new();
+        assert_eq!(
+            detector.on_object(&object(1, 0, ObjectStatus::Normal)),
+            None
+        );
+        assert_eq!(
+            detector.on_object(&object(1, 3, ObjectStatus::Normal)),
+            Some(Gap {
+                group_id: 1,
+                from_object: 1,
+                to_object: 2,
+            })
+        );
+        assert_eq!(
+            detector.on_object(&object(1, 5, ObjectStatus::EndOfGroup)),
+            Some(Gap {
+                group_id: 1,
+                from_object: 4,
+                to_object: 4,
+            })
+        );
+
+        // The group is closed, so a late object starts it afresh.
+        assert_eq!(
+            detector.on_object(&object(1, 4, ObjectStatus::Normal)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_older_groups_evicted() {
+        let mut detector = GapDetector::new();
+        for group_id in 0..100 {
+            detector.on_object(&object(group_id, 0, ObjectStatus::Normal));
+        }
+        assert_eq!(detector.next_object_ids.len(), 1);
+
+        // A late object of an older group is ignored.
+        assert_eq!(
+            detector.on_object(&object(50, 7, ObjectStatus::Normal)),
+            None
+        );
+        assert_eq!(detector.next_object_ids.len(), 1);
+        assert_eq!(
+            detector.on_object(&object(99, 2, ObjectStatus::Normal)),
+            Some(Gap {
+                group_id: 99,
+                from_object: 1,
+                to_object: 1,
+            })
+        );
+
+        // Other tracks are followed separately.
+        let other_track = ObjectHeader {
+            track_alias: 1,
+            ..object(3, 0, ObjectStatus::Normal)
+        };
+        assert_eq!(detector.on_object(&other_track), None);
+        assert_eq!(detector.next_object_ids.len(), 2);
+    }
+}
@@ -1,21 +1,72 @@
-use crate::{Deserializer, Result, Serializer};
+use crate::message::Version;
+use crate::{Deserializer, Error, Result, Serializer};
+use alloc::string::{String, ToString};
 use bytes::{Buf, BufMut};
 
-#[derive(Default, Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct GoAway {
-    pub new_session_uri: String,
+    /// Where to reconnect. `Some("")` asks the peer to reconnect to the same
+    /// URI. None means the field is absent, as in versions whose GOAWAY
+    /// carries no fields (see [`Version::go_away_has_uri`]).
+    pub new_session_uri: Option<String>,
 }
 
+/// Reconnect to the same URI.
+impl Default for GoAway {
+    fn default() -> Self {
+        Self {
+            new_session_uri: Some(String::new()),
+        }
+    }
+}
+
+impl GoAway {
+    /// Reads a GOAWAY in the layout used by `version`.
+    pub fn deserialize_with_version<R: Buf>(version: Version, r: &mut R) -> Result<(Self, usize)> {
+        if !version.go_away_has_uri() {
+            return Ok((
+                Self {
+                    new_session_uri: None,
+                },
+                0,
+            ));
+        }
+        let (new_session_uri, nsul) = String::deserialize(r)?;
+        Ok((
+            Self {
+                new_session_uri: Some(new_session_uri),
+            },
+            nsul,
+        ))
+    }
+
+    /// Writes a GOAWAY in the layout used by `version`. A version with the
+    /// field needs a URI, and one without it cannot carry a new URI.
+    pub fn serialize_with_version<W: BufMut>(&self, version: Version, w: &mut W) -> Result<usize> {
+        match (version.go_away_has_uri(), self.new_session_uri.as_ref()) {
+            (true, Some(new_session_uri)) => new_session_uri.serialize(w),
+            (true, None) => Err(Error::ErrFrameError(
+                "GOAWAY requires new_session_uri".to_string(),
+            )),
+            (false, Some(new_session_uri)) if !new_session_uri.is_empty() => Err(
+                Error::ErrFrameError("GOAWAY cannot carry new_session_uri".to_string()),
+            ),
+            (false, _) => Ok(0),
+        }
+    }
+}
+
+/// Uses the Draft-04 layout, which always carries the URI.
 impl Deserializer for GoAway {
     fn deserialize<R: Buf>(r: &mut R) -> Result<(Self, usize)> {
-        let (new_session_uri, nsul) = String::deserialize(r)?;
-        Ok((Self { new_session_uri }, nsul))
+        Self::deserialize_with_version(Version::Draft04, r)
     }
 }
 
 impl Serializer for GoAway {
     fn serialize<W: BufMut>(&self, w: &mut W) -> Result<usize> {
-        self.new_session_uri.serialize(w)
+        self.serialize_with_version(Version::Draft04, w)
     }
 }
 
@@ -30,7 +81,7 @@ mod test {
         let expected_packet: Vec<u8> = vec![0x10, 0x03, 0x66, 0x6f, 0x6f];
 
         let expected_message = ControlMessage::GoAway(GoAway {
-            new_session_uri: "foo".to_string(),
+            new_session_uri: Some("foo".to_string()),
         });
 
         let mut cursor: Cursor<&[u8]> = Cursor::new(expected_packet.as_ref());
@@ -44,4 +95,45 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn test_go_away_uri_forms() -> Result<()> {
+        let cases: [(Version, &[u8], Option<&str>); 3] = [
+            (Version::Draft04, &[0x03, 0x66, 0x6f, 0x6f], Some("foo")),
+            (Version::Draft04, &[0x00], Some("")),
+            (Version::Draft02, &[], None),
+        ];
+        for (version, packet, new_session_uri) in cases {
+            let expected = GoAway {
+                new_session_uri: new_session_uri.map(|uri| uri.to_string()),
+            };
+            assert_eq!(
+                GoAway::deserialize_with_version(version, &mut &packet[..])?,
+                (expected.clone(), packet.len())
+            );
+            let mut buf = vec![];
+            assert_eq!(
+                expected.serialize_with_version(version, &mut buf)?,
+                packet.len()
+            );
+            assert_eq!(&buf[..], packet);
+        }
+
+        // The default asks for the same URI.
+        assert_eq!(&GoAway::default().to_bytes()?[..], &[0x00]);
+
+        // Absent and present are not interchangeable.
+        let absent = GoAway {
+            new_session_uri: None,
+        };
+        assert!(absent.serialize(&mut vec![]).is_err());
+        let moved = GoAway {
+            new_session_uri: Some("foo".to_string()),
+        };
+        assert!(moved
+            .serialize_with_version(Version::Draft02, &mut vec![])
+            .is_err());
+
+        Ok(())
+    }
 }
@@ -0,0 +1,117 @@
+use crate::message::message_parser::MessageParserEvent;
+use crate::message::object::ObjectHeader;
+use crate::message::GroupOrder;
+use alloc::collections::BTreeMap;
+
+/// An object from a group that the promised group order had already moved
+/// past.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct GroupOrderViolation {
+    pub track_alias: u64,
+    /// The group the track had reached.
+    pub expected_after: u64,
+    pub group_id: u64,
+}
+
+/// Checks that objects arrive in the group order promised by SUBSCRIBE_OK:
+/// each new group must come after the previous one in ascending order, or
+/// before it in descending order. Objects of the group in progress are always
+/// accepted. Like `GapDetector`, it is fed `ObjectMessage` events and keeps
+/// no state in the parser.
+#[derive(Debug)]
+pub struct GroupOrderValidator {
+    group_order: GroupOrder,
+    /// The current group, keyed by track_alias.
+    current_group_ids: BTreeMap<u64, u64>,
+}
+
+impl GroupOrderValidator {
+    pub fn new(group_order: GroupOrder) -> Self {
+        Self {
+            group_order,
+            current_group_ids: BTreeMap::new(),
+        }
+    }
+
+    /// Returns a violation if `event` is an object out of group order. Other
+    /// events are ignored.
+    pub fn on_event(&mut self, event: &MessageParserEvent) -> Option<GroupOrderViolation> {
+        match event {
+            MessageParserEvent::ObjectMessage(header, _, _) => self.on_object(header),
+            _ => None,
+        }
+    }
+
+    /// As `on_event`, for a single object header. An out-of-order object does
+    /// not move the track's current group.
+    pub fn on_object(&mut self, header: &ObjectHeader) -> Option<GroupOrderViolation> {
+        let Some(&current) = self.current_group_ids.get(&header.track_alias) else {
+            self.current_group_ids
+                .insert(header.track_alias, header.group_id);
+            return None;
+        };
+        let in_order = match self.group_order {
+            GroupOrder::Ascending => header.group_id >= current,
+            GroupOrder::Descending => header.group_id <= current,
+        };
+        if !in_order {
+            return Some(GroupOrderViolation {
+                track_alias: header.track_alias,
+                expected_after: current,
+                group_id: header.group_id,
+            });
+        }
+        self.current_group_ids
+            .insert(header.track_alias, header.group_id);
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::vec::Vec;
+
+    fn violations(group_order: GroupOrder, group_ids: &[u64]) -> Vec<GroupOrderViolation> {
+        let mut validator = GroupOrderValidator::new(group_order);
+        group_ids
+            .iter()
+            .filter_map(|&group_id| {
+                validator.on_object(&ObjectHeader {
+                    group_id,
+                    ..Default::default()
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_ascending_in_order() {
+        assert!(violations(GroupOrder::Ascending, &[1, 1, 2, 4, 4, 7]).is_empty());
+    }
+
+    #[test]
+    fn test_descending_in_order() {
+        assert!(violations(GroupOrder::Descending, &[7, 7, 4, 2, 2, 1]).is_empty());
+    }
+
+    #[test]
+    fn test_out_of_order() {
+        assert_eq!(
+            violations(GroupOrder::Ascending, &[1, 3, 2, 3, 4]),
+            [GroupOrderViolation {
+                track_alias: 0,
+                expected_after: 3,
+                group_id: 2,
+            }]
+        );
+        assert_eq!(
+            violations(GroupOrder::Descending, &[5, 4, 6]),
+            [GroupOrderViolation {
+                track_alias: 0,
+                expected_after: 4,
+                group_id: 6,
+            }]
+        );
+    }
+}
@@ -1,7 +1,8 @@
 use crate::message::object::{ObjectForwardingPreference, ObjectHeader, ObjectStatus};
-use crate::message::{ControlMessage, MessageType};
+use crate::message::{ControlMessage, MessageType, Version};
 use crate::{Error, Result, Serializer};
-use bytes::{BufMut, Bytes};
+use alloc::string::ToString;
+use bytes::{BufMut, Bytes, BytesMut};
 
 pub struct MessageFramer;
 
@@ -13,10 +14,47 @@ impl MessageFramer {
         control_message.serialize(w)
     }
 
+    /// Serializes a control message in the layout of the negotiated
+    /// `version`.
+    pub fn serialize_control_message_with_version<W: BufMut>(
+        control_message: ControlMessage,
+        version: Version,
+        w: &mut W,
+    ) -> Result<usize> {
+        control_message.serialize_with_version(version, w)
+    }
+
+    /// Serializes a control message preceded by its varint length, for peers
+    /// that length-prefix messages on the control stream.
+    pub fn serialize_length_prefixed_control_message<W: BufMut>(
+        control_message: ControlMessage,
+        w: &mut W,
+    ) -> Result<usize> {
+        let mut l = control_message.encoded_size()?.serialize(w)?;
+        l += control_message.serialize(w)?;
+        Ok(l)
+    }
+
     pub fn serialize_object_header<W: BufMut>(
         object_header: ObjectHeader,
         is_first_in_stream: bool,
         w: &mut W,
+    ) -> Result<usize> {
+        MessageFramer::serialize_object_header_with_version(
+            object_header,
+            is_first_in_stream,
+            Version::Draft04,
+            w,
+        )
+    }
+
+    /// Like [`MessageFramer::serialize_object_header`], in the object header
+    /// layout of `version`.
+    pub fn serialize_object_header_with_version<W: BufMut>(
+        object_header: ObjectHeader,
+        is_first_in_stream: bool,
+        version: Version,
+        w: &mut W,
     ) -> Result<usize> {
         if object_header.object_payload_length.is_none()
             && !(object_header.object_forwarding_preference == ObjectForwardingPreference::Object
@@ -93,7 +131,7 @@ impl MessageFramer {
                 tl += message_type.serialize(w)?;
                 tl += object_header.subscribe_id.serialize(w)?;
                 tl += object_header.track_alias.serialize(w)?;
-                tl += object_header.object_send_order.serialize(w)?;
+                tl += MessageFramer::serialize_priority(&object_header, version, w)?;
                 tl += object_header.group_id.serialize(w)?;
                 tl += object_header.object_id.serialize(w)?;
                 tl += object_payload_length.serialize(w)?;
@@ -114,7 +152,7 @@ impl MessageFramer {
                 tl += object_header.subscribe_id.serialize(w)?;
                 tl += object_header.track_alias.serialize(w)?;
                 tl += object_header.group_id.serialize(w)?;
-                tl += object_header.object_send_order.serialize(w)?;
+                tl += MessageFramer::serialize_priority(&object_header, version, w)?;
                 tl += object_header.object_id.serialize(w)?;
                 tl += object_payload_length.serialize(w)?;
                 if object_payload_length == 0 {
@@ -128,7 +166,7 @@ impl MessageFramer {
                 tl += object_header.track_alias.serialize(w)?;
                 tl += object_header.group_id.serialize(w)?;
                 tl += object_header.object_id.serialize(w)?;
-                tl += object_header.object_send_order.serialize(w)?;
+                tl += MessageFramer::serialize_priority(&object_header, version, w)?;
                 tl += (object_header.object_status as u64).serialize(w)?;
 
                 Ok(tl)
@@ -150,10 +188,46 @@ impl MessageFramer {
         Ok(tl)
     }
 
+    /// Encodes a complete OBJECT_STREAM, header and payload, ready to send on
+    /// its own unidirectional stream. The object runs to the end of the stream,
+    /// so the header's forwarding preference and payload length are ignored.
+    pub fn object_stream(object_header: &ObjectHeader, payload: &[u8]) -> Result<Bytes> {
+        if object_header.object_status != ObjectStatus::Normal && !payload.is_empty() {
+            return Err(Error::ErrInvalidObjectType(
+                "Object status must be kNormal if payload is non-empty".to_string(),
+            ));
+        }
+
+        let mut adjusted_object_header = *object_header;
+        adjusted_object_header.object_forwarding_preference = ObjectForwardingPreference::Object;
+        adjusted_object_header.object_payload_length = None;
+
+        let mut buffer = BytesMut::new();
+        MessageFramer::serialize_object_header(adjusted_object_header, true, &mut buffer)?;
+        buffer.put_slice(payload);
+        Ok(buffer.freeze())
+    }
+
     pub fn serialize_object_datagram<W: BufMut>(
         object_header: ObjectHeader,
         payload: Bytes,
         w: &mut W,
+    ) -> Result<usize> {
+        MessageFramer::serialize_object_datagram_with_version(
+            object_header,
+            payload,
+            Version::Draft04,
+            w,
+        )
+    }
+
+    /// Like [`MessageFramer::serialize_object_datagram`], in the object header
+    /// layout of `version`.
+    pub fn serialize_object_datagram_with_version<W: BufMut>(
+        object_header: ObjectHeader,
+        payload: Bytes,
+        version: Version,
+        w: &mut W,
     ) -> Result<usize> {
         if object_header.object_status != ObjectStatus::Normal && !payload.is_empty() {
             return Err(Error::ErrInvalidObjectType(
@@ -167,10 +241,113 @@ impl MessageFramer {
         tl += object_header.track_alias.serialize(w)?;
         tl += object_header.group_id.serialize(w)?;
         tl += object_header.object_id.serialize(w)?;
-        tl += object_header.object_send_order.serialize(w)?;
+        tl += MessageFramer::serialize_priority(&object_header, version, w)?;
         tl += (object_header.object_status as u64).serialize(w)?;
         tl += payload.serialize(w)?;
 
         Ok(tl)
     }
+
+    // Draft-05 replaces the object send order with a one-byte priority.
+    fn serialize_priority<W: BufMut>(
+        object_header: &ObjectHeader,
+        version: Version,
+        w: &mut W,
+    ) -> Result<usize> {
+        if version.uses_publisher_priority() {
+            object_header.publisher_priority.serialize(w)
+        } else {
+            object_header.object_send_order.serialize(w)
+        }
+    }
+}
+
+/// Writes one object in pieces, for payloads too large to hold in memory at
+/// once. The header is written by [`ObjectStreamWriter::begin`] and the payload
+/// by any number of [`ObjectStreamWriter::write_chunk`] calls. An object with
+/// the Object forwarding preference runs to the end of its stream, so its
+/// length need not be known in advance; Track and Group objects must declare
+/// their length in the header and write exactly that many bytes.
+pub struct ObjectStreamWriter {
+    object_payload_length: Option<u64>,
+    written: u64,
+}
+
+impl ObjectStreamWriter {
+    pub fn begin<W: BufMut>(
+        object_header: ObjectHeader,
+        is_first_in_stream: bool,
+        w: &mut W,
+    ) -> Result<(Self, usize)> {
+        ObjectStreamWriter::begin_with_version(
+            object_header,
+            is_first_in_stream,
+            Version::Draft04,
+            w,
+        )
+    }
+
+    /// Like [`ObjectStreamWriter::begin`], in the object header layout of
+    /// `version`.
+    pub fn begin_with_version<W: BufMut>(
+        object_header: ObjectHeader,
+        is_first_in_stream: bool,
+        version: Version,
+        w: &mut W,
+    ) -> Result<(Self, usize)> {
+        if object_header.object_forwarding_preference == ObjectForwardingPreference::Datagram {
+            return Err(Error::ErrInvalidObjectType(
+                "Datagram forwarding preference cannot be written to a stream".to_string(),
+            ));
+        }
+        let object_payload_length = match object_header.object_forwarding_preference {
+            ObjectForwardingPreference::Object => None,
+            _ => object_header.object_payload_length,
+        };
+        let l = MessageFramer::serialize_object_header_with_version(
+            object_header,
+            is_first_in_stream,
+            version,
+            w,
+        )?;
+        Ok((
+            Self {
+                object_payload_length,
+                written: 0,
+            },
+            l,
+        ))
+    }
+
+    pub fn write_chunk<W: BufMut>(&mut self, chunk: &[u8], w: &mut W) -> Result<usize> {
+        let written = self.written + chunk.len() as u64;
+        if self
+            .object_payload_length
+            .is_some_and(|object_payload_length| written > object_payload_length)
+        {
+            return Err(Error::ErrInvalidObjectType(
+                "Payload exceeds the declared object length".to_string(),
+            ));
+        }
+        if w.remaining_mut() < chunk.len() {
+            return Err(Error::ErrBufferTooShort);
+        }
+        w.put_slice(chunk);
+        self.written = written;
+        Ok(chunk.len())
+    }
+
+    /// Ends the object. The caller closes the stream after an object with the
+    /// Object forwarding preference.
+    pub fn finish(self) -> Result<()> {
+        if self
+            .object_payload_length
+            .is_some_and(|object_payload_length| self.written != object_payload_length)
+        {
+            return Err(Error::ErrInvalidObjectType(
+                "Payload is shorter than the declared object length".to_string(),
+            ));
+        }
+        Ok(())
+    }
 }
@@ -1,4 +1,6 @@
-use crate::message::message_framer::MessageFramer;
+use crate::message::go_away::GoAway;
+use crate::message::message_framer::{MessageFramer, ObjectStreamWriter};
+use crate::message::message_parser::{MessageParser, MessageParserEvent};
 use crate::message::message_test::{
     create_test_message, MessageStructuredData, TestMessageBase, TestObjectDatagramMessage,
     TestStreamHeaderGroupMessage, TestStreamHeaderTrackMessage, TestStreamMiddlerGroupMessage,
@@ -7,8 +9,8 @@ use crate::message::message_test::{
 use crate::message::object::{ObjectForwardingPreference, ObjectHeader, ObjectStatus};
 use crate::message::subscribe::Subscribe;
 use crate::message::subscribe_update::SubscribeUpdate;
-use crate::message::{ControlMessage, FilterType, FullSequence, MessageType};
-use crate::{Error, Result};
+use crate::message::{ControlMessage, FilterType, FullSequence, MessageType, Priority, Version};
+use crate::{Error, Parameters, Result};
 use bytes::{BufMut, Bytes};
 use rstest::rstest;
 
@@ -205,6 +207,7 @@ fn test_bad_object_input() -> Result<()> {
         group_id: 5,
         object_id: 6,
         object_send_order: 7,
+        publisher_priority: Priority::default(),
         object_status: ObjectStatus::Normal,
         object_forwarding_preference: ObjectForwardingPreference::Object,
         object_payload_length: None,
@@ -241,6 +244,7 @@ fn test_datagram() -> Result<()> {
         group_id: 5,
         object_id: 6,
         object_send_order: 7,
+        publisher_priority: Priority::default(),
         object_status: ObjectStatus::Normal,
         object_forwarding_preference: ObjectForwardingPreference::Object,
         object_payload_length: None,
@@ -254,6 +258,55 @@ fn test_datagram() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_object_stream_round_trip() -> Result<()> {
+    let mut object = ObjectHeader {
+        subscribe_id: 3,
+        track_alias: 4,
+        group_id: 5,
+        object_id: 6,
+        object_send_order: 7,
+        publisher_priority: Priority::default(),
+        object_status: ObjectStatus::Normal,
+        object_forwarding_preference: ObjectForwardingPreference::Object,
+        object_payload_length: None,
+    };
+
+    for (status, payload) in [
+        (ObjectStatus::Normal, &b"foo"[..]),
+        (ObjectStatus::Normal, &b""[..]),
+        (ObjectStatus::EndOfGroup, &b""[..]),
+    ] {
+        object.object_status = status;
+        let mut buffer = MessageFramer::object_stream(&object, payload)?;
+
+        let mut parser = MessageParser::new(false);
+        parser.process_data(&mut buffer, true);
+        match parser.poll_event() {
+            Some(MessageParserEvent::ObjectMessage(header, actual_payload, end)) => {
+                assert_eq!(header, object);
+                assert_eq!(&actual_payload[..], payload);
+                assert!(end);
+            }
+            _ => unreachable!(),
+        }
+        assert!(matches!(
+            parser.poll_event(),
+            Some(MessageParserEvent::StreamClosed)
+        ));
+        assert!(parser.poll_event().is_none());
+    }
+
+    object.object_status = ObjectStatus::EndOfTrack;
+    assert_eq!(
+        MessageFramer::object_stream(&object, b"foo"),
+        Err(Error::ErrInvalidObjectType(
+            "Object status must be kNormal if payload is non-empty".to_string()
+        ))
+    );
+    Ok(())
+}
+
 #[test]
 fn test_all_subscribe_inputs() -> Result<()> {
     for start_group in [None, Some(4)] {
@@ -285,29 +338,14 @@ fn test_all_subscribe_inputs() -> Result<()> {
                         });
                     } else if start_group.is_some() && start_object.is_some() && end_group.is_some()
                     {
-                        if let Some(&end_object) = end_object.as_ref() {
-                            expected_filter_type = FilterType::AbsoluteRange(
-                                FullSequence {
-                                    group_id: start_group.unwrap(),
-                                    object_id: start_object.unwrap(),
-                                },
-                                FullSequence {
-                                    group_id: end_group.unwrap(),
-                                    object_id: end_object,
-                                },
-                            );
-                        } else {
-                            expected_filter_type = FilterType::AbsoluteRange(
-                                FullSequence {
-                                    group_id: start_group.unwrap(),
-                                    object_id: start_object.unwrap(),
-                                },
-                                FullSequence {
-                                    group_id: end_group.unwrap(),
-                                    object_id: u64::MAX,
-                                },
-                            );
-                        }
+                        expected_filter_type = FilterType::AbsoluteRange(
+                            FullSequence {
+                                group_id: start_group.unwrap(),
+                                object_id: start_object.unwrap(),
+                            },
+                            end_group.unwrap(),
+                            end_object,
+                        );
                     } else {
                         continue;
                     }
@@ -315,10 +353,11 @@ fn test_all_subscribe_inputs() -> Result<()> {
                     let subscribe = Subscribe {
                         subscribe_id: 3,
                         track_alias: 4,
-                        track_namespace: "foo".to_string(),
+                        track_namespace: "foo".into(),
                         track_name: "abcd".to_string(),
                         filter_type: expected_filter_type,
                         authorization_info: None,
+                        parameters: Parameters::new(),
                     };
                     let mut buffer = vec![];
                     let _ = MessageFramer::serialize_control_message(
@@ -329,7 +368,7 @@ fn test_all_subscribe_inputs() -> Result<()> {
                     let read = buffer[12];
                     assert_eq!(read, expected_filter_type.value());
                     assert!(!buffer.is_empty());
-                    if let FilterType::AbsoluteRange(_, _) = expected_filter_type {
+                    if let FilterType::AbsoluteRange(_, _, _) = expected_filter_type {
                         if let Some(&end_object) = end_object.as_ref() {
                             let object_id = buffer[12 + 4] as u64;
                             assert_eq!(object_id, end_object + 1);
@@ -347,19 +386,18 @@ fn test_subscribe_end_before_start() -> Result<()> {
     let mut subscribe = Subscribe {
         subscribe_id: 3,
         track_alias: 4,
-        track_namespace: "foo".to_string(),
+        track_namespace: "foo".into(),
         track_name: "abcd".to_string(),
         filter_type: FilterType::AbsoluteRange(
             FullSequence {
                 group_id: 4,
                 object_id: 4,
             },
-            FullSequence {
-                group_id: 3,
-                object_id: u64::MAX,
-            },
+            3,
+            None,
         ),
-        authorization_info: Some("bar".to_string()),
+        authorization_info: Some("bar".into()),
+        parameters: Parameters::new(),
     };
     let mut buffer = vec![];
     assert!(
@@ -376,10 +414,8 @@ fn test_subscribe_end_before_start() -> Result<()> {
             group_id: 4,
             object_id: 4,
         },
-        FullSequence {
-            group_id: 4,
-            object_id: 1,
-        },
+        4,
+        Some(1),
     );
     assert!(
         MessageFramer::serialize_control_message(
@@ -398,13 +434,14 @@ fn test_subscribe_latest_group_nonzero_object() -> Result<()> {
     let subscribe = Subscribe {
         subscribe_id: 3,
         track_alias: 4,
-        track_namespace: "foo".to_string(),
+        track_namespace: "foo".into(),
         track_name: "abcd".to_string(),
         filter_type: FilterType::AbsoluteStart(FullSequence {
             group_id: u64::MAX,
             object_id: 3,
         }),
-        authorization_info: Some("bar".to_string()),
+        authorization_info: Some("bar".into()),
+        parameters: Parameters::new(),
     };
     let mut buffer = vec![];
     assert!(
@@ -430,7 +467,9 @@ fn test_subscribe_update_end_group_only() -> Result<()> {
             group_id: 4,
             object_id: u64::MAX,
         }),
-        authorization_info: Some("bar".to_string()),
+        subscriber_priority: None,
+        authorization_info: Some("bar".into()),
+        parameters: Parameters::new(),
     };
     let mut buffer = vec![];
     let _ = MessageFramer::serialize_control_message(
@@ -457,7 +496,9 @@ fn test_subscribe_update_increments_end() -> Result<()> {
             group_id: 4,
             object_id: 6,
         }),
-        authorization_info: Some("bar".to_string()),
+        subscriber_priority: None,
+        authorization_info: Some("bar".into()),
+        parameters: Parameters::new(),
     };
     let mut buffer = vec![];
     let _ = MessageFramer::serialize_control_message(
@@ -484,7 +525,9 @@ fn test_subscribe_update_invalid_range() -> Result<()> {
             group_id: u64::MAX,
             object_id: 6,
         }),
-        authorization_info: Some("bar".to_string()),
+        subscriber_priority: None,
+        authorization_info: Some("bar".into()),
+        parameters: Parameters::new(),
     };
     let mut buffer = vec![];
     assert!(
@@ -497,3 +540,256 @@ fn test_subscribe_update_invalid_range() -> Result<()> {
     );
     Ok(())
 }
+
+#[rstest]
+#[case(Version::Draft04)]
+#[case(Version::Draft05)]
+fn test_object_priority_by_version(#[case] version: Version) -> Result<()> {
+    let object = ObjectHeader {
+        subscribe_id: 3,
+        track_alias: 4,
+        group_id: 5,
+        object_id: 6,
+        object_send_order: 7,
+        publisher_priority: Priority(8),
+        object_status: ObjectStatus::Normal,
+        object_forwarding_preference: ObjectForwardingPreference::Object,
+        object_payload_length: None,
+    };
+    // Each version carries only one of the two fields.
+    let mut expected = object;
+    if version.uses_publisher_priority() {
+        expected.object_send_order = 0;
+    } else {
+        expected.publisher_priority = Priority::default();
+    }
+
+    for preference in [
+        ObjectForwardingPreference::Object,
+        ObjectForwardingPreference::Track,
+        ObjectForwardingPreference::Group,
+    ] {
+        let mut object = object;
+        object.object_forwarding_preference = preference;
+        object.object_payload_length = Some(3);
+        let mut buffer = vec![];
+        MessageFramer::serialize_object_header_with_version(object, true, version, &mut buffer)?;
+        buffer.extend_from_slice(b"foo");
+
+        let mut parser = MessageParser::new(false);
+        parser.set_version(version);
+        parser.process_data(&mut buffer.as_slice(), true);
+        let event = core::iter::from_fn(|| parser.poll_event())
+            .find(|event| !matches!(event, MessageParserEvent::GroupStart(_)));
+        match event {
+            Some(MessageParserEvent::ObjectMessage(header, payload, true)) => {
+                assert_eq!(header.subscribe_id, expected.subscribe_id);
+                assert_eq!(header.track_alias, expected.track_alias);
+                assert_eq!(header.group_id, expected.group_id);
+                assert_eq!(header.object_id, expected.object_id);
+                assert_eq!(header.object_send_order, expected.object_send_order);
+                assert_eq!(header.publisher_priority, expected.publisher_priority);
+                assert_eq!(&payload[..], b"foo");
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    let mut buffer = vec![];
+    MessageFramer::serialize_object_datagram_with_version(
+        object,
+        Bytes::from_static(b"foo"),
+        version,
+        &mut buffer,
+    )?;
+    let (header, payload) =
+        MessageParser::process_datagram_with_version(&mut buffer.as_slice(), version)?;
+    assert_eq!(header.object_send_order, expected.object_send_order);
+    assert_eq!(header.publisher_priority, expected.publisher_priority);
+    assert_eq!(&payload[..], b"foo");
+
+    Ok(())
+}
+
+#[rstest]
+#[case(ObjectForwardingPreference::Object)]
+#[case(ObjectForwardingPreference::Track)]
+#[case(ObjectForwardingPreference::Group)]
+fn test_object_stream_writer(#[case] preference: ObjectForwardingPreference) -> Result<()> {
+    let chunks: [&[u8]; 3] = [b"foo", b"bar", b"bazz"];
+    let object = ObjectHeader {
+        subscribe_id: 3,
+        track_alias: 4,
+        group_id: 5,
+        object_id: 6,
+        object_send_order: 7,
+        publisher_priority: Priority::default(),
+        object_status: ObjectStatus::Normal,
+        object_forwarding_preference: preference,
+        object_payload_length: Some(10),
+    };
+
+    let mut buffer = vec![];
+    let (mut writer, _) = ObjectStreamWriter::begin(object, true, &mut buffer)?;
+    for chunk in chunks {
+        writer.write_chunk(chunk, &mut buffer)?;
+    }
+    writer.finish()?;
+
+    let mut parser = MessageParser::new(false);
+    parser.process_data(&mut buffer.as_slice(), true);
+    let mut payload = vec![];
+    while let Some(event) = parser.poll_event() {
+        match event {
+            MessageParserEvent::ObjectMessage(header, data, _) => {
+                assert_eq!(header.object_id, 6);
+                payload.extend_from_slice(&data);
+            }
+            MessageParserEvent::GroupStart(_) | MessageParserEvent::StreamClosed => {}
+            _ => panic!("expected an object"),
+        }
+    }
+    assert_eq!(payload, b"foobarbazz");
+
+    Ok(())
+}
+
+#[test]
+fn test_object_stream_writer_with_version() -> Result<()> {
+    let object = ObjectHeader {
+        subscribe_id: 3,
+        track_alias: 4,
+        group_id: 5,
+        object_id: 6,
+        publisher_priority: Priority(7),
+        object_forwarding_preference: ObjectForwardingPreference::Group,
+        object_payload_length: Some(3),
+        ..Default::default()
+    };
+
+    let mut buffer = vec![];
+    let (mut writer, _) =
+        ObjectStreamWriter::begin_with_version(object, true, Version::Draft05, &mut buffer)?;
+    writer.write_chunk(b"foo", &mut buffer)?;
+    writer.finish()?;
+
+    let mut parser = MessageParser::new(false);
+    parser.set_version(Version::Draft05);
+    parser.process_data(&mut buffer.as_slice(), true);
+    loop {
+        match parser.poll_event() {
+            Some(MessageParserEvent::ObjectMessage(header, payload, _)) => {
+                assert_eq!(header.publisher_priority, Priority(7));
+                assert_eq!(&payload[..], b"foo");
+                break;
+            }
+            Some(MessageParserEvent::GroupStart(_)) => {}
+            _ => panic!("expected an object"),
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_object_stream_writer_length_mismatch() -> Result<()> {
+    let object = ObjectHeader {
+        object_forwarding_preference: ObjectForwardingPreference::Group,
+        object_payload_length: Some(6),
+        ..Default::default()
+    };
+
+    let mut buffer = vec![];
+    let (mut writer, _) = ObjectStreamWriter::begin(object, true, &mut buffer)?;
+    writer.write_chunk(b"foo", &mut buffer)?;
+    assert!(writer.write_chunk(b"barbaz", &mut buffer).is_err());
+    assert!(writer.finish().is_err());
+
+    let mut object = object;
+    object.object_forwarding_preference = ObjectForwardingPreference::Datagram;
+    assert!(ObjectStreamWriter::begin(object, true, &mut buffer).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_stream_header_track_round_trip() -> Result<()> {
+    let first = ObjectHeader {
+        subscribe_id: 3,
+        track_alias: 4,
+        group_id: 5,
+        object_id: 6,
+        object_send_order: 7,
+        publisher_priority: Priority::default(),
+        object_status: ObjectStatus::Normal,
+        object_forwarding_preference: ObjectForwardingPreference::Track,
+        object_payload_length: None,
+    };
+    let second = ObjectHeader {
+        group_id: 8,
+        object_id: 0,
+        ..first
+    };
+
+    let mut buffer = vec![];
+    MessageFramer::serialize_object(first, true, Bytes::from_static(b"foo"), &mut buffer)?;
+    MessageFramer::serialize_object(second, false, Bytes::from_static(b"bazz"), &mut buffer)?;
+
+    let mut parser = MessageParser::new(false);
+    parser.process_data(&mut buffer.as_slice(), true);
+    let mut groups = vec![];
+    let mut objects = vec![];
+    while let Some(event) = parser.poll_event() {
+        match event {
+            MessageParserEvent::GroupStart(group_id) => groups.push(group_id),
+            MessageParserEvent::ObjectMessage(header, payload, true) => {
+                objects.push((header.group_id, header.object_id, payload))
+            }
+            MessageParserEvent::StreamClosed => {}
+            _ => panic!("expected a complete object"),
+        }
+    }
+    assert_eq!(groups, [5, 8]);
+    assert_eq!(
+        objects,
+        [
+            (5, 6, Bytes::from_static(b"foo")),
+            (8, 0, Bytes::from_static(b"bazz"))
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_go_away_follows_version() -> Result<()> {
+    for (version, new_session_uri, wire) in [
+        (
+            Version::Draft04,
+            Some("foo"),
+            &[0x10, 0x03, 0x66, 0x6f, 0x6f][..],
+        ),
+        (Version::Draft04, Some(""), &[0x10, 0x00][..]),
+        (Version::Draft02, None, &[0x10][..]),
+    ] {
+        let go_away = ControlMessage::GoAway(GoAway {
+            new_session_uri: new_session_uri.map(|uri| uri.to_string()),
+        });
+        let mut buffer = vec![];
+        MessageFramer::serialize_control_message_with_version(
+            go_away.clone(),
+            version,
+            &mut buffer,
+        )?;
+        assert_eq!(&buffer[..], wire);
+
+        let mut parser = MessageParser::new(false);
+        parser.set_version(version);
+        parser.process_data(&mut &buffer[..], false);
+        match parser.poll_event() {
+            Some(MessageParserEvent::ControlMessage(message)) => assert_eq!(message, go_away),
+            _ => panic!("expected GOAWAY"),
+        }
+    }
+    Ok(())
+}
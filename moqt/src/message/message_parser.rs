@@ -1,10 +1,15 @@
 use crate::message::object::{ObjectForwardingPreference, ObjectHeader, ObjectStatus};
-use crate::message::{ControlMessage, MessageType, MAX_MESSSAGE_HEADER_SIZE};
-use crate::serde::Deserializer;
+use crate::message::{
+    ControlMessage, MessageType, Priority, Role, StreamType, Version, MAX_MESSAGE_HEADER_SIZE,
+};
+use crate::serde::{checked_len, Deserializer};
 use crate::{Error, Result};
+use alloc::collections::{BTreeSet, VecDeque};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
 use bytes::{Buf, BufMut, Bytes, BytesMut};
-use std::collections::VecDeque;
-use std::fmt::{Display, Formatter};
+use core::fmt::{Display, Formatter};
 
 #[derive(Default, Debug, Copy, Clone, Eq, PartialEq)]
 pub enum ErrorCode {
@@ -19,23 +24,118 @@ pub enum ErrorCode {
 }
 
 impl Display for ErrorCode {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         write!(f, "{:?}", *self)
     }
 }
 
+impl ErrorCode {
+    /// The session error code sent on the wire when closing the session.
+    pub fn as_u64(&self) -> u64 {
+        *self as u64
+    }
+}
+
+/// Maps an error to the session error code used to close the session. Errors
+/// caused by malformed input from the peer are protocol violations, while
+/// local failures such as a short output buffer are internal errors.
+impl From<&Error> for ErrorCode {
+    fn from(err: &Error) -> Self {
+        match err.root() {
+            Error::ErrParseError(code, _) | Error::ErrStreamError(code, _) => *code,
+            Error::ErrParameterLengthMismatch { .. } => ErrorCode::ParameterLengthMismatch,
+            Error::ErrUnexpectedEnd
+            | Error::ErrMalformedVarInt
+            | Error::ErrNonCanonicalVarInt
+            | Error::ErrLengthOverflow
+            | Error::ErrDuplicateParameter(_)
+            | Error::ErrMissingParameter
+            | Error::ErrUnsupportedParameter(_)
+            | Error::ErrInvalidMessageType(_)
+            | Error::ErrInvalidFilterType(_)
+            | Error::ErrInvalidGroupOrder(_)
+            | Error::ErrInvalidPriority(_)
+            | Error::ErrInvalidBooleanValue(_)
+            | Error::ErrUnsupportedVersion(_)
+            | Error::ErrInvalidRole(_)
+            | Error::ErrInvalidErrorCode(_)
+            | Error::ErrInvalidObjectType(_)
+            | Error::ErrTrackGroupForwardPreferenceRequiresLength
+            | Error::ErrNonEmptyPayloadMustBeWithNormalObjectStatus
+            | Error::ErrFrameError(_)
+            | Error::ErrInvalidString(_)
+            | Error::ErrInvalidUtf8(_) => ErrorCode::ProtocolViolation,
+            Error::ErrVarIntBoundsExceeded
+            | Error::ErrBufferTooShort
+            | Error::ErrOther(_)
+            | Error::ErrStreamNotExisted
+            | Error::ErrStreamClosed
+            | Error::ErrAt { .. } => ErrorCode::InternalError,
+        }
+    }
+}
+
+impl From<Error> for ErrorCode {
+    fn from(err: Error) -> Self {
+        ErrorCode::from(&err)
+    }
+}
+
 pub enum MessageParserEvent {
     ParsingError(ErrorCode, String),
+    /// Precedes the first object of each group on a StreamHeaderGroup or
+    /// StreamHeaderTrack stream. A track stream starts a new group whenever an
+    /// object's group_id differs from the previous object's.
+    GroupStart(u64),
+    /// A length-prefixed control message of unknown type was skipped. len is
+    /// the declared length of the message.
+    UnknownControlMessage {
+        message_type: u64,
+        len: usize,
+    },
+    /// A length-prefixed message of a type registered with
+    /// [`MessageParser::register_custom_type`]. payload holds the bytes that
+    /// follow the type.
+    CustomMessage {
+        message_type: u64,
+        payload: Bytes,
+    },
     ObjectMessage(ObjectHeader, Bytes, bool),
     ControlMessage(ControlMessage),
+    /// The stream ended with a FIN and every message on it was parsed whole.
+    /// This is the last event; it is not sent after a parsing error.
+    StreamClosed,
 }
 
+// The header, payload length and end-of-message flag of an object whose
+// payload has been parsed but not yet taken from the input.
+type PendingPayload = Option<(ObjectHeader, usize, bool)>;
+
 pub struct MessageParser {
     uses_web_transport: bool,
+    // Each length-prefixed control message must fill its whole frame.
+    strict: bool,
+    // On raw QUIC, each stream starts with a stream-type varint.
+    expect_stream_type: bool,
+    stream_type: Option<StreamType>,
+    // The kind of stream the caller expects, which decides whether objects or
+    // control messages are accepted.
+    expected_stream_type: Option<StreamType>,
+    // Message types delivered raw as CustomMessage events.
+    custom_types: BTreeSet<u64>,
+    // Each control message is preceded by its varint length.
+    length_prefixed: bool,
+    // The negotiated version, which selects the object header and control
+    // message layouts. Until it is set, the Draft-04 layouts are used.
+    version: Option<Version>,
+    // The local role, which restricts the control messages accepted.
+    role: Option<Role>,
     no_more_data: bool, // Fatal error or fin. No more parsing.
     parsing_error: bool,
 
     buffered_message: BytesMut,
+    // The capacity the buffer is restored to on reset.
+    buffer_capacity: usize,
 
     // Metadata for an object which is delivered in parts.
     // If object_metadata_ is none, nothing has been processed on the stream.
@@ -49,25 +149,236 @@ pub struct MessageParser {
     // state straight.
     object_metadata: Option<ObjectHeader>,
     payload_length_remaining: usize,
+    // The group of the most recent object on a group or track stream.
+    current_group_id: Option<u64>,
+    // An object whose payload ends the message just parsed. The payload is
+    // taken from the input buffer once the message is consumed, so buffered
+    // data is handed off without a copy.
+    pending_payload: PendingPayload,
 
     parser_events: VecDeque<MessageParserEvent>,
+    // The buffered control message cannot be complete until this many bytes
+    // are buffered, as learned from a declared length that overran the input.
+    // Until then it is not decoded again.
+    incomplete_message_len: usize,
+    // Parsing pauses while this many events are queued.
+    max_events: Option<usize>,
+    // Parsing paused on a full event queue. buffered_message holds the bytes
+    // that have not been parsed yet.
+    backpressured: bool,
 }
 
 impl MessageParser {
     pub fn new(use_web_transport: bool) -> Self {
+        Self::with_buffer(use_web_transport, BytesMut::new())
+    }
+
+    /// As `new`, with [`MessageParser::set_strict`] applied.
+    pub fn with_strict(use_web_transport: bool, strict: bool) -> Self {
+        let mut parser = Self::new(use_web_transport);
+        parser.strict = strict;
+        parser
+    }
+
+    /// As `new`, but buffers split messages in `buffer`, for example one taken
+    /// from a pool. Its contents are discarded; its capacity is kept across
+    /// [`MessageParser::reset`] and returned by
+    /// [`MessageParser::into_buffer`].
+    pub fn with_buffer(use_web_transport: bool, mut buffer: BytesMut) -> Self {
+        buffer.clear();
         Self {
             uses_web_transport: use_web_transport,
+            strict: false,
+            expect_stream_type: false,
+            stream_type: None,
+            expected_stream_type: None,
+            custom_types: BTreeSet::new(),
+            length_prefixed: false,
+            version: None,
+            role: None,
             no_more_data: false,
             parsing_error: false,
 
-            buffered_message: Default::default(),
+            buffer_capacity: buffer.capacity(),
+            buffered_message: buffer,
             object_metadata: None,
             payload_length_remaining: 0,
+            current_group_id: None,
+            pending_payload: None,
 
             parser_events: VecDeque::new(),
+            incomplete_message_len: 0,
+            max_events: None,
+            backpressured: false,
         }
     }
 
+    /// Readies the parser for a new stream. Settings are kept; the stream
+    /// state, queued events and buffered input are dropped. The buffer keeps
+    /// its allocation unless an object payload handed out without a copy still
+    /// shares it.
+    pub fn reset(&mut self) {
+        self.stream_type = None;
+        self.no_more_data = false;
+        self.parsing_error = false;
+        self.reclaim_buffer();
+        self.object_metadata = None;
+        self.payload_length_remaining = 0;
+        self.current_group_id = None;
+        self.pending_payload = None;
+        self.parser_events.clear();
+        self.incomplete_message_len = 0;
+        self.backpressured = false;
+    }
+
+    /// Returns the empty buffer, with its capacity, for reuse by another
+    /// parser.
+    pub fn into_buffer(mut self) -> BytesMut {
+        self.reclaim_buffer();
+        self.buffered_message
+    }
+
+    fn reclaim_buffer(&mut self) {
+        // Consumed input advances the start of the buffer, so its capacity
+        // shrinks. Reserving on the empty buffer moves it back to the start of
+        // its allocation, or allocates afresh if that is still shared.
+        self.buffer_capacity = self.buffer_capacity.max(self.buffered_message.capacity());
+        self.buffered_message.clear();
+        self.buffered_message.reserve(self.buffer_capacity);
+    }
+
+    /// In strict mode, a length-prefixed control message that decodes in fewer
+    /// bytes than its declared length is a protocol violation. Otherwise the
+    /// rest of its frame is skipped. Messages split across calls to
+    /// process_data() are unaffected.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Expect each raw QUIC stream to start with a [`StreamType`] varint, which
+    /// is consumed and validated before any message. This has no effect when
+    /// the parser uses WebTransport, whose streams carry no prefix.
+    pub fn set_stream_type_prefix(&mut self, expect_stream_type: bool) {
+        self.expect_stream_type = expect_stream_type;
+    }
+
+    /// Declares whether this parser reads the control stream or a data stream.
+    /// Objects on a control stream, and control messages on a data stream, are
+    /// then protocol violations. A stream-type prefix, when in use, must agree.
+    /// Without either, both are accepted.
+    pub fn set_expected_stream_type(&mut self, stream_type: StreamType) {
+        self.expected_stream_type = Some(stream_type);
+    }
+
+    /// Returns the stream type declared with
+    /// [`MessageParser::set_expected_stream_type`], if any.
+    pub fn expected_stream_type(&self) -> Option<StreamType> {
+        self.expected_stream_type
+    }
+
+    /// The bytes held for a message that has not fully arrived.
+    #[cfg(test)]
+    pub(crate) fn buffered_message(&self) -> &BytesMut {
+        &self.buffered_message
+    }
+
+    /// How many bytes the buffered control message needs before it is decoded
+    /// again, or zero if that is not known.
+    #[cfg(test)]
+    pub(crate) fn incomplete_message_len(&self) -> usize {
+        self.incomplete_message_len
+    }
+
+    /// Expect each control message to be preceded by a varint holding its
+    /// length in bytes, as written by
+    /// `MessageFramer::serialize_length_prefixed_control_message`. The length
+    /// bounds parsing, and messages of unknown type are skipped. Only control
+    /// streams are framed this way, so objects are a protocol violation.
+    pub fn set_length_prefixed(&mut self, length_prefixed: bool) {
+        self.length_prefixed = length_prefixed;
+    }
+
+    /// Delivers length-prefixed messages of `message_type` as
+    /// [`MessageParserEvent::CustomMessage`] rather than skipping them, for
+    /// experimental or vendor messages. Only the length prefix can bound a
+    /// message this parser does not understand, so this has no effect without
+    /// [`MessageParser::set_length_prefixed`]. Types this crate knows are
+    /// decoded as usual.
+    pub fn register_custom_type(&mut self, message_type: u64) {
+        self.custom_types.insert(message_type);
+    }
+
+    /// Sets the negotiated version, which selects the object header layout
+    /// and the layout of version-dependent control messages: Draft-05 carries
+    /// a one-byte publisher priority where earlier drafts carry the object
+    /// send order, and adds the group order to SUBSCRIBE_OK. GOAWAY carries a
+    /// new session URI from Draft-03.
+    pub fn set_version(&mut self, version: Version) {
+        self.version = Some(version);
+    }
+
+    /// Returns the version set by [`MessageParser::set_version`], or None
+    /// before the session has negotiated one.
+    pub fn version(&self) -> Option<Version> {
+        self.version
+    }
+
+    // The layout for objects and version-dependent control messages.
+    fn wire_version(&self) -> Version {
+        self.version.unwrap_or(Version::Draft04)
+    }
+
+    /// Restricts the control messages accepted to those an endpoint in `role`
+    /// can receive, as decided by [`Role::receives`]. Any other message is a
+    /// protocol violation. Without a role, every message is accepted.
+    pub fn set_role(&mut self, role: Role) {
+        self.role = Some(role);
+    }
+
+    /// Bounds the number of queued events. Once `max_events` are queued, the
+    /// parser stops parsing further messages and buffers the remaining input
+    /// until [`MessageParser::poll_event`] drains the queue below the bound.
+    /// The remainder of an object payload already in progress is still
+    /// delivered, so the queue may briefly hold one more event.
+    pub fn set_max_events(&mut self, max_events: usize) {
+        self.max_events = Some(max_events);
+    }
+
+    /// Returns true while parsing is paused on a full event queue. The caller
+    /// should drain events before handing the parser more data.
+    pub fn is_backpressured(&self) -> bool {
+        self.backpressured
+    }
+
+    /// Returns the stream type read from the stream-type prefix, if any.
+    pub fn stream_type(&self) -> Option<StreamType> {
+        self.stream_type
+    }
+
+    /// Returns the type of the next buffered message without consuming any
+    /// input, or None until its type varint has been buffered. A pending
+    /// stream-type prefix or length prefix is skipped. Once an object stream
+    /// has started, the input holds objects rather than messages, so this is
+    /// always None.
+    pub fn peek_message_type(&self) -> Option<Result<MessageType>> {
+        if self.object_stream_initialized() {
+            return None;
+        }
+        let mut r = &self.buffered_message[..];
+        if self.expect_stream_type && !self.uses_web_transport && self.stream_type.is_none() {
+            match StreamType::deserialize(&mut r) {
+                Ok(_) => {}
+                Err(Error::ErrUnexpectedEnd) => return None,
+                Err(err) => return Some(Err(err)),
+            }
+        }
+        if self.length_prefixed {
+            u64::deserialize(&mut r).ok()?;
+        }
+        let (message_type, _) = u64::deserialize(&mut r).ok()?;
+        Some(MessageType::try_from(message_type))
+    }
+
     /// Take a buffer from the transport in |data|. Parse each complete message and
     /// call the appropriate visitor function. If |fin| is true, there
     /// is no more data arriving on the stream, so the parser will deliver any
@@ -76,6 +387,9 @@ impl MessageParser {
     /// error.
     /// Any calls after sending |fin| = true will be ignored.
     pub fn process_data<R: Buf>(&mut self, buf: &mut R, fin: bool) {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!("process_data", len = buf.remaining(), fin).entered();
+
         if self.no_more_data {
             self.parse_error(
                 ErrorCode::ProtocolViolation,
@@ -94,7 +408,7 @@ impl MessageParser {
                 );
                 return;
             }
-            if !self.buffered_message.is_empty() && !buf.has_remaining() {
+            if !self.buffered_message.is_empty() && !buf.has_remaining() && !self.backpressured {
                 self.parse_error(
                     ErrorCode::ProtocolViolation,
                     "End of stream before complete message".to_string(),
@@ -103,7 +417,58 @@ impl MessageParser {
             }
         }
 
-        self.buffered_message.put(buf);
+        if self.buffered_message.is_empty() && buf.chunk().len() == buf.remaining() {
+            // Nothing is buffered and the incoming data is contiguous, so parse it
+            // in place and only buffer the unconsumed tail.
+            self.process_input(buf, fin);
+            if buf.has_remaining() {
+                self.buffered_message.put(buf);
+            }
+        } else {
+            self.buffered_message.put(buf);
+            let mut buffered_message = core::mem::take(&mut self.buffered_message);
+            self.process_input(&mut buffered_message, fin);
+            self.buffered_message = buffered_message;
+        }
+
+        self.check_stream_closed();
+    }
+
+    /// Parses as many messages as possible out of |r|, which must be contiguous,
+    /// and advances it past the consumed bytes.
+    fn process_input<R: Buf>(&mut self, r: &mut R, fin: bool) {
+        if self.expect_stream_type && !self.uses_web_transport && self.stream_type.is_none() {
+            let mut st_reader = r.chunk();
+            match StreamType::deserialize(&mut st_reader) {
+                Ok((stream_type, stl)) => {
+                    if self
+                        .expected_stream_type
+                        .is_some_and(|expected| expected != stream_type)
+                    {
+                        self.parse_error(
+                            ErrorCode::ProtocolViolation,
+                            format!("Unexpected stream type {:?}", stream_type),
+                        );
+                        return;
+                    }
+                    self.stream_type = Some(stream_type);
+                    r.advance(stl);
+                }
+                Err(Error::ErrParseError(code, reason)) => {
+                    self.parse_error(code, reason);
+                    return;
+                }
+                Err(_) => {
+                    if fin {
+                        self.parse_error(
+                            ErrorCode::ProtocolViolation,
+                            "FIN before stream type".to_string(),
+                        );
+                    }
+                    return;
+                }
+            }
+        }
 
         // There are three cases: the parser has already delivered an OBJECT header
         // and is now delivering payload; part of a message is in the buffer; or
@@ -113,49 +478,59 @@ impl MessageParser {
                 // This is additional payload for an OBJECT.
                 if object_metadata.object_payload_length.is_none() {
                     // Deliver the data and exit.
-                    self.parser_events
-                        .push_back(MessageParserEvent::ObjectMessage(
-                            *object_metadata,
-                            self.buffered_message
-                                .copy_to_bytes(self.buffered_message.remaining()),
-                            fin,
-                        ));
+                    MessageParser::push_object(
+                        &mut self.parser_events,
+                        *object_metadata,
+                        r.copy_to_bytes(r.remaining()),
+                        fin,
+                    );
                     if fin {
                         self.object_metadata = None;
                     }
                     return;
                 }
-                if self.buffered_message.remaining() < self.payload_length_remaining {
+                if r.remaining() < self.payload_length_remaining {
                     // Does not finish the payload; deliver and exit.
-                    self.payload_length_remaining -= self.buffered_message.remaining();
-                    self.parser_events
-                        .push_back(MessageParserEvent::ObjectMessage(
-                            *object_metadata,
-                            self.buffered_message
-                                .copy_to_bytes(self.buffered_message.remaining()),
-                            false,
-                        ));
+                    self.payload_length_remaining -= r.remaining();
+                    MessageParser::push_object(
+                        &mut self.parser_events,
+                        *object_metadata,
+                        r.copy_to_bytes(r.remaining()),
+                        false,
+                    );
                     return;
                 }
                 // Finishes the payload. Deliver and continue.
-                self.parser_events
-                    .push_back(MessageParserEvent::ObjectMessage(
-                        *object_metadata,
-                        self.buffered_message
-                            .copy_to_bytes(self.payload_length_remaining),
-                        true,
-                    ));
+                MessageParser::push_object(
+                    &mut self.parser_events,
+                    *object_metadata,
+                    r.copy_to_bytes(self.payload_length_remaining),
+                    true,
+                );
                 self.payload_length_remaining = 0; // Expect a new object.
             }
         }
 
-        while self.buffered_message.has_remaining() {
-            let message_len = self.process_message(fin);
+        self.backpressured = false;
+        while r.has_remaining() {
+            if self
+                .max_events
+                .is_some_and(|max_events| self.parser_events.len() >= max_events)
+            {
+                self.backpressured = true;
+                break;
+            }
+            let message_len = if r.remaining() < self.incomplete_message_len {
+                0
+            } else {
+                self.process_message(r.chunk(), fin)
+            };
             if message_len == 0 {
-                if self.buffered_message.remaining() > MAX_MESSSAGE_HEADER_SIZE {
+                let max_message_size = self.max_message_size(r.chunk());
+                if r.remaining() > max_message_size {
                     self.parse_error(
                         ErrorCode::InternalError,
-                        "Cannot parse non-OBJECT messages > 2KB".to_string(),
+                        format!("Cannot parse messages > {} bytes", max_message_size),
                     );
                     return;
                 }
@@ -168,13 +543,34 @@ impl MessageParser {
                 }
                 break;
             }
-            self.buffered_message.advance(message_len);
+            self.incomplete_message_len = 0;
+            if let Some((object_header, payload_len, end_of_message)) = self.pending_payload.take()
+            {
+                r.advance(message_len - payload_len);
+                MessageParser::push_object(
+                    &mut self.parser_events,
+                    object_header,
+                    r.copy_to_bytes(payload_len),
+                    end_of_message,
+                );
+            } else {
+                r.advance(message_len);
+            }
         }
     }
 
     /// Provide a separate path for datagrams. Returns the ObjectHeader and payload bytes
     pub fn process_datagram<R: Buf>(r: &mut R) -> Result<(ObjectHeader, Bytes)> {
-        let (object_header, _) = MessageParser::parse_object_header(r)?;
+        MessageParser::process_datagram_with_version(r, Version::Draft04)
+    }
+
+    /// Like [`MessageParser::process_datagram`], for the object header layout
+    /// of `version`.
+    pub fn process_datagram_with_version<R: Buf>(
+        r: &mut R,
+        version: Version,
+    ) -> Result<(ObjectHeader, Bytes)> {
+        let (object_header, _) = MessageParser::parse_object_header(r, version)?;
         if object_header.object_forwarding_preference != ObjectForwardingPreference::Datagram {
             return Err(Error::ErrParseError(
                 ErrorCode::ProtocolViolation,
@@ -184,15 +580,88 @@ impl MessageParser {
         Ok((object_header, r.copy_to_bytes(r.remaining())))
     }
 
+    /// Parses each buffer as an independent OBJECT_DATAGRAM and queues an
+    /// ObjectMessage event for it. A malformed datagram queues a ParsingError
+    /// event instead, but unlike stream errors it does not stop the parser, so
+    /// the remaining datagrams are still delivered.
+    pub fn process_datagrams(&mut self, datagrams: impl Iterator<Item = Bytes>) {
+        for mut datagram in datagrams {
+            let event = match MessageParser::process_datagram_with_version(
+                &mut datagram,
+                self.wire_version(),
+            ) {
+                Ok((object_header, payload)) => {
+                    MessageParserEvent::ObjectMessage(object_header, payload, true)
+                }
+                Err(Error::ErrParseError(code, reason)) => {
+                    MessageParserEvent::ParsingError(code, reason)
+                }
+                Err(err) => MessageParserEvent::ParsingError(
+                    ErrorCode::ProtocolViolation,
+                    format!("Malformed OBJECT_DATAGRAM: {}", err),
+                ),
+            };
+            self.parser_events.push_back(event);
+        }
+    }
+
+    /// Returns true while the most recent object has delivered its header but
+    /// not all of its payload.
+    pub fn object_in_progress(&self) -> bool {
+        self.object_payload_in_progress()
+    }
+
+    /// Returns how many payload bytes of the current object are still to come:
+    /// zero when no object is in progress, and none when the object runs to the
+    /// end of the stream so its length is unknown.
+    pub fn payload_remaining(&self) -> Option<usize> {
+        if !self.object_payload_in_progress() {
+            return Some(0);
+        }
+        self.object_metadata
+            .as_ref()
+            .and_then(|object_metadata| object_metadata.object_payload_length)
+            .map(|_| self.payload_length_remaining)
+    }
+
     pub fn poll_event(&mut self) -> Option<MessageParserEvent> {
-        self.parser_events.pop_front()
+        let event = self.parser_events.pop_front();
+        if self.backpressured && !self.parsing_error {
+            // Resume parsing the input held back by a full queue.
+            let mut buffered_message = core::mem::take(&mut self.buffered_message);
+            self.process_input(&mut buffered_message, self.no_more_data);
+            self.buffered_message = buffered_message;
+            self.check_stream_closed();
+        }
+        event
+    }
+
+    /// Parses a complete stream held in memory, such as several control
+    /// messages, and returns every event. The buffer is treated as ending the
+    /// stream, so it must end on a message boundary. A parsing error is
+    /// returned as an error rather than an event, and the closing
+    /// `StreamClosed` is left out.
+    pub fn parse_all(&mut self, mut buf: Bytes) -> Result<Vec<MessageParserEvent>> {
+        self.process_data(&mut buf, true);
+        let mut events = Vec::new();
+        while let Some(event) = self.poll_event() {
+            match event {
+                MessageParserEvent::ParsingError(code, reason) => {
+                    return Err(Error::ErrParseError(code, reason))
+                }
+                MessageParserEvent::StreamClosed => {}
+                event => events.push(event),
+            }
+        }
+        Ok(events)
     }
 
-    fn process_message(&mut self, fin: bool) -> usize {
+    fn process_message(&mut self, data: &[u8], fin: bool) -> usize {
         if self.object_stream_initialized() && !self.object_payload_in_progress() {
             // This is a follow-on object in a stream.
             if let Some(object_metadata) = self.object_metadata.as_ref() {
                 return self.process_object(
+                    data,
                     object_metadata
                         .object_forwarding_preference
                         .get_message_type(),
@@ -200,7 +669,18 @@ impl MessageParser {
                 );
             }
         }
-        let mut mt_reader = self.buffered_message.as_ref();
+        if self.length_prefixed {
+            // Every frame belongs on the control stream.
+            if self.expected_stream_type.or(self.stream_type) == Some(StreamType::Data) {
+                self.parse_error(
+                    ErrorCode::ProtocolViolation,
+                    "Received control message on data stream".to_string(),
+                );
+                return 0;
+            }
+            return self.process_framed_message(data);
+        }
+        let mut mt_reader = data;
         let message_type = match MessageType::deserialize(&mut mt_reader) {
             Ok((message_type, _)) => message_type,
             Err(err) => {
@@ -211,85 +691,235 @@ impl MessageParser {
             }
         };
 
-        if message_type == MessageType::ObjectDatagram {
+        match (
+            self.expected_stream_type.or(self.stream_type),
+            message_type.is_object_type(),
+        ) {
+            (Some(StreamType::Control), true) => {
+                self.parse_error(
+                    ErrorCode::ProtocolViolation,
+                    "Received object on control stream".to_string(),
+                );
+                return 0;
+            }
+            (Some(StreamType::Data), false) => {
+                self.parse_error(
+                    ErrorCode::ProtocolViolation,
+                    "Received control message on data stream".to_string(),
+                );
+                return 0;
+            }
+            _ => {}
+        }
+
+        if message_type.is_object_type() {
+            self.process_object(data, message_type, fin)
+        } else {
+            let Some((control_message, message_len)) = self.decode_control_message(data, false)
+            else {
+                return 0;
+            };
+            self.push_control_message(control_message, message_len);
+            message_len
+        }
+    }
+
+    // With length-prefixed framing, each control message is preceded by its
+    // length in bytes. The length bounds the message, and frames of unknown
+    // type are skipped whole and reported.
+    fn process_framed_message(&mut self, data: &[u8]) -> usize {
+        let mut reader = data;
+        let Ok((length, ll)) = usize::deserialize(&mut reader) else {
+            return 0;
+        };
+        let Some(frame) = reader.get(..length) else {
+            return 0; // Incomplete frame.
+        };
+        let mut mt_reader = frame;
+        let Ok((message_type, _)) = u64::deserialize(&mut mt_reader) else {
             self.parse_error(
                 ErrorCode::ProtocolViolation,
-                "Received OBJECT_DATAGRAM on strea".to_string(),
+                "Empty control message frame".to_string(),
             );
-            0
-        } else if message_type == MessageType::ObjectStream
-            || message_type == MessageType::StreamHeaderTrack
-            || message_type == MessageType::StreamHeaderGroup
-        {
-            self.process_object(message_type, fin)
-        } else {
-            let mut msg_reader = self.buffered_message.as_ref();
-            let (control_message, message_len) = match ControlMessage::deserialize(&mut msg_reader)
-            {
-                Ok((mut control_message, message_len)) => {
-                    if let ControlMessage::ClientSetup(client_setup) = &mut control_message {
-                        if self.uses_web_transport && client_setup.path.is_some() {
-                            self.parse_error(
-                                ErrorCode::ProtocolViolation,
-                                "WebTransport connection is using PATH parameter in SETUP"
-                                    .to_string(),
-                            );
-                            return 0;
-                        } else if !self.uses_web_transport && client_setup.path.is_none() {
-                            self.parse_error(
-                                ErrorCode::ProtocolViolation,
-                                "PATH SETUP parameter missing from Client message over QUIC"
-                                    .to_string(),
-                            );
-                            return 0;
-                        }
-                        client_setup.uses_web_transport = self.uses_web_transport;
-                    }
+            return 0;
+        };
+        match MessageType::try_from(message_type) {
+            Err(_) if self.custom_types.contains(&message_type) => {
+                self.parser_events
+                    .push_back(MessageParserEvent::CustomMessage {
+                        message_type,
+                        payload: Bytes::copy_from_slice(mt_reader),
+                    });
+                ll + length
+            }
+            Err(_) => {
+                self.parser_events
+                    .push_back(MessageParserEvent::UnknownControlMessage {
+                        message_type,
+                        len: length,
+                    });
+                ll + length
+            }
+            Ok(message_type) if message_type.is_object_type() => {
+                self.parse_error(
+                    ErrorCode::ProtocolViolation,
+                    "Received OBJECT in a length-prefixed frame".to_string(),
+                );
+                0
+            }
+            Ok(_) => {
+                let Some((control_message, message_len)) = self.decode_control_message(frame, true)
+                else {
+                    self.parse_error(
+                        ErrorCode::ProtocolViolation,
+                        "Control message length mismatch".to_string(),
+                    );
+                    return 0;
+                };
+                if message_len != length && self.strict {
+                    self.parse_error(
+                        ErrorCode::ProtocolViolation,
+                        "Trailing bytes after control message".to_string(),
+                    );
+                    return 0;
+                }
+                self.push_control_message(control_message, message_len);
+                ll + length
+            }
+        }
+    }
 
-                    (control_message, message_len)
+    // Decodes the control message at the start of data. Returns none if the
+    // message is incomplete or invalid; the latter also reports a parse error.
+    // When data holds the whole message, a parameter running past its end is
+    // reported rather than waited on.
+    fn decode_control_message(
+        &mut self,
+        data: &[u8],
+        bounded: bool,
+    ) -> Option<(ControlMessage, usize)> {
+        let mut msg_reader = data;
+        match ControlMessage::deserialize_with_version(self.wire_version(), &mut msg_reader) {
+            Ok((mut control_message, message_len)) => {
+                if let Some(role) = self.role {
+                    let message_type = control_message.message_type();
+                    if !role.receives(message_type) {
+                        self.parse_error(
+                            ErrorCode::ProtocolViolation,
+                            format!("Received {:?} as {:?}", message_type, role),
+                        );
+                        return None;
+                    }
                 }
-                Err(err) => {
-                    if let Error::ErrParseError(code, reason) = err {
-                        self.parse_error(code, reason);
+                if let ControlMessage::ClientSetup(client_setup) = &mut control_message {
+                    if self.uses_web_transport && client_setup.path.is_some() {
+                        self.parse_error(
+                            ErrorCode::ProtocolViolation,
+                            "WebTransport connection is using PATH parameter in SETUP".to_string(),
+                        );
+                        return None;
+                    } else if !self.uses_web_transport && client_setup.path.is_none() {
+                        self.parse_error(
+                            ErrorCode::ProtocolViolation,
+                            "PATH SETUP parameter missing from Client message over QUIC"
+                                .to_string(),
+                        );
+                        return None;
                     }
-                    return 0;
+                    client_setup.uses_web_transport = self.uses_web_transport;
                 }
-            };
-            self.parser_events
-                .push_back(MessageParserEvent::ControlMessage(control_message));
-            message_len
+
+                Some((control_message, message_len))
+            }
+            Err(err) => {
+                match err {
+                    Error::ErrParseError(code, reason) => self.parse_error(code, reason),
+                    Error::ErrParameterLengthMismatch { .. } if bounded => self.parse_error(
+                        ErrorCode::ParameterLengthMismatch,
+                        "Parameter length exceeds message length".to_string(),
+                    ),
+                    // The parameter value starts where decoding stopped, so
+                    // skip decoding again until all of it has arrived.
+                    Error::ErrParameterLengthMismatch { len, .. } => {
+                        let offset = data.len() - msg_reader.len();
+                        self.incomplete_message_len = offset.saturating_add(len);
+                    }
+                    _ => {}
+                }
+                None
+            }
         }
     }
 
-    fn process_object(&mut self, message_type: MessageType, fin: bool) -> usize {
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    fn push_control_message(&mut self, control_message: ControlMessage, message_len: usize) {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            message_type = ?control_message.message_type(),
+            len = message_len,
+            "control message"
+        );
+        self.parser_events
+            .push_back(MessageParserEvent::ControlMessage(control_message));
+    }
+
+    fn process_object(&mut self, data: &[u8], message_type: MessageType, fin: bool) -> usize {
         let mut processed_data = 0;
         assert!(!self.object_payload_in_progress());
         if !self.object_stream_initialized() {
-            let mut oh_reader = self.buffered_message.as_ref();
-            let (object_metadata, obl) = match MessageParser::parse_object_header(&mut oh_reader) {
-                Ok((object_metadata, obl)) => (object_metadata, obl),
-                Err(err) => {
-                    if let Error::ErrParseError(code, reason) = err {
+            let mut oh_reader = data;
+            let (object_metadata, obl) =
+                match MessageParser::parse_object_header(&mut oh_reader, self.wire_version()) {
+                    Ok((object_metadata, obl)) => (object_metadata, obl),
+                    // The header spans reads. Nothing is kept from this attempt; the
+                    // bytes stay buffered and the header is parsed again whole once
+                    // more arrive.
+                    Err(Error::ErrUnexpectedEnd) => return 0,
+                    Err(Error::ErrParseError(code, reason)) => {
                         self.parse_error(code, reason);
+                        return 0;
                     }
-                    return 0;
-                }
-            };
+                    Err(err) => {
+                        self.parse_error(
+                            ErrorCode::from(&err),
+                            format!("Malformed object header: {}", err),
+                        );
+                        return 0;
+                    }
+                };
+            // Datagram objects never travel on a stream, so there is no payload
+            // state to enter for one.
+            if object_metadata.object_forwarding_preference == ObjectForwardingPreference::Datagram
+            {
+                self.parse_error(
+                    ErrorCode::ProtocolViolation,
+                    "Received OBJECT_DATAGRAM on stream".to_string(),
+                );
+                return 0;
+            }
             self.object_metadata = Some(object_metadata);
             processed_data += obl;
         }
 
-        let mut payload_reader = &self.buffered_message.as_ref()[processed_data..];
-        match MessageParser::process_object_payload(
-            &mut self.parser_events,
-            &mut self.object_metadata,
-            &mut self.payload_length_remaining,
-            &mut payload_reader,
-            message_type,
-            fin,
-        ) {
-            Ok(prl) => {
+        // The header length comes from parsing data, but never trust it to index.
+        let payload = data
+            .get(processed_data..)
+            .ok_or(Error::ErrUnexpectedEnd)
+            .and_then(|mut payload_reader| {
+                MessageParser::process_object_payload(
+                    &mut self.parser_events,
+                    &mut self.object_metadata,
+                    &mut self.payload_length_remaining,
+                    &mut self.current_group_id,
+                    &mut payload_reader,
+                    message_type,
+                    fin,
+                )
+            });
+        match payload {
+            Ok((prl, pending_payload)) => {
                 processed_data += prl;
+                self.pending_payload = pending_payload;
             }
             Err(err) => {
                 if let Error::ErrParseError(code, reason) = err {
@@ -301,7 +931,7 @@ impl MessageParser {
         processed_data
     }
 
-    fn parse_object_header<R: Buf>(r: &mut R) -> Result<(ObjectHeader, usize)> {
+    fn parse_object_header<R: Buf>(r: &mut R, version: Version) -> Result<(ObjectHeader, usize)> {
         let (message_type, mtl) = MessageType::deserialize(r)?;
         let (subscribe_id, sil) = u64::deserialize(r)?;
         let (track_alias, tal) = u64::deserialize(r)?;
@@ -317,7 +947,13 @@ impl MessageParser {
         } else {
             (0, 0)
         };
-        let (object_send_order, osol) = u64::deserialize(r)?;
+        let (object_send_order, publisher_priority, osol) = if version.uses_publisher_priority() {
+            let (publisher_priority, ppl) = Priority::deserialize(r)?;
+            (0, publisher_priority, ppl)
+        } else {
+            let (object_send_order, osol) = u64::deserialize(r)?;
+            (object_send_order, Priority::default(), osol)
+        };
         let (status, osl) = if message_type == MessageType::ObjectStream
             || message_type == MessageType::ObjectDatagram
         {
@@ -336,11 +972,12 @@ impl MessageParser {
                 group_id,
                 object_id,
                 object_send_order,
+                publisher_priority,
                 object_status,
                 object_forwarding_preference,
                 object_payload_length: None,
             },
-            mtl + sil + tal + gil + oil + osol + osl,
+            checked_len(&[mtl, sil, tal, gil, oil, osol, osl])?,
         ))
     }
 
@@ -348,14 +985,16 @@ impl MessageParser {
         parser_events: &mut VecDeque<MessageParserEvent>,
         object_header: &mut Option<ObjectHeader>,
         payload_length_remaining: &mut usize,
+        current_group_id: &mut Option<u64>,
         r: &mut R,
         message_type: MessageType,
         fin: bool,
-    ) -> Result<usize> {
+    ) -> Result<(usize, PendingPayload)> {
         // At this point, enough data has been processed to store in object_metadata_,
         // even if there's nothing else in the buffer.
         assert!(*payload_length_remaining == 0);
         let mut total_len = 0;
+        let mut pending_payload = None;
         if message_type == MessageType::StreamHeaderTrack {
             let (group_id, gil) = u64::deserialize(r)?;
             total_len += gil;
@@ -383,6 +1022,12 @@ impl MessageParser {
                 object_metadata.object_id = object_id;
                 object_metadata.object_payload_length = Some(object_payload_length);
                 object_metadata.object_status = status.into();
+
+                if *current_group_id != Some(object_metadata.group_id) {
+                    *current_group_id = Some(object_metadata.group_id);
+                    parser_events
+                        .push_back(MessageParserEvent::GroupStart(object_metadata.group_id));
+                }
             }
         }
 
@@ -405,12 +1050,8 @@ impl MessageParser {
                         "Object with non-normal status has payload".to_string(),
                     ));
                 }
-                parser_events.push_back(MessageParserEvent::ObjectMessage(
-                    *object_metadata,
-                    Bytes::new(),
-                    true,
-                ));
-                return Ok(total_len);
+                MessageParser::push_object(parser_events, *object_metadata, Bytes::new(), true);
+                return Ok((total_len, None));
             }
 
             let has_length = object_metadata.object_payload_length.is_some();
@@ -437,11 +1078,8 @@ impl MessageParser {
             // message is "done" if fin regardless of has_length, it's bad to report to
             // the application that the object is done if it hasn't reached the promised
             // length.
-            parser_events.push_back(MessageParserEvent::ObjectMessage(
-                *object_metadata,
-                r.copy_to_bytes(payload_to_draw),
-                received_complete_message,
-            ));
+            pending_payload = Some((*object_metadata, payload_to_draw, received_complete_message));
+            r.advance(payload_to_draw);
             *payload_length_remaining = if has_length {
                 payload_length - payload_to_draw
             } else {
@@ -451,19 +1089,74 @@ impl MessageParser {
             total_len += payload_to_draw;
         }
 
-        Ok(total_len)
+        Ok((total_len, pending_payload))
+    }
+
+    fn push_object(
+        parser_events: &mut VecDeque<MessageParserEvent>,
+        object_header: ObjectHeader,
+        payload: Bytes,
+        end_of_message: bool,
+    ) {
+        #[cfg(feature = "tracing")]
+        tracing::trace!(
+            subscribe_id = object_header.subscribe_id,
+            group_id = object_header.group_id,
+            object_id = object_header.object_id,
+            status = ?object_header.object_status,
+            payload_len = payload.len(),
+            end_of_message,
+            "object"
+        );
+        parser_events.push_back(MessageParserEvent::ObjectMessage(
+            object_header,
+            payload,
+            end_of_message,
+        ));
+    }
+
+    // Once a FIN has been processed, reports a clean close if nothing is left
+    // partly parsed or held back by a full queue. An object without a length
+    // runs to the FIN, so it is complete by now.
+    fn check_stream_closed(&mut self) {
+        if self.no_more_data
+            && !self.parsing_error
+            && !self.backpressured
+            && self.buffered_message.is_empty()
+            && self.payload_length_remaining == 0
+        {
+            self.parser_events
+                .push_back(MessageParserEvent::StreamClosed);
+        }
     }
 
     fn parse_error(&mut self, error_code: ErrorCode, error_reason: String) {
         if self.parsing_error {
             return; // Don't send multiple parse errors.
         }
+        #[cfg(feature = "tracing")]
+        tracing::warn!(code = %error_code, reason = %error_reason, "parse error");
         self.no_more_data = true;
         self.parsing_error = true;
         self.parser_events
             .push_back(MessageParserEvent::ParsingError(error_code, error_reason));
     }
 
+    // Returns the buffering limit for the incomplete message at the start of
+    // data. Follow-on objects and messages whose type cannot be read yet fall
+    // back to the default limit.
+    fn max_message_size(&self, mut data: &[u8]) -> usize {
+        if self.object_stream_initialized() {
+            return MAX_MESSAGE_HEADER_SIZE;
+        }
+        if self.length_prefixed && usize::deserialize(&mut data).is_err() {
+            return MAX_MESSAGE_HEADER_SIZE;
+        }
+        MessageType::deserialize(&mut data).map_or(MAX_MESSAGE_HEADER_SIZE, |(message_type, _)| {
+            message_type.max_message_size()
+        })
+    }
+
     // Simplify understanding of state.
     // Returns true if the stream has delivered all object metadata common to all
     // objects on that stream.
@@ -478,8 +1171,6 @@ impl MessageParser {
             object_metadata.object_status == ObjectStatus::Normal
                 && (object_metadata.object_forwarding_preference
                     == ObjectForwardingPreference::Object
-                    || object_metadata.object_forwarding_preference
-                        == ObjectForwardingPreference::Datagram
                     || self.payload_length_remaining > 0)
         } else {
             false
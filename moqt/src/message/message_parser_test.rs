@@ -1,16 +1,23 @@
+use crate::message::message_framer::MessageFramer;
 use crate::message::message_parser::{ErrorCode, MessageParser, MessageParserEvent};
 use crate::message::message_test::{
     create_test_message, MessageStructuredData, TestMessageBase, TestObjectDatagramMessage,
     TestObjectStreamMessage, TestStreamHeaderGroupMessage, TestStreamHeaderTrackMessage,
     TestStreamMiddlerGroupMessage, TestStreamMiddlerTrackMessage, TestSubscribeDoneMessage,
-    TestSubscribeOkMessage,
+    TestSubscribeMessage, TestSubscribeOkMessage,
 };
-use crate::message::object::ObjectHeader;
-use crate::message::{ControlMessage, FilterType, MessageType, MAX_MESSSAGE_HEADER_SIZE};
-use crate::{Error, Result, Serializer};
-use bytes::Bytes;
+use crate::message::object::{ObjectForwardingPreference, ObjectHeader, ObjectStatus};
+use crate::message::subscribe::Subscribe;
+use crate::message::subscribe_done::{SubscribeDone, SubscribeDoneCode};
+use crate::message::subscribe_ok::SubscribeOk;
+use crate::message::{
+    ControlMessage, FilterType, FullSequence, GroupOrder, MessageType, Priority, Role, StreamType,
+    Version,
+};
+use crate::{Error, Parameters, Result, Serializer};
+use bytes::{BufMut, Bytes, BytesMut};
+use core::fmt::{Display, Formatter};
 use rstest::rstest;
-use std::fmt::{Display, Formatter};
 
 struct TestParserParams {
     message_type: MessageType,
@@ -18,7 +25,7 @@ struct TestParserParams {
 }
 
 impl Display for TestParserParams {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         write!(
             f,
             "{:?}_{}",
@@ -48,6 +55,8 @@ struct TestParserVisitor {
     parsing_error_code: ErrorCode,
     messages_received: u64,
     last_message: Option<MessageStructuredData>,
+    groups_started: Vec<u64>,
+    stream_closed: bool,
 }
 
 impl TestParserVisitor {
@@ -59,6 +68,8 @@ impl TestParserVisitor {
             parsing_error_code: ErrorCode::NoError,
             messages_received: 0,
             last_message: None,
+            groups_started: vec![],
+            stream_closed: false,
         }
     }
 
@@ -69,6 +80,10 @@ impl TestParserVisitor {
                 self.on_object_message(message, payload, end_of_message)
             }
             MessageParserEvent::ControlMessage(message) => self.on_control_message(message),
+            MessageParserEvent::GroupStart(group_id) => self.groups_started.push(group_id),
+            MessageParserEvent::UnknownControlMessage { .. }
+            | MessageParserEvent::CustomMessage { .. } => {}
+            MessageParserEvent::StreamClosed => self.stream_closed = true,
         }
     }
 
@@ -163,7 +178,7 @@ fn test_parse_one_message(params: (MessageType, bool)) -> Result<()> {
         "message type {:?}",
         tester.message_type
     );
-    if tester.message_type.is_object_message() {
+    if tester.message_type.is_object_type() {
         // Check payload message.
         assert!(
             tester.visitor.object_payload.is_some(),
@@ -236,7 +251,7 @@ fn test_one_message_with_long_varints(params: (MessageType, bool)) -> Result<()>
         "message type {:?}",
         tester.message_type
     );
-    if tester.message_type.is_object_message() {
+    if tester.message_type.is_object_type() {
         // Check payload message.
         assert_eq!(
             "foo",
@@ -321,7 +336,7 @@ fn test_two_part_message(params: (MessageType, bool)) -> Result<()> {
         "message type {:?}",
         tester.message_type
     );
-    if tester.message_type.is_object_message() {
+    if tester.message_type.is_object_type() {
         assert_eq!(
             "foo",
             tester.visitor.object_payload.as_ref().unwrap(),
@@ -374,7 +389,7 @@ fn test_one_byte_at_atime(params: (MessageType, bool)) -> Result<()> {
     let total_message_size = message.packet_sample().len();
     let object_payload_size = 3;
     for i in 0..total_message_size {
-        if !tester.message_type.is_object_message() {
+        if !tester.message_type.is_object_type() {
             assert_eq!(
                 0, tester.visitor.messages_received,
                 "message type {:?} at {}-th byte of {} bytes",
@@ -394,7 +409,7 @@ fn test_one_byte_at_atime(params: (MessageType, bool)) -> Result<()> {
         }
     }
     assert_eq!(
-        if tester.message_type.is_object_message() {
+        if tester.message_type.is_object_type() {
             object_payload_size + 1
         } else {
             1
@@ -478,7 +493,7 @@ fn test_one_byte_at_a_time_longer_varints(params: (MessageType, bool)) -> Result
     let total_message_size = message.packet_sample().len();
     let object_payload_size = 3;
     for i in 0..total_message_size {
-        if !tester.message_type.is_object_message() {
+        if !tester.message_type.is_object_type() {
             assert_eq!(
                 0, tester.visitor.messages_received,
                 "message type {:?}",
@@ -498,7 +513,7 @@ fn test_one_byte_at_a_time_longer_varints(params: (MessageType, bool)) -> Result
         }
     }
     assert_eq!(
-        if tester.message_type.is_object_message() {
+        if tester.message_type.is_object_type() {
             object_payload_size + 1
         } else {
             1
@@ -1360,6 +1375,134 @@ fn test_non_normal_object_has_payload() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_end_of_track_object_has_payload() -> Result<()> {
+    let mut tester = TestMessageSpecific::new();
+    let mut parser = MessageParser::new(K_RAW_QUIC);
+    let object_stream = vec![
+        0x00, 0x03, 0x04, 0x05, 0x06, 0x07, 0x04, // varints, status = EndOfTrack
+        0x66, 0x6f, 0x6f, // payload = "foo"
+    ];
+    parser.process_data(&mut &object_stream[..], false);
+    while let Some(event) = parser.poll_event() {
+        tester.visitor.handle_event(event);
+    }
+    assert_eq!(tester.visitor.messages_received, 0);
+    assert_eq!(
+        tester.visitor.parsing_error,
+        Some("Object with non-normal status has payload".to_string())
+    );
+    assert_eq!(
+        tester.visitor.parsing_error_code,
+        ErrorCode::ProtocolViolation
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_end_of_track_object_without_payload() -> Result<()> {
+    let mut tester = TestMessageSpecific::new();
+    let mut parser = MessageParser::new(K_RAW_QUIC);
+    let object_stream = [
+        0x00, 0x03, 0x04, 0x05, 0x06, 0x07, 0x04, // varints, status = EndOfTrack
+    ];
+    parser.process_data(&mut &object_stream[..], true);
+    while let Some(event) = parser.poll_event() {
+        tester.visitor.handle_event(event);
+    }
+    assert_eq!(tester.visitor.messages_received, 1);
+    assert!(tester.visitor.end_of_message);
+    assert_eq!(tester.visitor.object_payload, Some(Bytes::new()));
+    if let Some(MessageStructuredData::Object(object_header)) = tester.visitor.last_message {
+        assert_eq!(object_header.object_status, ObjectStatus::EndOfTrack);
+    } else {
+        unreachable!();
+    }
+    assert!(tester.visitor.parsing_error.is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_stream_header_group_end_of_group() -> Result<()> {
+    let stream_header_group = [
+        0x40, 0x51, // two-byte type field
+        0x03, 0x04, 0x05, 0x07, // varints
+        0x00, 0x03, 0x66, 0x6f, 0x6f, // object 0, payload = "foo"
+        0x01, 0x00, 0x03, // object 1, status = EndOfGroup
+    ];
+    for splits in [vec![], vec![8], vec![11], vec![13]] {
+        let mut parser = MessageParser::new(K_RAW_QUIC);
+        let events = feed_with_splits(&mut parser, &stream_header_group, &splits);
+        assert_eq!(events.len(), 2);
+        match (&events[0], &events[1]) {
+            (
+                LifecycleEvent::Object(normal, payload),
+                LifecycleEvent::Object(end_of_group, marker),
+            ) => {
+                assert_eq!(normal.object_id, 0);
+                assert_eq!(normal.object_status, ObjectStatus::Normal);
+                assert_eq!(payload.as_ref(), b"foo");
+                assert_eq!(end_of_group.group_id, 5);
+                assert_eq!(end_of_group.object_id, 1);
+                assert_eq!(end_of_group.object_status, ObjectStatus::EndOfGroup);
+                assert_eq!(end_of_group.object_payload_length, Some(0));
+                assert!(marker.is_empty());
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_group_start() -> Result<()> {
+    let stream_header_track = [
+        0x40, 0x50, // two-byte type field
+        0x03, 0x04, 0x07, // varints
+        0x05, 0x00, 0x03, 0x66, 0x6f, 0x6f, // group 5, object 0, payload = "foo"
+        0x05, 0x01, 0x03, 0x62, 0x61, 0x72, // group 5, object 1, payload = "bar"
+        0x06, 0x00, 0x03, 0x62, 0x61, 0x7a, // group 6, object 0, payload = "baz"
+    ];
+    for split in 0..stream_header_track.len() {
+        let mut tester = TestMessageSpecific::new();
+        let mut parser = MessageParser::new(K_RAW_QUIC);
+        parser.process_data(&mut &stream_header_track[..split], false);
+        parser.process_data(&mut &stream_header_track[split..], true);
+        let mut groups = vec![];
+        while let Some(event) = parser.poll_event() {
+            if let MessageParserEvent::GroupStart(group_id) = event {
+                // Each group starts before its first object is delivered.
+                assert!(tester.visitor.last_message.as_ref().is_none_or(|message| {
+                    matches!(message, MessageStructuredData::Object(header) if header.group_id < group_id)
+                }));
+                groups.push(group_id);
+            } else {
+                tester.visitor.handle_event(event);
+            }
+        }
+        assert_eq!(groups, vec![5, 6], "split {}", split);
+        assert!(tester.visitor.parsing_error.is_none());
+    }
+
+    // A group stream starts exactly one group.
+    let mut tester = TestMessageSpecific::new();
+    let mut parser = MessageParser::new(K_RAW_QUIC);
+    let message = TestStreamHeaderGroupMessage::new();
+    parser.process_data(&mut message.packet_sample(), false);
+    let middler = TestStreamMiddlerGroupMessage::new();
+    parser.process_data(&mut middler.packet_sample(), false);
+    while let Some(event) = parser.poll_event() {
+        tester.visitor.handle_event(event);
+    }
+    assert_eq!(tester.visitor.messages_received, 2);
+    assert_eq!(tester.visitor.groups_started, vec![5]);
+
+    Ok(())
+}
+
 #[test]
 fn test_invalid_object_status() -> Result<()> {
     let mut tester = TestMessageSpecific::new();
@@ -1386,16 +1529,17 @@ fn test_invalid_object_status() -> Result<()> {
 }
 
 #[test]
-fn test_setup2kb() -> Result<()> {
+fn test_setup_exceeds_max_message_size() -> Result<()> {
     let mut tester = TestMessageSpecific::new();
     let mut parser = MessageParser::new(K_RAW_QUIC);
+    let max_message_size = MessageType::ServerSetup.max_message_size();
     let mut writer = vec![];
     (MessageType::ServerSetup as u64).serialize(&mut writer)?;
     0x1u64.serialize(&mut writer)?; // version
     0x1u64.serialize(&mut writer)?; // num_params
     0xbeefu64.serialize(&mut writer)?; // unknown param
-    MAX_MESSSAGE_HEADER_SIZE.serialize(&mut writer)?; // very long parameter
-    writer.append(&mut vec![0x04u8; MAX_MESSSAGE_HEADER_SIZE]);
+    max_message_size.serialize(&mut writer)?; // very long parameter
+    writer.append(&mut vec![0x04u8; max_message_size]);
 
     // Send incomplete message
     parser.process_data(&mut &writer[..writer.len() - 1], false);
@@ -1406,13 +1550,65 @@ fn test_setup2kb() -> Result<()> {
     assert!(tester.visitor.parsing_error.is_some());
     assert_eq!(
         tester.visitor.parsing_error,
-        Some("Cannot parse non-OBJECT messages > 2KB".to_string())
+        Some(format!(
+            "Cannot parse messages > {} bytes",
+            max_message_size
+        ))
     );
     assert_eq!(tester.visitor.parsing_error_code, ErrorCode::InternalError);
 
     Ok(())
 }
 
+// Returns the start of a message of the given type whose string or parameter
+// runs well past the type's size limit.
+fn oversized_message(message_type: MessageType) -> Result<Vec<u8>> {
+    let long_length = 2 * message_type.max_message_size();
+    let mut writer = vec![];
+    (message_type as u64).serialize(&mut writer)?;
+    match message_type {
+        MessageType::ClientSetup => {
+            0x1u64.serialize(&mut writer)?; // num_versions
+            0x1u64.serialize(&mut writer)?; // version
+            0x1u64.serialize(&mut writer)?; // num_params
+            0xbeefu64.serialize(&mut writer)?; // unknown param
+        }
+        MessageType::Subscribe => {
+            0x1u64.serialize(&mut writer)?; // subscribe_id
+            0x2u64.serialize(&mut writer)?; // track_alias
+        }
+        _ => unreachable!(),
+    }
+    long_length.serialize(&mut writer)?;
+    writer.append(&mut vec![0x04u8; long_length]);
+    Ok(writer)
+}
+
+#[rstest(
+    params => [
+    (MessageType::ClientSetup, 0, false),
+    (MessageType::ClientSetup, 1, true),
+    (MessageType::Subscribe, 0, false),
+    (MessageType::Subscribe, 1, true),
+    ]
+)]
+fn test_max_message_size_boundary(params: (MessageType, usize, bool)) -> Result<()> {
+    let (message_type, excess, expect_error) = params;
+    let mut tester = TestMessageSpecific::new();
+    let mut parser = MessageParser::new(K_RAW_QUIC);
+    let max_message_size = message_type.max_message_size();
+    let message = oversized_message(message_type)?;
+
+    parser.process_data(&mut &message[..max_message_size + excess], false);
+    while let Some(event) = parser.poll_event() {
+        tester.visitor.handle_event(event);
+    }
+    assert_eq!(tester.visitor.messages_received, 0);
+    assert_eq!(tester.visitor.parsing_error.is_some(), expect_error);
+
+    Ok(())
+}
+
 #[test]
 fn test_unknown_message_type() -> Result<()> {
     let mut tester = TestMessageSpecific::new();
@@ -1571,11 +1767,11 @@ fn test_absolute_range_explicit_end_object() -> Result<()> {
         assert!(false);
         return Err(Error::ErrInvalidMessageType(0));
     };
-    if let FilterType::AbsoluteRange(start, end) = message.filter_type {
+    if let FilterType::AbsoluteRange(start, end_group, end_object) = message.filter_type {
         assert_eq!(start.group_id, 4);
         assert_eq!(start.object_id, 1);
-        assert_eq!(end.group_id, 7);
-        assert_eq!(end.object_id, 2);
+        assert_eq!(end_group, 7);
+        assert_eq!(end_object, Some(2));
     } else {
         assert!(false);
     }
@@ -1613,11 +1809,11 @@ fn test_absolute_range_whole_end_group() -> Result<()> {
         assert!(false);
         return Err(Error::ErrInvalidMessageType(0));
     };
-    if let FilterType::AbsoluteRange(start, end) = message.filter_type {
+    if let FilterType::AbsoluteRange(start, end_group, end_object) = message.filter_type {
         assert_eq!(start.group_id, 4);
         assert_eq!(start.object_id, 1);
-        assert_eq!(end.group_id, 7);
-        assert_eq!(end.object_id, u64::MAX);
+        assert_eq!(end_group, 7);
+        assert_eq!(end_object, None);
     } else {
         assert!(false);
     }
@@ -1829,7 +2025,7 @@ fn test_all_messages_together() -> Result<()> {
     for &message_type in TEST_MESSAGE_TYPES {
         // Each iteration, process from the halfway point of one message to the
         // halfway point of the next.
-        if message_type.is_object_message() {
+        if message_type.is_object_type() {
             continue; // Objects cannot share a stream with other messages.
         }
         let message = create_test_message(message_type, K_RAW_QUIC);
@@ -1868,6 +2064,28 @@ fn test_all_messages_together() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_object_datagram_on_stream() -> Result<()> {
+    let mut tester = TestMessageSpecific::new();
+    let mut parser = MessageParser::new(K_RAW_QUIC);
+    let message = TestObjectDatagramMessage::new();
+    parser.process_data(&mut message.packet_sample(), false);
+    while let Some(event) = parser.poll_event() {
+        tester.visitor.handle_event(event);
+    }
+    assert_eq!(tester.visitor.messages_received, 0);
+    assert_eq!(
+        tester.visitor.parsing_error,
+        Some("Received OBJECT_DATAGRAM on stream".to_string())
+    );
+    assert_eq!(
+        tester.visitor.parsing_error_code,
+        ErrorCode::ProtocolViolation
+    );
+
+    Ok(())
+}
+
 #[test]
 fn test_datagram_successful() -> Result<()> {
     let message = TestObjectDatagramMessage::new();
@@ -1916,6 +2134,28 @@ fn test_very_truncated_datagram() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_datagram_priority_range() -> Result<()> {
+    // Draft-05 priorities are a single byte, so every encoding is in range.
+    let draft05 = [0x01, 0x03, 0x04, 0x05, 0x06, 0xff, 0x00, 0x66, 0x6f, 0x6f];
+    let (object_header, _) =
+        MessageParser::process_datagram_with_version(&mut &draft05[..], Version::Draft05)?;
+    assert_eq!(object_header.publisher_priority, Priority(0xff));
+
+    // A Draft-04 send order of 0x100 does not fit in a Draft-05 priority.
+    let draft04 = [
+        0x01, 0x03, 0x04, 0x05, 0x06, 0x41, 0x00, 0x00, 0x66, 0x6f, 0x6f,
+    ];
+    let (object_header, _) = MessageParser::process_datagram(&mut &draft04[..])?;
+    assert_eq!(object_header.object_send_order, 0x100);
+    assert_eq!(
+        Priority::try_from(object_header.object_send_order),
+        Err(Error::ErrInvalidPriority(0x100))
+    );
+
+    Ok(())
+}
+
 #[test]
 fn test_subscribe_ok_invalid_content_exists() -> Result<()> {
     let mut tester = TestMessageSpecific::new();
@@ -1955,3 +2195,1185 @@ fn test_subscribe_done_invalid_content_exists() -> Result<()> {
 
     Ok(())
 }
+
+// Reassembles complete objects and control messages out of the parser events,
+// so that the same expectations hold no matter where the byte stream is split.
+#[derive(Debug, PartialEq)]
+enum LifecycleEvent {
+    Control(ControlMessage),
+    Object(ObjectHeader, Bytes),
+}
+
+fn drain_lifecycle_events(
+    parser: &mut MessageParser,
+    partial_payload: &mut BytesMut,
+    events: &mut Vec<LifecycleEvent>,
+) {
+    while let Some(event) = parser.poll_event() {
+        match event {
+            MessageParserEvent::ParsingError(code, reason) => {
+                panic!("unexpected parsing error {}: {}", code, reason)
+            }
+            MessageParserEvent::ControlMessage(message) => {
+                events.push(LifecycleEvent::Control(message))
+            }
+            MessageParserEvent::GroupStart(_)
+            | MessageParserEvent::UnknownControlMessage { .. }
+            | MessageParserEvent::CustomMessage { .. }
+            | MessageParserEvent::StreamClosed => {}
+            MessageParserEvent::ObjectMessage(header, payload, end_of_message) => {
+                partial_payload.extend_from_slice(&payload);
+                if end_of_message {
+                    events.push(LifecycleEvent::Object(
+                        header,
+                        partial_payload.split().freeze(),
+                    ));
+                }
+            }
+        }
+    }
+}
+
+fn feed_with_splits(
+    parser: &mut MessageParser,
+    data: &[u8],
+    splits: &[usize],
+) -> Vec<LifecycleEvent> {
+    let mut events = vec![];
+    let mut partial_payload = BytesMut::new();
+    let mut read = 0;
+    for &split in splits.iter().filter(|&&split| split < data.len()) {
+        parser.process_data(&mut &data[read..split], false);
+        drain_lifecycle_events(parser, &mut partial_payload, &mut events);
+        read = split;
+    }
+    parser.process_data(&mut &data[read..], true);
+    drain_lifecycle_events(parser, &mut partial_payload, &mut events);
+    events
+}
+
+#[test]
+fn test_subscribe_lifecycle() -> Result<()> {
+    let subscribe = ControlMessage::Subscribe(Subscribe {
+        subscribe_id: 1,
+        track_alias: 2,
+        track_namespace: "foo".into(),
+        track_name: "abcd".to_string(),
+        filter_type: FilterType::AbsoluteStart(FullSequence {
+            group_id: 4,
+            object_id: 0,
+        }),
+        authorization_info: Some("bar".into()),
+        parameters: Parameters::new(),
+    });
+    let subscribe_ok = ControlMessage::SubscribeOk(SubscribeOk {
+        subscribe_id: 1,
+        expires: 0,
+        group_order: None,
+        largest_group_object: None,
+    });
+    let subscribe_done = ControlMessage::SubscribeDone(SubscribeDone {
+        subscribe_id: 1,
+        status_code: SubscribeDoneCode::SubscriptionEnded as u64,
+        reason_phrase: "done".to_string(),
+        final_group_object: Some(FullSequence {
+            group_id: 4,
+            object_id: 2,
+        }),
+    });
+    let object_header = ObjectHeader {
+        subscribe_id: 1,
+        track_alias: 2,
+        group_id: 4,
+        object_id: 0,
+        object_send_order: 0,
+        publisher_priority: Priority::default(),
+        object_status: ObjectStatus::Normal,
+        object_forwarding_preference: ObjectForwardingPreference::Group,
+        object_payload_length: None,
+    };
+    let payloads = [
+        Bytes::from_static(b"foo"),
+        Bytes::from_static(b"deadbeef"),
+        Bytes::from_static(b"bar"),
+    ];
+
+    // Subscriber -> publisher control stream.
+    let mut subscriber_control = vec![];
+    MessageFramer::serialize_control_message(subscribe.clone(), &mut subscriber_control)?;
+
+    // Publisher -> subscriber control stream.
+    let mut publisher_control = vec![];
+    let subscribe_ok_len =
+        MessageFramer::serialize_control_message(subscribe_ok.clone(), &mut publisher_control)?;
+    MessageFramer::serialize_control_message(subscribe_done.clone(), &mut publisher_control)?;
+
+    // Publisher -> subscriber data stream, one group.
+    let mut data_stream = vec![];
+    let mut object_boundaries = vec![];
+    let mut expected_objects = vec![];
+    for (object_id, payload) in payloads.iter().enumerate() {
+        let mut header = object_header;
+        header.object_id = object_id as u64;
+        MessageFramer::serialize_object(header, object_id == 0, payload.clone(), &mut data_stream)?;
+        object_boundaries.push(data_stream.len());
+        header.object_payload_length = Some(payload.len() as u64);
+        expected_objects.push(LifecycleEvent::Object(header, payload.clone()));
+    }
+
+    let split_sets: Vec<Vec<usize>> = vec![
+        vec![],
+        vec![1],
+        vec![subscribe_ok_len, object_boundaries[0]],
+        vec![3, subscribe_ok_len + 1, object_boundaries[1] - 2],
+        (1..data_stream.len().max(publisher_control.len())).collect(),
+    ];
+    for splits in split_sets {
+        let mut publisher_parser = MessageParser::new(K_RAW_QUIC);
+        let events = feed_with_splits(&mut publisher_parser, &subscriber_control, &splits);
+        assert_eq!(events, vec![LifecycleEvent::Control(subscribe.clone())]);
+
+        let mut subscriber_parser = MessageParser::new(K_RAW_QUIC);
+        let events = feed_with_splits(&mut subscriber_parser, &publisher_control, &splits);
+        assert_eq!(
+            events,
+            vec![
+                LifecycleEvent::Control(subscribe_ok.clone()),
+                LifecycleEvent::Control(subscribe_done.clone()),
+            ]
+        );
+
+        let mut data_parser = MessageParser::new(K_RAW_QUIC);
+        let events = feed_with_splits(&mut data_parser, &data_stream, &splits);
+        assert_eq!(events, expected_objects);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_event_queue_bound() -> Result<()> {
+    let mut parser = MessageParser::new(K_RAW_QUIC);
+    parser.set_max_events(1);
+    let subscribe_ok = TestSubscribeOkMessage::new();
+    let mut data = vec![];
+    for _ in 0..3 {
+        data.extend_from_slice(subscribe_ok.packet_sample());
+    }
+
+    // Only the first message is parsed; the rest stays buffered.
+    parser.process_data(&mut data.as_slice(), true);
+    assert!(parser.is_backpressured());
+
+    // Each drained event lets the parser queue the next message.
+    let mut received = 0;
+    let mut closed = false;
+    while let Some(event) = parser.poll_event() {
+        match event {
+            MessageParserEvent::ControlMessage(ControlMessage::SubscribeOk(_)) => received += 1,
+            // The FIN is only reported once the held-back input is parsed.
+            MessageParserEvent::StreamClosed => closed = received == 3,
+            _ => panic!("expected SUBSCRIBE_OK"),
+        }
+        // The third message is held back until the second is drained.
+        assert_eq!(parser.is_backpressured(), received == 1);
+    }
+    assert_eq!(received, 3);
+    assert!(closed);
+    assert!(!parser.is_backpressured());
+
+    Ok(())
+}
+
+#[test]
+fn test_chained_buf() -> Result<()> {
+    use bytes::Buf;
+
+    let mut tester = TestMessageSpecific::new();
+    let mut parser = MessageParser::new(K_RAW_QUIC);
+    let subscribe_ok = TestSubscribeOkMessage::new();
+    // The sample message with subscribe_id = 1 in a two-byte varint, split
+    // between its two bytes.
+    let packet = [0x04, 0x40, 0x01, 0x03, 0x01, 0x0c, 0x14];
+    let (first, second) = packet.split_at(2);
+    parser.process_data(&mut first.chain(second), false);
+    while let Some(event) = parser.poll_event() {
+        tester.visitor.handle_event(event);
+    }
+    assert_eq!(tester.visitor.messages_received, 1);
+    assert!(tester.visitor.parsing_error.is_none());
+    assert!(subscribe_ok.equal_field_values(tester.visitor.last_message.as_ref().unwrap()));
+
+    Ok(())
+}
+
+#[test]
+fn test_malformed_message_is_protocol_violation() -> Result<()> {
+    // SUBSCRIBE_OK with content_exists = 2.
+    let packet = [0x04, 0x01, 0x03, 0x02, 0x02, 0x0c, 0x14];
+    let err = crate::try_decode_control(&packet).unwrap_err();
+    assert!(matches!(
+        err.root(),
+        Error::ErrParseError(ErrorCode::ProtocolViolation, _)
+    ));
+
+    let mut parser = MessageParser::new(K_RAW_QUIC);
+    parser.process_data(&mut &packet[..], false);
+    assert!(matches!(
+        parser.poll_event(),
+        Some(MessageParserEvent::ParsingError(
+            ErrorCode::ProtocolViolation,
+            _
+        ))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_negotiated_version_selects_layout() -> Result<()> {
+    let mut parser = MessageParser::new(K_RAW_QUIC);
+    assert_eq!(parser.version(), None);
+    parser.set_version(Version::Draft05);
+    assert_eq!(parser.version(), Some(Version::Draft05));
+
+    let packet = [
+        0x40, 0x51, 0x03, 0x04, 0x05, // type, subscribe_id, track_alias, group_id
+        0x80, // publisher_priority, which would start a two-byte varint in Draft-04
+        0x06, 0x03, 0x66, 0x6f, 0x6f, // object_id = 6, length = 3, payload = "foo"
+    ];
+    parser.process_data(&mut &packet[..], false);
+    let event = core::iter::from_fn(|| parser.poll_event())
+        .find(|event| !matches!(event, MessageParserEvent::GroupStart(_)));
+    match event {
+        Some(MessageParserEvent::ObjectMessage(header, payload, true)) => {
+            assert_eq!(header.publisher_priority, Priority(0x80));
+            assert_eq!(header.object_id, 6);
+            assert_eq!(payload, "foo");
+        }
+        _ => panic!("expected an object"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_negotiated_version_selects_subscribe_ok_layout() -> Result<()> {
+    let draft04 = [0x04, 0x01, 0x03, 0x00];
+    let draft05 = [0x04, 0x01, 0x03, 0x02, 0x00]; // group_order = descending
+
+    for (version, packet, group_order) in [
+        (None, &draft04[..], None),
+        (
+            Some(Version::Draft05),
+            &draft05[..],
+            Some(GroupOrder::Descending),
+        ),
+    ] {
+        let mut parser = MessageParser::new(K_RAW_QUIC);
+        if let Some(version) = version {
+            parser.set_version(version);
+        }
+        parser.process_data(&mut &packet[..], false);
+        match parser.poll_event() {
+            Some(MessageParserEvent::ControlMessage(ControlMessage::SubscribeOk(subscribe_ok))) => {
+                assert_eq!(subscribe_ok.group_order, group_order);
+                assert_eq!(subscribe_ok.largest_group_object, None);
+            }
+            _ => panic!("expected SUBSCRIBE_OK"),
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_parse_all() -> Result<()> {
+    let subscribe_ok = TestSubscribeOkMessage::new();
+    let subscribe_done = TestSubscribeDoneMessage::new();
+    let mut data = BytesMut::new();
+    data.extend_from_slice(subscribe_ok.packet_sample());
+    data.extend_from_slice(subscribe_done.packet_sample());
+    data.extend_from_slice(subscribe_ok.packet_sample());
+
+    let events = MessageParser::new(K_RAW_QUIC).parse_all(data.clone().freeze())?;
+    assert_eq!(events.len(), 3);
+    for (event, expected) in events.iter().zip([
+        MessageType::SubscribeOk,
+        MessageType::SubscribeDone,
+        MessageType::SubscribeOk,
+    ]) {
+        match event {
+            MessageParserEvent::ControlMessage(message) => {
+                assert_eq!(message.message_type(), expected)
+            }
+            _ => panic!("expected a control message"),
+        }
+    }
+
+    // The last message is cut short.
+    data.truncate(data.len() - 1);
+    assert_eq!(
+        MessageParser::new(K_RAW_QUIC)
+            .parse_all(data.freeze())
+            .map(|events| events.len()),
+        Err(Error::ErrParseError(
+            ErrorCode::ProtocolViolation,
+            "FIN after incomplete message".to_string()
+        ))
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_setup_authorization_info_twice() -> Result<()> {
+    let mut tester = TestMessageSpecific::new();
+    let mut parser = MessageParser::new(K_WEB_TRANS);
+    let setup = vec![
+        0x40, 0x41, 0x01, // version = 1
+        0x03, // 3 params
+        0x00, 0x01, 0x03, // role = PubSub
+        0x02, 0x03, 0x62, 0x61, 0x72, // authorization_info = "bar"
+        0x02, 0x03, 0x62, 0x61, 0x72, // authorization_info = "bar"
+    ];
+    parser.process_data(&mut &setup[..], false);
+    while let Some(event) = parser.poll_event() {
+        tester.visitor.handle_event(event);
+    }
+    assert_eq!(tester.visitor.messages_received, 0);
+    assert_eq!(
+        tester.visitor.parsing_error,
+        Some("AUTHORIZATION_INFO parameter appears twice in SETUP".to_string())
+    );
+    assert_eq!(
+        tester.visitor.parsing_error_code,
+        ErrorCode::ProtocolViolation
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_stream_header_track_payload_lengths() -> Result<()> {
+    let mut parser = MessageParser::new(K_RAW_QUIC);
+    let stream = [
+        0x40, 0x50, 0x03, 0x04, 0x07, // type, subscribe_id, track_alias, send order
+        0x05, 0x06, 0x04, 0x66, 0x6f, 0x6f, 0x6f, // group 5, object 6, "fooo"
+        0x05, 0x07, 0x02, 0x62, 0x61, // group 5, object 7, "ba"
+    ];
+    // Split inside the first payload so the rest of it is delivered through
+    // payload_length_remaining.
+    parser.process_data(&mut &stream[..9], false);
+    parser.process_data(&mut &stream[9..], false);
+
+    let mut objects = vec![];
+    while let Some(event) = parser.poll_event() {
+        match event {
+            MessageParserEvent::ObjectMessage(header, payload, end_of_message) => objects.push((
+                header.object_id,
+                header.object_payload_length,
+                payload,
+                end_of_message,
+            )),
+            MessageParserEvent::GroupStart(5) => {}
+            _ => panic!("expected an object"),
+        }
+    }
+    assert_eq!(
+        objects,
+        [
+            (6, Some(4), Bytes::from_static(b"f"), false),
+            (6, Some(4), Bytes::from_static(b"ooo"), true),
+            (7, Some(2), Bytes::from_static(b"ba"), true),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_peek_message_type() -> Result<()> {
+    let mut parser = MessageParser::new(K_RAW_QUIC);
+    assert_eq!(parser.peek_message_type(), None);
+
+    let message = TestSubscribeMessage::new();
+    let packet = message.packet_sample();
+    parser.process_data(&mut &packet[..1], false);
+    assert_eq!(parser.peek_message_type(), Some(Ok(MessageType::Subscribe)));
+    assert!(parser.poll_event().is_none());
+
+    // Peeking consumes nothing, so the message still parses once complete.
+    parser.process_data(&mut &packet[1..], false);
+    assert!(matches!(
+        parser.poll_event(),
+        Some(MessageParserEvent::ControlMessage(
+            ControlMessage::Subscribe(_)
+        ))
+    ));
+    assert_eq!(parser.peek_message_type(), None);
+
+    // A two-byte type is not known until both bytes arrive.
+    parser.process_data(&mut &[0x40][..], false);
+    assert_eq!(parser.peek_message_type(), None);
+    parser.process_data(&mut &[0x41][..], false);
+    assert_eq!(
+        parser.peek_message_type(),
+        Some(Ok(MessageType::ServerSetup))
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_object_datagram_header_on_stream() -> Result<()> {
+    let mut tester = TestMessageSpecific::new();
+    let mut parser = MessageParser::new(K_RAW_QUIC);
+    let message = TestObjectDatagramMessage::new();
+    // Only the header, so the payload would still be to come.
+    parser.process_data(&mut &message.packet_sample()[..7], false);
+    assert!(!parser.object_in_progress());
+    while let Some(event) = parser.poll_event() {
+        tester.visitor.handle_event(event);
+    }
+    assert_eq!(tester.visitor.messages_received, 0);
+    assert_eq!(
+        tester.visitor.parsing_error,
+        Some("Received OBJECT_DATAGRAM on stream".to_string())
+    );
+    assert_eq!(
+        tester.visitor.parsing_error_code,
+        ErrorCode::ProtocolViolation
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_stream_closed() -> Result<()> {
+    let message = TestSubscribeMessage::new();
+    let mut parser = MessageParser::new(K_RAW_QUIC);
+    parser.process_data(&mut message.packet_sample(), true);
+    assert!(matches!(
+        parser.poll_event(),
+        Some(MessageParserEvent::ControlMessage(
+            ControlMessage::Subscribe(_)
+        ))
+    ));
+    assert!(matches!(
+        parser.poll_event(),
+        Some(MessageParserEvent::StreamClosed)
+    ));
+    assert!(parser.poll_event().is_none());
+
+    // Without a FIN the parser is still waiting for more.
+    let mut parser = MessageParser::new(K_RAW_QUIC);
+    parser.process_data(&mut message.packet_sample(), false);
+    assert!(parser.poll_event().is_some());
+    assert!(parser.poll_event().is_none());
+
+    // A FIN in the middle of a message is an error, not a clean close.
+    let mut tester = TestMessageSpecific::new();
+    let mut parser = MessageParser::new(K_RAW_QUIC);
+    parser.process_data(&mut &message.packet_sample()[..4], true);
+    while let Some(event) = parser.poll_event() {
+        tester.visitor.handle_event(event);
+    }
+    assert!(tester.visitor.parsing_error.is_some());
+    assert!(!tester.visitor.stream_closed);
+
+    Ok(())
+}
+
+#[test]
+fn test_object_header_across_reads() -> Result<()> {
+    let message = TestObjectStreamMessage::new();
+    let packet = message.packet_sample();
+    let header_len = packet.len() - 3; // payload = "foo"
+
+    let mut parser = MessageParser::new(K_RAW_QUIC);
+    for i in 0..header_len - 1 {
+        parser.process_data(&mut &packet[i..i + 1], false);
+        assert!(parser.poll_event().is_none(), "byte {}", i);
+        assert!(!parser.object_in_progress(), "byte {}", i);
+    }
+    parser.process_data(&mut &packet[header_len - 1..], true);
+
+    let mut tester = TestMessageSpecific::new();
+    match parser.poll_event() {
+        Some(event @ MessageParserEvent::ObjectMessage(..)) => tester.visitor.handle_event(event),
+        _ => panic!("expected an object"),
+    }
+    assert!(matches!(
+        parser.poll_event(),
+        Some(MessageParserEvent::StreamClosed)
+    ));
+    assert_eq!(tester.visitor.messages_received, 1);
+    assert!(tester.visitor.end_of_message);
+    assert_eq!(tester.visitor.object_payload.as_deref(), Some(&b"foo"[..]));
+    assert!(message.equal_field_values(tester.visitor.last_message.as_ref().unwrap()));
+
+    Ok(())
+}
+
+#[test]
+fn test_error_code_from_error() {
+    assert_eq!(ErrorCode::ParameterLengthMismatch.as_u64(), 0x5);
+    assert_eq!(ErrorCode::GoawayTimeout.as_u64(), 0x10);
+
+    assert_eq!(
+        ErrorCode::from(Error::ErrParseError(
+            ErrorCode::Unauthorized,
+            "bad token".to_string()
+        )),
+        ErrorCode::Unauthorized
+    );
+    assert_eq!(
+        ErrorCode::from(Error::ErrParameterLengthMismatch { key: 2, len: 3 }),
+        ErrorCode::ParameterLengthMismatch
+    );
+    assert_eq!(
+        ErrorCode::from(Error::ErrDuplicateParameter(0).at(4)),
+        ErrorCode::ProtocolViolation
+    );
+    assert_eq!(
+        ErrorCode::from(Error::ErrInvalidMessageType(0x99)),
+        ErrorCode::ProtocolViolation
+    );
+    assert_eq!(
+        ErrorCode::from(Error::ErrBufferTooShort),
+        ErrorCode::InternalError
+    );
+}
+
+#[test]
+fn test_contiguous_input_is_not_buffered() {
+    let message = TestSubscribeMessage::new();
+    let sample = message.packet_sample();
+    let mut data = BytesMut::new();
+    data.put_slice(sample);
+    data.put_slice(sample);
+
+    // Whole messages delivered in one contiguous chunk are parsed in place.
+    let mut parser = MessageParser::new(false);
+    let mut aligned = data.clone().freeze();
+    parser.process_data(&mut aligned, false);
+    assert_eq!(parser.buffered_message().capacity(), 0);
+    let mut messages = 0;
+    while let Some(event) = parser.poll_event() {
+        assert!(matches!(event, MessageParserEvent::ControlMessage(_)));
+        messages += 1;
+    }
+    assert_eq!(messages, 2);
+
+    // Only the unconsumed tail of a split message is buffered.
+    let split = sample.len() + 1;
+    let mut parser = MessageParser::new(false);
+    let mut first = data.clone().freeze().slice(..split);
+    parser.process_data(&mut first, false);
+    assert_eq!(parser.buffered_message().len(), 1);
+    let mut second = data.freeze().slice(split..);
+    parser.process_data(&mut second, false);
+    assert!(parser.buffered_message().is_empty());
+    let mut messages = 0;
+    while let Some(event) = parser.poll_event() {
+        assert!(matches!(event, MessageParserEvent::ControlMessage(_)));
+        messages += 1;
+    }
+    assert_eq!(messages, 2);
+}
+
+#[test]
+fn test_reset_keeps_buffer_capacity() {
+    let message = TestSubscribeMessage::new();
+    let sample = message.packet_sample();
+    let mut parser = MessageParser::with_buffer(false, BytesMut::with_capacity(4096));
+
+    for _ in 0..2 {
+        // A split message is buffered, then parsed from the buffer.
+        parser.process_data(&mut &sample[..1], false);
+        assert_eq!(parser.buffered_message().len(), 1);
+        parser.process_data(&mut &sample[1..], true);
+        assert!(matches!(
+            parser.poll_event(),
+            Some(MessageParserEvent::ControlMessage(_))
+        ));
+        assert!(matches!(
+            parser.poll_event(),
+            Some(MessageParserEvent::StreamClosed)
+        ));
+
+        parser.reset();
+        assert!(parser.buffered_message().is_empty());
+        assert!(parser.buffered_message().capacity() >= 4096);
+        assert!(parser.poll_event().is_none());
+    }
+
+    // A reset parser takes a new stream after the previous one ended.
+    parser.process_data(&mut &sample[..], false);
+    assert!(matches!(
+        parser.poll_event(),
+        Some(MessageParserEvent::ControlMessage(_))
+    ));
+    assert!(parser.into_buffer().capacity() >= 4096);
+}
+
+#[test]
+fn test_large_client_setup_one_byte_at_a_time() -> Result<()> {
+    use crate::message::client_setup::ClientSetup;
+    use crate::message::{Role, Version};
+
+    let client_setup = ClientSetup {
+        supported_versions: vec![Version::Draft04],
+        role: Some(Role::PubSub),
+        path: Some("a".repeat(4000)),
+        ..ClientSetup::new(false)
+    };
+    let mut packet = BytesMut::new();
+    MessageFramer::serialize_control_message(
+        ControlMessage::ClientSetup(client_setup.clone()),
+        &mut packet,
+    )?;
+
+    let mut parser = MessageParser::new(false);
+    let mut skipped = 0;
+    for (i, byte) in packet.chunks(1).enumerate() {
+        parser.process_data(&mut &byte[..], false);
+        if i + 1 < packet.len() {
+            assert!(parser.poll_event().is_none());
+            // The PATH value ends the message, so once its length is known
+            // the message is not decoded again until all of it arrives.
+            if parser.incomplete_message_len() == packet.len() {
+                skipped += 1;
+            }
+        }
+    }
+    assert!(skipped > packet.len() - 32);
+    match parser.poll_event() {
+        Some(MessageParserEvent::ControlMessage(ControlMessage::ClientSetup(actual))) => {
+            assert_eq!(actual, client_setup)
+        }
+        _ => panic!("expected CLIENT_SETUP"),
+    }
+    assert_eq!(parser.incomplete_message_len(), 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_strict_trailing_bytes() -> Result<()> {
+    let message = TestSubscribeMessage::new();
+    let subscribe = message.packet_sample();
+    let mut data = vec![];
+    (subscribe.len() + 1).serialize(&mut data)?;
+    data.extend_from_slice(subscribe);
+    data.push(0x03);
+
+    // By default the stray byte inside the frame is skipped.
+    let mut parser = MessageParser::new(false);
+    parser.set_length_prefixed(true);
+    parser.process_data(&mut data.as_slice(), false);
+    assert!(matches!(
+        parser.poll_event(),
+        Some(MessageParserEvent::ControlMessage(_))
+    ));
+    assert!(parser.poll_event().is_none());
+
+    let mut parser = MessageParser::with_strict(false, true);
+    parser.set_length_prefixed(true);
+    parser.process_data(&mut data.as_slice(), false);
+    match parser.poll_event() {
+        Some(MessageParserEvent::ParsingError(code, reason)) => {
+            assert_eq!(code, ErrorCode::ProtocolViolation);
+            assert_eq!(reason, "Trailing bytes after control message");
+        }
+        _ => unreachable!(),
+    }
+
+    // A message split across reads is accepted.
+    let mut parser = MessageParser::with_strict(false, true);
+    let (first, second) = subscribe.split_at(subscribe.len() / 2);
+    parser.process_data(&mut &first[..], false);
+    assert!(parser.poll_event().is_none());
+    parser.process_data(&mut &second[..], false);
+    assert!(matches!(
+        parser.poll_event(),
+        Some(MessageParserEvent::ControlMessage(_))
+    ));
+    assert!(parser.poll_event().is_none());
+
+    Ok(())
+}
+
+#[cfg(feature = "tracing")]
+#[test]
+fn test_tracing_control_message_event() {
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata};
+
+    struct FieldRecorder(Vec<String>);
+
+    impl Visit for FieldRecorder {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.0.push(format!("{}={:?}", field.name(), value));
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct EventRecorder(Arc<Mutex<Vec<Vec<String>>>>);
+
+    impl tracing::Subscriber for EventRecorder {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+        fn new_span(&self, _: &Attributes<'_>) -> Id {
+            Id::from_u64(1)
+        }
+        fn record(&self, _: &Id, _: &Record<'_>) {}
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+        fn event(&self, event: &Event<'_>) {
+            let mut fields = FieldRecorder(vec![]);
+            event.record(&mut fields);
+            self.0.lock().unwrap().push(fields.0);
+        }
+        fn enter(&self, _: &Id) {}
+        fn exit(&self, _: &Id) {}
+    }
+
+    let recorder = EventRecorder::default();
+    tracing::subscriber::with_default(recorder.clone(), || {
+        let message = TestSubscribeMessage::new();
+        let mut parser = MessageParser::new(false);
+        parser.process_data(&mut message.packet_sample(), false);
+    });
+
+    let events = recorder.0.lock().unwrap();
+    assert_eq!(events.len(), 1);
+    assert!(events[0].contains(&"message=control message".to_string()));
+    assert!(events[0].contains(&"message_type=Subscribe".to_string()));
+    assert!(events[0].contains(&format!(
+        "len={}",
+        TestSubscribeMessage::new().packet_sample().len()
+    )));
+}
+
+#[test]
+fn test_truncated_object_header() {
+    let samples = [
+        TestObjectStreamMessage::new().packet_sample().to_vec(),
+        TestStreamHeaderTrackMessage::new().packet_sample().to_vec(),
+        TestStreamHeaderGroupMessage::new().packet_sample().to_vec(),
+    ];
+    for sample in samples {
+        for split in 0..sample.len() {
+            let mut parser = MessageParser::new(false);
+            parser.process_data(&mut &sample[..split], false);
+            parser.process_data(&mut &sample[split..], false);
+            let mut objects = 0;
+            while let Some(event) = parser.poll_event() {
+                match event {
+                    MessageParserEvent::ObjectMessage(..) => objects += 1,
+                    MessageParserEvent::GroupStart(_) => {}
+                    _ => unreachable!("unexpected event at split {}", split),
+                }
+            }
+            assert!(objects > 0, "no object at split {}", split);
+        }
+    }
+}
+
+#[test]
+fn test_payload_remaining() {
+    // No object yet.
+    let mut parser = MessageParser::new(false);
+    assert!(!parser.object_in_progress());
+    assert_eq!(parser.payload_remaining(), Some(0));
+
+    // OBJECT_STREAM payloads run to FIN, so the length is unknown.
+    let message = TestObjectStreamMessage::new();
+    let sample = message.packet_sample();
+    parser.process_data(&mut &sample[..sample.len() - 1], false);
+    assert!(parser.object_in_progress());
+    assert_eq!(parser.payload_remaining(), None);
+    parser.process_data(&mut &sample[sample.len() - 1..], true);
+    assert!(!parser.object_in_progress());
+    assert_eq!(parser.payload_remaining(), Some(0));
+
+    // Objects on a group stream carry an explicit length.
+    let mut parser = MessageParser::new(false);
+    let message = TestStreamHeaderGroupMessage::new();
+    let sample = message.packet_sample();
+    parser.process_data(&mut &sample[..sample.len() - 2], false);
+    assert!(parser.object_in_progress());
+    assert_eq!(parser.payload_remaining(), Some(2));
+    parser.process_data(&mut &sample[sample.len() - 2..], false);
+    assert!(!parser.object_in_progress());
+    assert_eq!(parser.payload_remaining(), Some(0));
+}
+
+#[test]
+fn test_subscriber_role() {
+    let mut parser = MessageParser::new(false);
+    parser.set_role(Role::Subscriber);
+    parser.process_data(&mut TestSubscribeOkMessage::new().packet_sample(), false);
+    assert!(matches!(
+        parser.poll_event(),
+        Some(MessageParserEvent::ControlMessage(
+            ControlMessage::SubscribeOk(_)
+        ))
+    ));
+
+    parser.process_data(&mut TestSubscribeMessage::new().packet_sample(), false);
+    match parser.poll_event() {
+        Some(MessageParserEvent::ParsingError(code, reason)) => {
+            assert_eq!(code, ErrorCode::ProtocolViolation);
+            assert_eq!(reason, "Received Subscribe as Subscriber");
+        }
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn test_fin_without_data_ends_unbounded_object() {
+    let message = TestObjectStreamMessage::new();
+    let mut parser = MessageParser::new(false);
+    parser.process_data(&mut message.packet_sample(), false);
+    match parser.poll_event() {
+        Some(MessageParserEvent::ObjectMessage(_, payload, false)) => {
+            assert_eq!(&payload[..], b"foo");
+        }
+        _ => unreachable!(),
+    }
+    assert!(parser.object_in_progress());
+
+    parser.process_data(&mut &[][..], true);
+    match parser.poll_event() {
+        Some(MessageParserEvent::ObjectMessage(object_header, payload, true)) => {
+            assert!(message.equal_field_values(&MessageStructuredData::Object(object_header)));
+            assert!(payload.is_empty());
+        }
+        _ => unreachable!(),
+    }
+    assert!(matches!(
+        parser.poll_event(),
+        Some(MessageParserEvent::StreamClosed)
+    ));
+    assert!(parser.poll_event().is_none());
+    assert!(!parser.object_in_progress());
+}
+
+#[test]
+fn test_buffered_payload_shares_allocation() {
+    let message = TestStreamHeaderGroupMessage::new();
+    let sample = message.packet_sample();
+    let mut second = sample[2..].to_vec();
+    second.extend_from_slice(&[0x07, 0x03, 0x62, 0x61, 0x72]); // object 7 = "bar"
+
+    // The header is split, so the second read is parsed from the buffer.
+    let mut parser = MessageParser::new(false);
+    parser.process_data(&mut &sample[..2], false);
+    parser.process_data(&mut second.as_slice(), false);
+    let payloads: Vec<Bytes> = core::iter::from_fn(|| parser.poll_event())
+        .filter_map(|event| match event {
+            MessageParserEvent::ObjectMessage(_, payload, true) => Some(payload),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(payloads.len(), 2);
+    assert_eq!(&payloads[0][..], b"foo");
+    assert_eq!(&payloads[1][..], b"bar");
+
+    let clone = payloads[0].clone();
+    assert_eq!(clone.as_ptr(), payloads[0].as_ptr());
+    // Both payloads point into the buffered message, three payload bytes
+    // and a two-byte object header apart.
+    assert_eq!(
+        payloads[1].as_ptr() as usize,
+        payloads[0].as_ptr() as usize + 5
+    );
+}
+
+#[test]
+fn test_stream_type_prefix() {
+    let message = TestSubscribeMessage::new();
+    let mut data = vec![StreamType::Control as u8];
+    data.extend_from_slice(message.packet_sample());
+
+    // Raw QUIC consumes the prefix, even when it arrives on its own.
+    let mut parser = MessageParser::new(false);
+    parser.set_stream_type_prefix(true);
+    parser.process_data(&mut &data[..0], false);
+    assert_eq!(parser.stream_type(), None);
+    parser.process_data(&mut &data[..1], false);
+    assert_eq!(parser.stream_type(), Some(StreamType::Control));
+    parser.process_data(&mut &data[1..], false);
+    assert!(matches!(
+        parser.poll_event(),
+        Some(MessageParserEvent::ControlMessage(_))
+    ));
+    assert!(parser.poll_event().is_none());
+
+    // WebTransport streams carry no prefix.
+    let mut parser = MessageParser::new(true);
+    parser.set_stream_type_prefix(true);
+    parser.process_data(&mut message.packet_sample(), false);
+    assert_eq!(parser.stream_type(), None);
+    assert!(matches!(
+        parser.poll_event(),
+        Some(MessageParserEvent::ControlMessage(_))
+    ));
+
+    // Unknown stream types are rejected.
+    let mut parser = MessageParser::new(false);
+    parser.set_stream_type_prefix(true);
+    parser.process_data(&mut [0x3fu8].as_slice(), false);
+    match parser.poll_event() {
+        Some(MessageParserEvent::ParsingError(code, reason)) => {
+            assert_eq!(code, ErrorCode::ProtocolViolation);
+            assert_eq!(reason, "Unknown stream type 0x3f");
+        }
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn test_stream_type_mismatch() {
+    fn first_error(parser: &mut MessageParser, data: &[u8]) -> Option<String> {
+        parser.process_data(&mut &data[..], false);
+        match parser.poll_event() {
+            Some(MessageParserEvent::ParsingError(code, reason)) => {
+                assert_eq!(code, ErrorCode::ProtocolViolation);
+                Some(reason)
+            }
+            _ => None,
+        }
+    }
+    let object = TestObjectStreamMessage::new();
+    let subscribe = TestSubscribeMessage::new();
+
+    let mut parser = MessageParser::new(false);
+    parser.set_expected_stream_type(StreamType::Control);
+    assert_eq!(
+        first_error(&mut parser, object.packet_sample()).as_deref(),
+        Some("Received object on control stream")
+    );
+
+    let mut parser = MessageParser::new(false);
+    parser.set_expected_stream_type(StreamType::Data);
+    assert_eq!(parser.expected_stream_type(), Some(StreamType::Data));
+    assert_eq!(parser.stream_type(), None);
+    assert_eq!(
+        first_error(&mut parser, subscribe.packet_sample()).as_deref(),
+        Some("Received control message on data stream")
+    );
+
+    // Length-prefixed control messages are checked as well.
+    let mut framed = vec![];
+    subscribe
+        .packet_sample()
+        .len()
+        .serialize(&mut framed)
+        .unwrap();
+    framed.extend_from_slice(subscribe.packet_sample());
+    let mut parser = MessageParser::new(false);
+    parser.set_length_prefixed(true);
+    parser.set_expected_stream_type(StreamType::Data);
+    assert_eq!(
+        first_error(&mut parser, &framed).as_deref(),
+        Some("Received control message on data stream")
+    );
+
+    // The matching kind parses as usual.
+    let mut parser = MessageParser::new(false);
+    parser.set_expected_stream_type(StreamType::Data);
+    assert_eq!(first_error(&mut parser, object.packet_sample()), None);
+
+    // A stream-type prefix sets the kind, and must agree with the one set.
+    let mut data = vec![StreamType::Data as u8];
+    data.extend_from_slice(subscribe.packet_sample());
+    let mut parser = MessageParser::new(false);
+    parser.set_stream_type_prefix(true);
+    assert_eq!(
+        first_error(&mut parser, &data).as_deref(),
+        Some("Received control message on data stream")
+    );
+
+    let mut parser = MessageParser::new(false);
+    parser.set_stream_type_prefix(true);
+    parser.set_expected_stream_type(StreamType::Control);
+    assert_eq!(
+        first_error(&mut parser, &data).as_deref(),
+        Some("Unexpected stream type Data")
+    );
+}
+
+#[test]
+fn test_length_prefixed_skips_unknown_message() -> Result<()> {
+    let message = TestSubscribeMessage::new();
+    let subscribe = message.packet_sample();
+    let mut data = vec![];
+    subscribe.len().serialize(&mut data)?;
+    data.extend_from_slice(subscribe);
+    data.extend_from_slice(&[0x04, 0x3f, 0x01, 0x02, 0x03]); // unknown type 0x3f
+    subscribe.len().serialize(&mut data)?;
+    data.extend_from_slice(subscribe);
+
+    for split in 0..data.len() {
+        let mut parser = MessageParser::new(false);
+        parser.set_length_prefixed(true);
+        parser.process_data(&mut &data[..split], false);
+        parser.process_data(&mut &data[split..], false);
+        let events: Vec<MessageParserEvent> = core::iter::from_fn(|| parser.poll_event()).collect();
+        assert_eq!(events.len(), 3, "split {}", split);
+        assert!(matches!(events[0], MessageParserEvent::ControlMessage(_)));
+        assert!(matches!(
+            events[1],
+            MessageParserEvent::UnknownControlMessage {
+                message_type: 0x3f,
+                len: 4
+            }
+        ));
+        assert!(matches!(events[2], MessageParserEvent::ControlMessage(_)));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_custom_message_type() -> Result<()> {
+    let data = [
+        0x04, 0x3f, 0x01, 0x02, 0x03, // custom type 0x3f
+        0x03, 0x3e, 0x04, 0x05, // unregistered type 0x3e
+    ];
+
+    let mut parser = MessageParser::new(false);
+    parser.set_length_prefixed(true);
+    parser.register_custom_type(0x3f);
+    parser.process_data(&mut &data[..], false);
+    match parser.poll_event() {
+        Some(MessageParserEvent::CustomMessage {
+            message_type,
+            payload,
+        }) => {
+            assert_eq!(message_type, 0x3f);
+            assert_eq!(&payload[..], &[0x01, 0x02, 0x03]);
+        }
+        _ => panic!("expected a custom message"),
+    }
+    assert!(matches!(
+        parser.poll_event(),
+        Some(MessageParserEvent::UnknownControlMessage {
+            message_type: 0x3e,
+            len: 3
+        })
+    ));
+    assert!(parser.poll_event().is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_length_prefixed_length_mismatch() -> Result<()> {
+    let message = TestSubscribeMessage::new();
+    let subscribe = message.packet_sample();
+    let mut data = vec![];
+    // The frame ends before the track namespace.
+    3usize.serialize(&mut data)?;
+    data.extend_from_slice(&subscribe[..3]);
+
+    let mut parser = MessageParser::new(false);
+    parser.set_length_prefixed(true);
+    parser.process_data(&mut data.as_slice(), false);
+    match parser.poll_event() {
+        Some(MessageParserEvent::ParsingError(code, reason)) => {
+            assert_eq!(code, ErrorCode::ProtocolViolation);
+            assert_eq!(reason, "Control message length mismatch");
+        }
+        _ => unreachable!(),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_parameter_length_overruns_message() -> Result<()> {
+    let announce = [
+        0x06, // type
+        0x03, 0x66, 0x6f, 0x6f, // track_namespace = "foo"
+        0x01, // 1 parameter
+        0x02, 0x0a, 0x62, 0x61, 0x72, // authorization_info is 10 bytes, 3 present
+    ];
+
+    // On its own stream the rest of the parameter may still arrive.
+    let mut parser = MessageParser::new(false);
+    parser.process_data(&mut &announce[..], false);
+    assert!(parser.poll_event().is_none());
+
+    // A frame bounds the message, so the parameter can never complete.
+    let mut data = vec![];
+    announce.len().serialize(&mut data)?;
+    data.extend_from_slice(&announce);
+    let mut parser = MessageParser::new(false);
+    parser.set_length_prefixed(true);
+    parser.process_data(&mut data.as_slice(), false);
+    match parser.poll_event() {
+        Some(MessageParserEvent::ParsingError(code, reason)) => {
+            assert_eq!(code, ErrorCode::ParameterLengthMismatch);
+            assert_eq!(reason, "Parameter length exceeds message length");
+        }
+        _ => unreachable!(),
+    }
+    assert!(parser.poll_event().is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_process_datagrams_isolates_errors() -> Result<()> {
+    let datagram = TestObjectDatagramMessage::new();
+    let good = Bytes::copy_from_slice(datagram.packet_sample());
+    let bad = good.slice(..3); // Truncated inside the object header.
+
+    let mut parser = MessageParser::new(false);
+    parser.process_datagrams([good.clone(), bad, good].into_iter());
+    let events: Vec<_> = core::iter::from_fn(|| parser.poll_event()).collect();
+    assert_eq!(events.len(), 3);
+    for i in [0, 2] {
+        match &events[i] {
+            MessageParserEvent::ObjectMessage(object_header, payload, true) => {
+                assert!(datagram.equal_field_values(&MessageStructuredData::Object(*object_header)));
+                assert_eq!(&payload[..], b"foo");
+            }
+            _ => unreachable!(),
+        }
+    }
+    assert!(matches!(
+        events[1],
+        MessageParserEvent::ParsingError(ErrorCode::ProtocolViolation, _)
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_length_prefixed_framer() -> Result<()> {
+    let message = TestSubscribeMessage::new();
+    let mut data = vec![];
+    if let MessageStructuredData::Control(control_message) = message.structured_data() {
+        MessageFramer::serialize_length_prefixed_control_message(control_message, &mut data)?;
+    }
+
+    let mut parser = MessageParser::new(false);
+    parser.set_length_prefixed(true);
+    parser.process_data(&mut data.as_slice(), false);
+    match parser.poll_event() {
+        Some(MessageParserEvent::ControlMessage(control_message)) => {
+            assert!(message.equal_field_values(&MessageStructuredData::Control(control_message)))
+        }
+        _ => unreachable!(),
+    }
+
+    Ok(())
+}
@@ -15,9 +15,9 @@ use crate::message::track_status::{TrackStatus, TrackStatusCode};
 use crate::message::track_status_request::TrackStatusRequest;
 use crate::message::unannounce::UnAnnounce;
 use crate::message::unsubscribe::UnSubscribe;
-use crate::message::{ControlMessage, MessageType, Version, MAX_MESSSAGE_HEADER_SIZE};
-use crate::message::{FilterType, FullSequence, Role};
-use crate::{Deserializer, Error, Result, Serializer, VarInt};
+use crate::message::{ControlMessage, MessageType, Version, MAX_MESSAGE_HEADER_SIZE};
+use crate::message::{FilterType, FullSequence, Priority, Role};
+use crate::{Deserializer, Error, Parameters, Result, Serializer, VarInt};
 use bytes::{Buf, BufMut};
 use std::ops::{Deref, DerefMut};
 
@@ -46,7 +46,7 @@ pub(crate) trait TestMessageBase {
 
 pub(crate) struct TestMessage {
     message_type: MessageType,
-    wire_image: [u8; MAX_MESSSAGE_HEADER_SIZE + 20],
+    wire_image: [u8; MAX_MESSAGE_HEADER_SIZE + 20],
     wire_image_size: usize,
 }
 
@@ -54,7 +54,7 @@ impl TestMessage {
     fn new(message_type: MessageType) -> Self {
         Self {
             message_type,
-            wire_image: [0u8; MAX_MESSSAGE_HEADER_SIZE + 20],
+            wire_image: [0u8; MAX_MESSAGE_HEADER_SIZE + 20],
             wire_image_size: 0,
         }
     }
@@ -192,6 +192,7 @@ impl TestObjectMessage {
                 group_id: 5,
                 object_id: 6,
                 object_send_order: 7,
+                publisher_priority: Priority::default(),
                 object_status: ObjectStatus::Normal,
                 object_forwarding_preference: message_type
                     .get_object_forwarding_preference()
@@ -758,13 +759,14 @@ impl TestSubscribeMessage {
         let subscribe = Subscribe {
             subscribe_id: 1,
             track_alias: 2,
-            track_namespace: "foo".to_string(),
+            track_namespace: "foo".into(),
             track_name: "abcd".to_string(),
             filter_type: FilterType::AbsoluteStart(FullSequence {
                 group_id: 4,
                 object_id: 1,
             }),
-            authorization_info: Some("bar".to_string()),
+            authorization_info: Some("bar".into()),
+            parameters: Parameters::new(),
         };
         let raw_packet = vec![
             0x03, 0x01, 0x02, // id and alias
@@ -854,6 +856,7 @@ impl TestSubscribeOkMessage {
         let subscribe_ok = SubscribeOk {
             subscribe_id: 1,
             expires: 3,
+            group_order: None,
             largest_group_object: Some(FullSequence {
                 group_id: 12,
                 object_id: 20,
@@ -917,6 +920,9 @@ impl TestMessageBase for TestSubscribeOkMessage {
         if cast.expires != self.subscribe_ok.expires {
             return false;
         }
+        if cast.group_order != self.subscribe_ok.group_order {
+            return false;
+        }
         if cast.largest_group_object != self.subscribe_ok.largest_group_object {
             return false;
         }
@@ -1183,7 +1189,9 @@ impl TestSubscribeUpdateMessage {
                 group_id: 4,
                 object_id: 5,
             }),
-            authorization_info: Some("bar".to_string()),
+            subscriber_priority: None,
+            authorization_info: Some("bar".into()),
+            parameters: Parameters::new(),
         };
         let raw_packet = vec![
             0x02, 0x02, 0x03, 0x01, 0x05, 0x06, // start and end sequences
@@ -1262,8 +1270,9 @@ impl TestAnnounceMessage {
     pub(crate) fn new() -> Self {
         let mut base = TestMessage::new(MessageType::Announce);
         let announce = Announce {
-            track_namespace: "foo".to_string(),
-            authorization_info: Some("bar".to_string()),
+            track_namespace: "foo".into(),
+            authorization_info: Some("bar".into()),
+            parameters: Parameters::new(),
         };
         let raw_packet = vec![
             0x06, 0x03, 0x66, 0x6f, 0x6f, // track_namespace = "foo"
@@ -1333,7 +1342,7 @@ impl TestAnnounceOkMessage {
     pub(crate) fn new() -> Self {
         let mut base = TestMessage::new(MessageType::AnnounceOk);
         let announce_ok = AnnounceOk {
-            track_namespace: "foo".to_string(),
+            track_namespace: "foo".into(),
         };
         let raw_packet = vec![
             0x07, 0x03, 0x66, 0x6f, 0x6f, // track_namespace = "foo"
@@ -1399,7 +1408,7 @@ impl TestAnnounceErrorMessage {
     pub(crate) fn new() -> Self {
         let mut base = TestMessage::new(MessageType::AnnounceError);
         let announce_error = AnnounceError {
-            track_namespace: "foo".to_string(),
+            track_namespace: "foo".into(),
             error_code: 1,
             reason_phrase: "bar".to_string(),
         };
@@ -1475,7 +1484,7 @@ impl TestAnnounceCancelMessage {
     pub(crate) fn new() -> Self {
         let mut base = TestMessage::new(MessageType::AnnounceCancel);
         let announce_cancel = AnnounceCancel {
-            track_namespace: "foo".to_string(),
+            track_namespace: "foo".into(),
         };
         let raw_packet = vec![
             0x0c, 0x03, 0x66, 0x6f, 0x6f, // track_namespace = "foo"
@@ -1541,7 +1550,7 @@ impl TestUnAnnounceMessage {
     pub(crate) fn new() -> Self {
         let mut base = TestMessage::new(MessageType::UnAnnounce);
         let un_announce = UnAnnounce {
-            track_namespace: "foo".to_string(),
+            track_namespace: "foo".into(),
         };
         let raw_packet = vec![
             0x09, 0x03, 0x66, 0x6f, 0x6f, // track_namespace
@@ -1607,7 +1616,7 @@ impl TestTrackStatusRequestMessage {
     pub(crate) fn new() -> Self {
         let mut base = TestMessage::new(MessageType::TrackStatusRequest);
         let track_status_request = TrackStatusRequest {
-            track_namespace: "foo".to_string(),
+            track_namespace: "foo".into(),
             track_name: "abcd".to_string(),
         };
         let raw_packet = vec![
@@ -1681,7 +1690,7 @@ impl TestTrackStatusMessage {
     pub(crate) fn new() -> Self {
         let mut base = TestMessage::new(MessageType::TrackStatus);
         let track_status = TrackStatus {
-            track_namespace: "foo".to_string(),
+            track_namespace: "foo".into(),
             track_name: "abcd".to_string(),
             status_code: TrackStatusCode::InProgress as u64,
             last_group_object: FullSequence {
@@ -1764,7 +1773,7 @@ impl TestGoAwayMessage {
     pub(crate) fn new() -> Self {
         let mut base = TestMessage::new(MessageType::GoAway);
         let go_away = GoAway {
-            new_session_uri: "foo".to_string(),
+            new_session_uri: Some("foo".to_string()),
         };
         let raw_packet = vec![0x10, 0x03, 0x66, 0x6f, 0x6f];
         base.set_wire_image(&raw_packet, raw_packet.len());
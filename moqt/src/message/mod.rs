@@ -5,7 +5,7 @@ use crate::message::announce_ok::AnnounceOk;
 use crate::message::client_setup::ClientSetup;
 use crate::message::go_away::GoAway;
 use crate::message::message_parser::ErrorCode;
-use crate::message::object::ObjectForwardingPreference;
+use crate::message::object::{ObjectForwardingPreference, ObjectHeader};
 use crate::message::server_setup::ServerSetup;
 use crate::message::subscribe::Subscribe;
 use crate::message::subscribe_done::SubscribeDone;
@@ -16,15 +16,23 @@ use crate::message::track_status::TrackStatus;
 use crate::message::track_status_request::TrackStatusRequest;
 use crate::message::unannounce::UnAnnounce;
 use crate::message::unsubscribe::UnSubscribe;
-use crate::{Deserializer, Error, Result, Serializer};
-use bytes::{Buf, BufMut};
+use crate::serde::counting_writer::CountingWriter;
+use crate::serde::varint::VarInt;
+use crate::{Deserializer, Error, LengthPrefixedBytes, Result, Serializer};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use bytes::{Buf, BufMut, Bytes};
 
 pub mod announce;
 pub mod announce_cancel;
 pub mod announce_error;
 pub mod announce_ok;
 pub mod client_setup;
+pub mod gap_detector;
 pub mod go_away;
+pub mod group_order_validator;
 pub mod message_framer;
 pub mod message_parser;
 pub mod object;
@@ -40,18 +48,33 @@ pub mod unannounce;
 pub mod unsubscribe;
 
 #[cfg(test)]
+#[allow(
+    clippy::assertions_on_constants,
+    clippy::nonminimal_bool,
+    clippy::unnecessary_unwrap
+)]
 mod message_framer_test;
 #[cfg(test)]
+#[allow(
+    clippy::assertions_on_constants,
+    clippy::nonminimal_bool,
+    clippy::useless_vec
+)]
 mod message_parser_test;
 #[cfg(test)]
 mod message_test;
 
-/// The maximum length of a message, excluding and OBJECT payload.
+/// The default maximum length of a message, excluding an OBJECT payload.
 /// This prevents DoS attack via forcing the parser to buffer a large
-/// message (OBJECT payloads are not buffered by the parser)
-pub const MAX_MESSSAGE_HEADER_SIZE: usize = 2048;
+/// message (OBJECT payloads are not buffered by the parser). See
+/// `MessageType::max_message_size` for the per-type limits.
+pub const MAX_MESSAGE_HEADER_SIZE: usize = 2048;
+
+#[deprecated(note = "use MAX_MESSAGE_HEADER_SIZE")]
+pub const MAX_MESSSAGE_HEADER_SIZE: usize = MAX_MESSAGE_HEADER_SIZE;
 
 #[derive(Default, Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub enum MessageType {
     #[default]
     ObjectStream = 0x0,
@@ -77,11 +100,41 @@ pub enum MessageType {
 }
 
 impl MessageType {
+    /// Returns true for the message types that carry objects, whether on a
+    /// stream or in a datagram.
+    pub fn is_object_type(&self) -> bool {
+        matches!(
+            *self,
+            MessageType::ObjectStream
+                | MessageType::ObjectDatagram
+                | MessageType::StreamHeaderTrack
+                | MessageType::StreamHeaderGroup
+        )
+    }
+
+    #[deprecated(note = "use is_object_type")]
     pub fn is_object_message(&self) -> bool {
-        *self == MessageType::ObjectStream
-            || *self == MessageType::ObjectDatagram
-            || *self == MessageType::StreamHeaderTrack
-            || *self == MessageType::StreamHeaderGroup
+        self.is_object_type()
+    }
+
+    /// Returns true for the message types sent on the control stream.
+    pub fn is_control_type(&self) -> bool {
+        !self.is_object_type()
+    }
+
+    /// The maximum length of a message of this type, excluding any OBJECT
+    /// payload, that the parser will buffer while waiting for the rest of it.
+    /// SETUP and ANNOUNCE may carry many versions or parameters, while messages
+    /// made only of a few varints are kept small.
+    pub fn max_message_size(&self) -> usize {
+        match *self {
+            MessageType::ClientSetup | MessageType::ServerSetup | MessageType::Announce => {
+                4 * MAX_MESSAGE_HEADER_SIZE
+            }
+            MessageType::Subscribe | MessageType::SubscribeUpdate => MAX_MESSAGE_HEADER_SIZE / 2,
+            MessageType::SubscribeOk | MessageType::UnSubscribe => 64,
+            _ => MAX_MESSAGE_HEADER_SIZE,
+        }
     }
 
     pub fn is_object_without_payload_length(&self) -> bool {
@@ -102,7 +155,7 @@ impl MessageType {
 impl TryFrom<u64> for MessageType {
     type Error = Error;
 
-    fn try_from(value: u64) -> std::result::Result<Self, Self::Error> {
+    fn try_from(value: u64) -> core::result::Result<Self, Self::Error> {
         match value {
             0x0 => Ok(MessageType::ObjectStream),
             0x1 => Ok(MessageType::ObjectDatagram),
@@ -146,24 +199,300 @@ impl Serializer for MessageType {
     }
 }
 
+/// The type of a raw QUIC stream, sent as a varint ahead of its first message
+/// when the stream-type prefix is in use. WebTransport streams carry no prefix.
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum StreamType {
+    #[default]
+    Control = 0x0,
+    Data = 0x1,
+}
+
+impl TryFrom<u64> for StreamType {
+    type Error = Error;
+
+    fn try_from(value: u64) -> core::result::Result<Self, Self::Error> {
+        match value {
+            0x0 => Ok(StreamType::Control),
+            0x1 => Ok(StreamType::Data),
+            _ => Err(Error::ErrParseError(
+                ErrorCode::ProtocolViolation,
+                format!("Unknown stream type 0x{:x}", value),
+            )),
+        }
+    }
+}
+
+impl Deserializer for StreamType {
+    fn deserialize<R: Buf>(r: &mut R) -> Result<(Self, usize)> {
+        let (v, l) = u64::deserialize(r)?;
+        let t = v.try_into()?;
+        Ok((t, l))
+    }
+}
+
+impl Serializer for StreamType {
+    fn serialize<W: BufMut>(&self, w: &mut W) -> Result<usize> {
+        (*self as u64).serialize(w)
+    }
+}
+
+/// The most elements a tuple namespace may hold, as in Draft-06.
+pub const MAX_NAMESPACE_ELEMENTS: u64 = 32;
+
+/// A track namespace as an ordered tuple of binary elements (Draft-06 and
+/// later), encoded as an element count followed by each element with a length
+/// prefix. Both the tuple and its elements may be empty.
+#[derive(Default, Debug, Clone, Eq, PartialEq, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct TrackNamespace(pub Vec<Bytes>);
+
+impl TrackNamespace {
+    pub fn new(elements: Vec<Bytes>) -> Self {
+        Self(elements)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// A single-element tuple holding the string's bytes.
+impl From<String> for TrackNamespace {
+    fn from(value: String) -> Self {
+        Self(vec![Bytes::from(value)])
+    }
+}
+
+impl Deserializer for TrackNamespace {
+    fn deserialize<R: Buf>(r: &mut R) -> Result<(Self, usize)> {
+        let (count, mut l) = u64::deserialize(r)?;
+        if count > MAX_NAMESPACE_ELEMENTS {
+            return Err(Error::ErrParseError(
+                ErrorCode::ProtocolViolation,
+                format!("Too many track namespace elements: {}", count),
+            ));
+        }
+        let mut elements = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let (element, el) = LengthPrefixedBytes::deserialize(r)?;
+            elements.push(element.0);
+            l += el;
+        }
+        Ok((Self(elements), l))
+    }
+}
+
+impl Serializer for TrackNamespace {
+    fn serialize<W: BufMut>(&self, w: &mut W) -> Result<usize> {
+        let mut l = self.0.len().serialize(w)?;
+        for element in &self.0 {
+            l += LengthPrefixedBytes(element.clone()).serialize(w)?;
+        }
+        Ok(l)
+    }
+}
+
+/// A track namespace in either of its wire forms: a single string before
+/// Draft-06, or a tuple from Draft-06 on. A string and the one-element tuple
+/// holding it name the same namespace, so comparison and hashing look only at
+/// the namespace elements.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(untagged))]
+pub enum Namespace {
+    Legacy(String),
+    Tuple(TrackNamespace),
+}
+
+impl Default for Namespace {
+    fn default() -> Self {
+        Namespace::Legacy(String::new())
+    }
+}
+
+impl PartialEq for Namespace {
+    fn eq(&self, other: &Self) -> bool {
+        self.elements().eq(other.elements())
+    }
+}
+
+impl Eq for Namespace {}
+
+impl PartialOrd for Namespace {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Namespace {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        self.elements().cmp(other.elements())
+    }
+}
+
+impl core::hash::Hash for Namespace {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        for element in self.elements() {
+            element.hash(state);
+        }
+    }
+}
+
+impl From<String> for Namespace {
+    fn from(value: String) -> Self {
+        Namespace::Legacy(value)
+    }
+}
+
+impl From<&str> for Namespace {
+    fn from(value: &str) -> Self {
+        Namespace::Legacy(value.to_string())
+    }
+}
+
+impl From<TrackNamespace> for Namespace {
+    fn from(value: TrackNamespace) -> Self {
+        Namespace::Tuple(value)
+    }
+}
+
+impl Namespace {
+    /// The namespace in tuple form. A legacy string namespace is a tuple of
+    /// one element.
+    pub fn to_tuple(&self) -> TrackNamespace {
+        match self {
+            Namespace::Legacy(namespace) => TrackNamespace::from(namespace.clone()),
+            Namespace::Tuple(tuple) => tuple.clone(),
+        }
+    }
+
+    /// The namespace as a single string, if it can be written that way: a
+    /// tuple qualifies when it is one UTF-8 element.
+    pub fn as_legacy(&self) -> Option<&str> {
+        match self {
+            Namespace::Legacy(namespace) => Some(namespace),
+            Namespace::Tuple(TrackNamespace(elements)) if elements.len() == 1 => {
+                core::str::from_utf8(&elements[0]).ok()
+            }
+            Namespace::Tuple(_) => None,
+        }
+    }
+
+    /// Whether the namespace is an empty string or a tuple of no elements.
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Namespace::Legacy(namespace) => namespace.is_empty(),
+            Namespace::Tuple(tuple) => tuple.is_empty(),
+        }
+    }
+
+    fn elements(&self) -> impl Iterator<Item = &[u8]> {
+        let (legacy, tuple) = match self {
+            Namespace::Legacy(namespace) => (Some(namespace.as_bytes()), &[][..]),
+            Namespace::Tuple(tuple) => (None, &tuple.0[..]),
+        };
+        legacy
+            .into_iter()
+            .chain(tuple.iter().map(|element| &element[..]))
+    }
+
+    /// Reads the namespace in the layout used by `version`: a tuple if
+    /// [`Version::uses_namespace_tuple`], otherwise a single string.
+    pub fn deserialize_with_version<R: Buf>(version: Version, r: &mut R) -> Result<(Self, usize)> {
+        if !version.uses_namespace_tuple() {
+            return Self::deserialize(r);
+        }
+        let (track_namespace, tnsl) = TrackNamespace::deserialize(r)?;
+        Ok((Namespace::Tuple(track_namespace), tnsl))
+    }
+
+    /// Writes the namespace in the layout used by `version`. A tuple other
+    /// than a single UTF-8 element cannot be written for a version that
+    /// expects a single string.
+    pub fn serialize_with_version<W: BufMut>(&self, version: Version, w: &mut W) -> Result<usize> {
+        if !version.uses_namespace_tuple() {
+            return self.serialize(w);
+        }
+        match self {
+            Namespace::Legacy(_) => self.to_tuple().serialize(w),
+            Namespace::Tuple(tuple) => tuple.serialize(w),
+        }
+    }
+}
+
+/// Reads the single-string layout.
+impl Deserializer for Namespace {
+    fn deserialize<R: Buf>(r: &mut R) -> Result<(Self, usize)> {
+        let (track_namespace, tnsl) = String::deserialize(r)?;
+        Ok((Namespace::Legacy(track_namespace), tnsl))
+    }
+}
+
+/// Writes the single-string layout. Use `serialize_with_version` for a tuple
+/// of several elements.
+impl Serializer for Namespace {
+    fn serialize<W: BufMut>(&self, w: &mut W) -> Result<usize> {
+        let Some(track_namespace) = self.as_legacy() else {
+            return Err(Error::ErrOther(
+                "Tuple track namespace in a single-string version".to_string(),
+            ));
+        };
+        let mut l = track_namespace.len().serialize(w)?;
+        if w.remaining_mut() < track_namespace.len() {
+            return Err(Error::ErrBufferTooShort);
+        }
+        w.put_slice(track_namespace.as_bytes());
+        l += track_namespace.len();
+        Ok(l)
+    }
+
+    fn encoded_size(&self) -> Result<usize> {
+        let Some(track_namespace) = self.as_legacy() else {
+            return self.serialize(&mut CountingWriter::new());
+        };
+        Ok(track_namespace.len().encoded_size()? + track_namespace.len())
+    }
+}
+
+/// A track's namespace and name.
 #[derive(Default, Debug, Clone, Eq, PartialEq, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct FullTrackName {
-    pub track_namespace: String,
+    pub track_namespace: Namespace,
     pub track_name: String,
 }
 
 impl FullTrackName {
     pub fn new(track_namespace: String, track_name: String) -> Self {
         Self {
-            track_namespace,
+            track_namespace: Namespace::Legacy(track_namespace),
             track_name,
         }
     }
-}
 
-impl Deserializer for FullTrackName {
-    fn deserialize<R: Buf>(r: &mut R) -> Result<(Self, usize)> {
-        let (track_namespace, tnsl) = String::deserialize(r)?;
+    pub fn with_namespace_tuple(track_namespace: TrackNamespace, track_name: String) -> Self {
+        Self {
+            track_namespace: Namespace::Tuple(track_namespace),
+            track_name,
+        }
+    }
+
+    /// The namespace in tuple form. A legacy string namespace is a tuple of
+    /// one element.
+    pub fn namespace_tuple(&self) -> TrackNamespace {
+        self.track_namespace.to_tuple()
+    }
+
+    /// Reads the name in the layout used by `version` (see
+    /// [`Namespace::deserialize_with_version`]).
+    pub fn deserialize_with_version<R: Buf>(version: Version, r: &mut R) -> Result<(Self, usize)> {
+        let (track_namespace, tnsl) = Namespace::deserialize_with_version(version, r)?;
         let (track_name, tnl) = String::deserialize(r)?;
         Ok((
             Self {
@@ -173,17 +502,71 @@ impl Deserializer for FullTrackName {
             tnsl + tnl,
         ))
     }
+
+    /// Writes the name in the layout used by `version` (see
+    /// [`Namespace::serialize_with_version`]).
+    pub fn serialize_with_version<W: BufMut>(&self, version: Version, w: &mut W) -> Result<usize> {
+        let mut l = self.track_namespace.serialize_with_version(version, w)?;
+        l += self.track_name.serialize(w)?;
+        Ok(l)
+    }
+
+    /// A track alias derived from the name alone, so that relays choosing
+    /// hash-based aliases agree on them independently. This is the 64-bit
+    /// FNV-1a hash of a layout tag followed by the wire encoding of the name
+    /// (the namespace followed by the length-prefixed track name), truncated to
+    /// 62 bits so that it fits in a varint. Names that can be written with a
+    /// single-string namespace are hashed in that layout under tag 0, whichever
+    /// form holds them, and other tuples in the tuple layout under tag 1. The
+    /// tag keeps an empty string apart from an empty tuple, whose encodings
+    /// are otherwise the same.
+    pub fn alias_hash(&self) -> u64 {
+        const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+        const FNV_PRIME: u64 = 0x100000001b3;
+        const LEGACY_LAYOUT: u8 = 0;
+        const TUPLE_LAYOUT: u8 = 1;
+
+        let mut encoded = Vec::new();
+        // Writing to a Vec cannot fail.
+        let _ = match self.track_namespace.as_legacy() {
+            Some(_) => {
+                encoded.push(LEGACY_LAYOUT);
+                self.serialize(&mut encoded)
+            }
+            None => {
+                encoded.push(TUPLE_LAYOUT);
+                self.serialize_with_version(Version::Draft06, &mut encoded)
+            }
+        };
+        let hash = encoded.iter().fold(FNV_OFFSET_BASIS, |hash, &byte| {
+            (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+        });
+        hash & VarInt::MAX.into_inner()
+    }
 }
 
+/// Reads the single-string layout.
+impl Deserializer for FullTrackName {
+    fn deserialize<R: Buf>(r: &mut R) -> Result<(Self, usize)> {
+        Self::deserialize_with_version(Version::Draft04, r)
+    }
+}
+
+/// Writes the single-string layout. Use `serialize_with_version` for a name
+/// that holds a tuple namespace of several elements.
 impl Serializer for FullTrackName {
     fn serialize<W: BufMut>(&self, w: &mut W) -> Result<usize> {
-        let mut l = self.track_namespace.serialize(w)?;
-        l += self.track_name.serialize(w)?;
-        Ok(l)
+        self.serialize_with_version(Version::Draft04, w)
+    }
+
+    fn encoded_size(&self) -> Result<usize> {
+        Ok(self.track_namespace.encoded_size()? + self.track_name.encoded_size()?)
     }
 }
 
-#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, PartialOrd, Hash)]
+/// FullSequence orders lexicographically by (group_id, object_id).
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct FullSequence {
     pub group_id: u64,
     pub object_id: u64,
@@ -197,11 +580,41 @@ impl FullSequence {
         }
     }
 
+    /// The position of the object `header` describes.
+    pub fn from_header(header: &ObjectHeader) -> Self {
+        Self::new(header.group_id, header.object_id)
+    }
+
     pub fn next(&self) -> Self {
-        Self {
+        self.next_object()
+    }
+
+    /// The position of the following object in the same group. Panics if the
+    /// object id is `u64::MAX`; see [`FullSequence::checked_next_object`].
+    pub fn next_object(&self) -> Self {
+        self.checked_next_object().expect("object_id overflow")
+    }
+
+    /// The position of the first object in the following group. Panics if the
+    /// group id is `u64::MAX`; see [`FullSequence::checked_next_group`].
+    pub fn next_group(&self) -> Self {
+        self.checked_next_group().expect("group_id overflow")
+    }
+
+    /// As `next_object`, but None when the object id would overflow.
+    pub fn checked_next_object(&self) -> Option<Self> {
+        Some(Self {
             group_id: self.group_id,
-            object_id: self.object_id + 1,
-        }
+            object_id: self.object_id.checked_add(1)?,
+        })
+    }
+
+    /// As `next_group`, but None when the group id would overflow.
+    pub fn checked_next_group(&self) -> Option<Self> {
+        Some(Self {
+            group_id: self.group_id.checked_add(1)?,
+            object_id: 0,
+        })
     }
 }
 
@@ -227,13 +640,104 @@ impl Serializer for FullSequence {
     }
 }
 
+/// The order in which the publisher delivers groups, sent as a single byte.
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum GroupOrder {
+    #[default]
+    Ascending = 0x1,
+    Descending = 0x2,
+}
+
+impl TryFrom<u64> for GroupOrder {
+    type Error = Error;
+
+    fn try_from(value: u64) -> core::result::Result<Self, Self::Error> {
+        match value {
+            0x1 => Ok(GroupOrder::Ascending),
+            0x2 => Ok(GroupOrder::Descending),
+            _ => Err(Error::ErrInvalidGroupOrder(value)),
+        }
+    }
+}
+
+impl Deserializer for GroupOrder {
+    fn deserialize<R: Buf>(r: &mut R) -> Result<(Self, usize)> {
+        if !r.has_remaining() {
+            return Err(Error::ErrUnexpectedEnd);
+        }
+        let group_order = (r.get_u8() as u64).try_into()?;
+        Ok((group_order, 1))
+    }
+}
+
+impl Serializer for GroupOrder {
+    fn serialize<W: BufMut>(&self, w: &mut W) -> Result<usize> {
+        if !w.has_remaining_mut() {
+            return Err(Error::ErrBufferTooShort);
+        }
+        w.put_u8(*self as u8);
+        Ok(1)
+    }
+}
+
+/// The object send order of Draft-04 and earlier, for scheduling objects
+/// against each other. A lower send order is sent sooner, so a `SendOrder`
+/// that compares less has the higher priority; wrap it in
+/// `core::cmp::Reverse` for a max-heap such as `BinaryHeap`.
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct SendOrder(pub u64);
+
+/// The publisher's priority for an object, which replaces the object send
+/// order from Draft-05. Sent as a single byte.
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Priority(pub u8);
+
+/// Fails with ErrInvalidPriority for values that do not fit in a byte, such as
+/// an object send order carried over from an earlier draft.
+impl TryFrom<u64> for Priority {
+    type Error = Error;
+
+    fn try_from(value: u64) -> core::result::Result<Self, Self::Error> {
+        u8::try_from(value)
+            .map(Priority)
+            .map_err(|_| Error::ErrInvalidPriority(value))
+    }
+}
+
+impl Deserializer for Priority {
+    fn deserialize<R: Buf>(r: &mut R) -> Result<(Self, usize)> {
+        if !r.has_remaining() {
+            return Err(Error::ErrUnexpectedEnd);
+        }
+        Ok((Priority(r.get_u8()), 1))
+    }
+}
+
+impl Serializer for Priority {
+    fn serialize<W: BufMut>(&self, w: &mut W) -> Result<usize> {
+        if !w.has_remaining_mut() {
+            return Err(Error::ErrBufferTooShort);
+        }
+        w.put_u8(self.0);
+        Ok(1)
+    }
+}
+
+/// AbsoluteRange carries the start, the end group and the end object. The end
+/// object is none when the range runs to the end of the end group, which is
+/// encoded as an end object of 0 on the wire; otherwise the wire value is the
+/// end object plus one.
 #[derive(Default, Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub enum FilterType {
     #[default]
     LatestGroup, // = 0x1,
-    LatestObject,                              // = 0x2,
-    AbsoluteStart(FullSequence),               // = 0x3,
-    AbsoluteRange(FullSequence, FullSequence), // = 0x4,
+    LatestObject,                                  // = 0x2,
+    AbsoluteStart(FullSequence),                   // = 0x3,
+    AbsoluteRange(FullSequence, u64, Option<u64>), // = 0x4,
 }
 
 impl FilterType {
@@ -242,7 +746,52 @@ impl FilterType {
             FilterType::LatestGroup => 0x1,
             FilterType::LatestObject => 0x2,
             FilterType::AbsoluteStart(_) => 0x3,
-            FilterType::AbsoluteRange(_, _) => 0x4,
+            FilterType::AbsoluteRange(_, _, _) => 0x4,
+        }
+    }
+
+    pub fn latest_group() -> Self {
+        FilterType::LatestGroup
+    }
+
+    pub fn latest_object() -> Self {
+        FilterType::LatestObject
+    }
+
+    pub fn absolute_start(group_id: u64, object_id: u64) -> Self {
+        FilterType::AbsoluteStart(FullSequence::new(group_id, object_id))
+    }
+
+    /// A range from the start position through `end_object` of `end_group`,
+    /// or through the whole end group if `end_object` is none.
+    pub fn absolute_range(
+        start_group_id: u64,
+        start_object_id: u64,
+        end_group: u64,
+        end_object: Option<u64>,
+    ) -> Self {
+        FilterType::AbsoluteRange(
+            FullSequence::new(start_group_id, start_object_id),
+            end_group,
+            end_object,
+        )
+    }
+
+    /// Returns whether `sequence` lies within the filter's bounds, which
+    /// include both the start and the end. The latest filters start wherever
+    /// the publisher is when the subscription begins, so they match any
+    /// position.
+    pub fn contains(&self, sequence: FullSequence) -> bool {
+        match *self {
+            FilterType::LatestGroup | FilterType::LatestObject => true,
+            FilterType::AbsoluteStart(start) => sequence >= start,
+            FilterType::AbsoluteRange(start, end_group, end_object) => {
+                sequence >= start
+                    && (sequence.group_id < end_group
+                        || (sequence.group_id == end_group
+                            && end_object
+                                .is_none_or(|end_object| sequence.object_id <= end_object)))
+            }
         }
     }
 }
@@ -259,24 +808,30 @@ impl Deserializer for FilterType {
             }
             0x4 => {
                 let (start, sl) = FullSequence::deserialize(r)?;
-                let (mut end, el) = FullSequence::deserialize(r)?;
-                if end.object_id == 0 {
-                    end.object_id = u64::MAX;
+                let (end_group, egl) = u64::deserialize(r)?;
+                let (end_object, eol) = u64::deserialize(r)?;
+                let end_object = if end_object == 0 {
+                    None
                 } else {
-                    end.object_id -= 1;
-                }
-                if end.group_id < start.group_id {
+                    Some(end_object - 1)
+                };
+                if end_group < start.group_id {
                     Err(Error::ErrParseError(
                         ErrorCode::ProtocolViolation,
                         "End group is less than start group".to_string(),
                     ))
-                } else if end.group_id == start.group_id && end.object_id < start.object_id {
+                } else if end_group == start.group_id
+                    && end_object.is_some_and(|end_object| end_object < start.object_id)
+                {
                     Err(Error::ErrParseError(
                         ErrorCode::ProtocolViolation,
                         "End object comes before start object".to_string(),
                     ))
                 } else {
-                    Ok((FilterType::AbsoluteRange(start, end), vl + sl + el))
+                    Ok((
+                        FilterType::AbsoluteRange(start, end_group, end_object),
+                        vl + sl + egl + eol,
+                    ))
                 }
             }
             _ => Err(Error::ErrInvalidFilterType(v)),
@@ -294,25 +849,30 @@ impl Serializer for FilterType {
                 l += start.serialize(w)?;
                 Ok(l)
             }
-            FilterType::AbsoluteRange(start, mut end) => {
-                if end.group_id < start.group_id {
+            FilterType::AbsoluteRange(start, end_group, end_object) => {
+                if end_group < start.group_id {
                     return Err(Error::ErrFrameError(
                         "End group is less than start group".to_string(),
                     ));
-                } else if end.group_id == start.group_id && end.object_id < start.object_id {
+                } else if end_group == start.group_id
+                    && end_object.is_some_and(|end_object| end_object < start.object_id)
+                {
                     return Err(Error::ErrFrameError(
                         "End object comes before start object".to_string(),
                     ));
                 }
+                let end_object = if let Some(end_object) = end_object {
+                    end_object
+                        .checked_add(1)
+                        .ok_or(Error::ErrVarIntBoundsExceeded)?
+                } else {
+                    0
+                };
 
                 let mut l = 0x4u64.serialize(w)?;
                 l += start.serialize(w)?;
-                if end.object_id == u64::MAX {
-                    end.object_id = 0;
-                } else {
-                    end.object_id += 1;
-                }
-                l += end.serialize(w)?;
+                l += end_group.serialize(w)?;
+                l += end_object.serialize(w)?;
                 Ok(l)
             }
         }
@@ -320,6 +880,7 @@ impl Serializer for FilterType {
 }
 
 #[derive(Default, Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 #[repr(u32)]
 pub enum Version {
     #[default]
@@ -328,6 +889,8 @@ pub enum Version {
     Draft02 = 0xff000002,
     Draft03 = 0xff000003,
     Draft04 = 0xff000004,
+    Draft05 = 0xff000005,
+    Draft06 = 0xff000006,
     Unsupported(u32),
 }
 
@@ -339,11 +902,58 @@ impl From<u64> for Version {
             0xff000002 => Version::Draft02,
             0xff000003 => Version::Draft03,
             0xff000004 => Version::Draft04,
+            0xff000005 => Version::Draft05,
+            0xff000006 => Version::Draft06,
             _ => Version::Unsupported(value as u32),
         }
     }
 }
 
+impl From<Version> for u64 {
+    fn from(value: Version) -> Self {
+        match value {
+            Version::Draft00 => 0xff000000,
+            Version::Draft01 => 0xff000001,
+            Version::Draft02 => 0xff000002,
+            Version::Draft03 => 0xff000003,
+            Version::Draft04 => 0xff000004,
+            Version::Draft05 => 0xff000005,
+            Version::Draft06 => 0xff000006,
+            Version::Unsupported(value) => value as u64,
+        }
+    }
+}
+
+impl Version {
+    /// Whether objects carry a one-byte publisher priority instead of the
+    /// object send order.
+    pub fn uses_publisher_priority(&self) -> bool {
+        matches!(self, Version::Draft05 | Version::Draft06)
+    }
+
+    /// Whether SUBSCRIBE_OK carries the group order, added in Draft-05.
+    pub fn uses_group_order(&self) -> bool {
+        matches!(self, Version::Draft05 | Version::Draft06)
+    }
+
+    /// Whether SUBSCRIBE_UPDATE carries the subscriber priority, added in
+    /// Draft-05.
+    pub fn uses_subscriber_priority(&self) -> bool {
+        matches!(self, Version::Draft05 | Version::Draft06)
+    }
+
+    /// Whether GOAWAY carries a new session URI. Drafts before Draft-03 define
+    /// GOAWAY without fields.
+    pub fn go_away_has_uri(&self) -> bool {
+        !matches!(self, Version::Draft00 | Version::Draft01 | Version::Draft02)
+    }
+
+    /// Whether track namespaces are tuples rather than a single string.
+    pub fn uses_namespace_tuple(&self) -> bool {
+        matches!(self, Version::Draft06)
+    }
+}
+
 impl Deserializer for Version {
     fn deserialize<R: Buf>(r: &mut R) -> Result<(Self, usize)> {
         let (v, vl) = u64::deserialize(r)?;
@@ -354,19 +964,12 @@ impl Deserializer for Version {
 
 impl Serializer for Version {
     fn serialize<W: BufMut>(&self, w: &mut W) -> Result<usize> {
-        let value: u64 = match *self {
-            Version::Draft00 => 0xff000000,
-            Version::Draft01 => 0xff000001,
-            Version::Draft02 => 0xff000002,
-            Version::Draft03 => 0xff000003,
-            Version::Draft04 => 0xff000004,
-            Version::Unsupported(value) => value as u64,
-        };
-        value.serialize(w)
+        u64::from(*self).serialize(w)
     }
 }
 
 #[derive(Default, Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub enum Role {
     Publisher = 0x1,
     Subscriber = 0x2,
@@ -377,7 +980,7 @@ pub enum Role {
 impl TryFrom<u64> for Role {
     type Error = Error;
 
-    fn try_from(value: u64) -> std::result::Result<Self, Self::Error> {
+    fn try_from(value: u64) -> core::result::Result<Self, Self::Error> {
         match value {
             0x1 => Ok(Role::Publisher),
             0x2 => Ok(Role::Subscriber),
@@ -387,6 +990,39 @@ impl TryFrom<u64> for Role {
     }
 }
 
+impl Role {
+    /// Whether an endpoint in this role can receive the control message type.
+    /// SETUP and GOAWAY go both ways; subscription and track status requests
+    /// flow to publishers, and their replies and announcements flow to
+    /// subscribers.
+    pub fn receives(&self, message_type: MessageType) -> bool {
+        let to_publisher = matches!(
+            message_type,
+            MessageType::Subscribe
+                | MessageType::SubscribeUpdate
+                | MessageType::UnSubscribe
+                | MessageType::TrackStatusRequest
+                | MessageType::AnnounceOk
+                | MessageType::AnnounceError
+                | MessageType::AnnounceCancel
+        );
+        let to_subscriber = matches!(
+            message_type,
+            MessageType::SubscribeOk
+                | MessageType::SubscribeError
+                | MessageType::SubscribeDone
+                | MessageType::TrackStatus
+                | MessageType::Announce
+                | MessageType::UnAnnounce
+        );
+        match self {
+            Role::Publisher => !to_subscriber,
+            Role::Subscriber => !to_publisher,
+            Role::PubSub => true,
+        }
+    }
+}
+
 impl Deserializer for Role {
     fn deserialize<R: Buf>(r: &mut R) -> Result<(Self, usize)> {
         let (v, vl) = u64::deserialize(r)?;
@@ -402,6 +1038,7 @@ impl Serializer for Role {
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub enum ControlMessage {
     SubscribeUpdate(SubscribeUpdate),
     Subscribe(Subscribe),
@@ -422,7 +1059,82 @@ pub enum ControlMessage {
 }
 
 impl Deserializer for ControlMessage {
+    /// Decodes a control message. On failure, the reader is left where
+    /// decoding stopped.
+    ///
+    /// In debug builds, each decoded message is checked with
+    /// [`ControlMessage::check_round_trip`], panicking if the encoder and
+    /// decoder disagree. The `skip-round-trip-check` feature turns this off.
     fn deserialize<R: Buf>(r: &mut R) -> Result<(Self, usize)> {
+        ControlMessage::deserialize_with_version(Version::Draft04, r)
+    }
+}
+
+impl ControlMessage {
+    /// As [`ControlMessage::deserialize`], for the layout used by `version`.
+    /// SUBSCRIBE_OK, SUBSCRIBE_UPDATE, GOAWAY and the messages carrying a
+    /// track namespace differ between the supported drafts.
+    pub fn deserialize_with_version<R: Buf>(version: Version, r: &mut R) -> Result<(Self, usize)> {
+        let decoded = ControlMessage::deserialize_message(version, r)?;
+        #[cfg(all(debug_assertions, not(feature = "skip-round-trip-check")))]
+        if let Err(err) = decoded.0.check_round_trip_with_version(version) {
+            panic!("control message does not round-trip: {}", err);
+        }
+        Ok(decoded)
+    }
+
+    /// The length of the message in the layout used by `version`.
+    pub fn encoded_size_with_version(&self, version: Version) -> Result<usize> {
+        self.serialize_with_version(version, &mut CountingWriter::new())
+    }
+
+    pub fn message_type(&self) -> MessageType {
+        match self {
+            ControlMessage::SubscribeUpdate(_) => MessageType::SubscribeUpdate,
+            ControlMessage::Subscribe(_) => MessageType::Subscribe,
+            ControlMessage::SubscribeOk(_) => MessageType::SubscribeOk,
+            ControlMessage::SubscribeError(_) => MessageType::SubscribeError,
+            ControlMessage::Announce(_) => MessageType::Announce,
+            ControlMessage::AnnounceOk(_) => MessageType::AnnounceOk,
+            ControlMessage::AnnounceError(_) => MessageType::AnnounceError,
+            ControlMessage::UnAnnounce(_) => MessageType::UnAnnounce,
+            ControlMessage::UnSubscribe(_) => MessageType::UnSubscribe,
+            ControlMessage::SubscribeDone(_) => MessageType::SubscribeDone,
+            ControlMessage::AnnounceCancel(_) => MessageType::AnnounceCancel,
+            ControlMessage::TrackStatusRequest(_) => MessageType::TrackStatusRequest,
+            ControlMessage::TrackStatus(_) => MessageType::TrackStatus,
+            ControlMessage::GoAway(_) => MessageType::GoAway,
+            ControlMessage::ClientSetup(_) => MessageType::ClientSetup,
+            ControlMessage::ServerSetup(_) => MessageType::ServerSetup,
+        }
+    }
+
+    /// Re-encodes the message and checks that decoding the result gives the
+    /// same message. The comparison is by value, since re-encoding is not
+    /// byte-for-byte idempotent for every input: varints are re-encoded in
+    /// their shortest form, and parameter blocks (SETUP, SUBSCRIBE,
+    /// SUBSCRIBE_UPDATE and ANNOUNCE) are re-encoded in key order with
+    /// repeated unknown parameters dropped.
+    pub fn check_round_trip(&self) -> Result<()> {
+        self.check_round_trip_with_version(Version::Draft04)
+    }
+
+    /// As [`ControlMessage::check_round_trip`], for the layout used by
+    /// `version`.
+    pub fn check_round_trip_with_version(&self, version: Version) -> Result<()> {
+        let mut encoded = Vec::new();
+        self.serialize_with_version(version, &mut encoded)?;
+        let (decoded, _) = ControlMessage::deserialize_message(version, &mut &encoded[..])?;
+        if decoded != *self {
+            return Err(Error::ErrOther(format!(
+                "{:?} re-decodes as {:?}",
+                self, decoded
+            )));
+        }
+        Ok(())
+    }
+
+    fn deserialize_message<R: Buf>(version: Version, r: &mut R) -> Result<(Self, usize)> {
         let (message_type, mtl) = MessageType::deserialize(r)?;
         match message_type {
             MessageType::ObjectStream
@@ -430,15 +1142,15 @@ impl Deserializer for ControlMessage {
             | MessageType::StreamHeaderGroup
             | MessageType::ObjectDatagram => Err(Error::ErrInvalidMessageType(message_type as u64)),
             MessageType::SubscribeUpdate => {
-                let (m, ml) = SubscribeUpdate::deserialize(r)?;
+                let (m, ml) = SubscribeUpdate::deserialize_with_version(version, r)?;
                 Ok((ControlMessage::SubscribeUpdate(m), mtl + ml))
             }
             MessageType::Subscribe => {
-                let (m, ml) = Subscribe::deserialize(r)?;
+                let (m, ml) = Subscribe::deserialize_with_version(version, r)?;
                 Ok((ControlMessage::Subscribe(m), mtl + ml))
             }
             MessageType::SubscribeOk => {
-                let (m, ml) = SubscribeOk::deserialize(r)?;
+                let (m, ml) = SubscribeOk::deserialize_with_version(version, r)?;
                 Ok((ControlMessage::SubscribeOk(m), mtl + ml))
             }
             MessageType::SubscribeError => {
@@ -446,19 +1158,19 @@ impl Deserializer for ControlMessage {
                 Ok((ControlMessage::SubscribeError(m), mtl + ml))
             }
             MessageType::Announce => {
-                let (m, ml) = Announce::deserialize(r)?;
+                let (m, ml) = Announce::deserialize_with_version(version, r)?;
                 Ok((ControlMessage::Announce(m), mtl + ml))
             }
             MessageType::AnnounceOk => {
-                let (m, ml) = AnnounceOk::deserialize(r)?;
+                let (m, ml) = AnnounceOk::deserialize_with_version(version, r)?;
                 Ok((ControlMessage::AnnounceOk(m), mtl + ml))
             }
             MessageType::AnnounceError => {
-                let (m, ml) = AnnounceError::deserialize(r)?;
+                let (m, ml) = AnnounceError::deserialize_with_version(version, r)?;
                 Ok((ControlMessage::AnnounceError(m), mtl + ml))
             }
             MessageType::UnAnnounce => {
-                let (m, ml) = UnAnnounce::deserialize(r)?;
+                let (m, ml) = UnAnnounce::deserialize_with_version(version, r)?;
                 Ok((ControlMessage::UnAnnounce(m), mtl + ml))
             }
             MessageType::UnSubscribe => {
@@ -470,19 +1182,19 @@ impl Deserializer for ControlMessage {
                 Ok((ControlMessage::SubscribeDone(m), mtl + ml))
             }
             MessageType::AnnounceCancel => {
-                let (m, ml) = AnnounceCancel::deserialize(r)?;
+                let (m, ml) = AnnounceCancel::deserialize_with_version(version, r)?;
                 Ok((ControlMessage::AnnounceCancel(m), mtl + ml))
             }
             MessageType::TrackStatusRequest => {
-                let (m, ml) = TrackStatusRequest::deserialize(r)?;
+                let (m, ml) = TrackStatusRequest::deserialize_with_version(version, r)?;
                 Ok((ControlMessage::TrackStatusRequest(m), mtl + ml))
             }
             MessageType::TrackStatus => {
-                let (m, ml) = TrackStatus::deserialize(r)?;
+                let (m, ml) = TrackStatus::deserialize_with_version(version, r)?;
                 Ok((ControlMessage::TrackStatus(m), mtl + ml))
             }
             MessageType::GoAway => {
-                let (m, ml) = GoAway::deserialize(r)?;
+                let (m, ml) = GoAway::deserialize_with_version(version, r)?;
                 Ok((ControlMessage::GoAway(m), mtl + ml))
             }
             MessageType::ClientSetup => {
@@ -497,22 +1209,71 @@ impl Deserializer for ControlMessage {
     }
 }
 
+/// Fails with a protocol violation if a name `field` that `message` requires is
+/// empty.
+pub(crate) fn require_non_empty(value: &str, message: &str, field: &str) -> Result<()> {
+    if value.is_empty() {
+        return Err(Error::ErrParseError(
+            ErrorCode::ProtocolViolation,
+            format!("{} has empty {}", message, field),
+        ));
+    }
+    Ok(())
+}
+
+/// Decodes one control message from the front of `data`, returning it with
+/// the number of bytes consumed. This is the entry point for fuzzing: it never
+/// panics on arbitrary input (apart from the debug-build round-trip check in
+/// [`ControlMessage::deserialize`]), truncated input fails with
+/// `ErrUnexpectedEnd` or `ErrParameterLengthMismatch`, and any bytes after the
+/// message are left unread. Errors are wrapped in [`Error::ErrAt`] with the
+/// offset at which decoding stopped.
+pub fn try_decode_control(data: &[u8]) -> Result<(ControlMessage, usize)> {
+    let mut r = data;
+    ControlMessage::deserialize(&mut r).map_err(|err| err.at(data.len() - r.len()))
+}
+
+/// Decodes a slice holding exactly one control message. Bytes after the
+/// message are a protocol violation.
+impl TryFrom<&[u8]> for ControlMessage {
+    type Error = Error;
+
+    fn try_from(data: &[u8]) -> core::result::Result<Self, Self::Error> {
+        let (control_message, len) = try_decode_control(data)?;
+        if len != data.len() {
+            return Err(Error::ErrParseError(
+                ErrorCode::ProtocolViolation,
+                "Trailing bytes after control message".to_string(),
+            ));
+        }
+        Ok(control_message)
+    }
+}
+
+/// Uses the Draft-04 layout.
 impl Serializer for ControlMessage {
     fn serialize<W: BufMut>(&self, w: &mut W) -> Result<usize> {
+        self.serialize_with_version(Version::Draft04, w)
+    }
+}
+
+impl ControlMessage {
+    /// Writes the message in the layout used by `version`.
+    pub fn serialize_with_version<W: BufMut>(&self, version: Version, w: &mut W) -> Result<usize> {
         match self {
             ControlMessage::SubscribeUpdate(subscribe_update) => {
                 let mut l = MessageType::SubscribeUpdate.serialize(w)?;
-                l += subscribe_update.serialize(w)?;
+                l += subscribe_update.serialize_with_version(version, w)?;
                 Ok(l)
             }
             ControlMessage::Subscribe(subscribe) => {
                 let mut l = MessageType::Subscribe.serialize(w)?;
-                l += subscribe.serialize(w)?;
+                l += subscribe.serialize_with_version(version, w)?;
                 Ok(l)
             }
             ControlMessage::SubscribeOk(subscribe_ok) => {
                 let mut l = MessageType::SubscribeOk.serialize(w)?;
-                l += subscribe_ok.serialize(w)?;
+                l += subscribe_ok.serialize_with_version(version, w)?;
                 Ok(l)
             }
             ControlMessage::SubscribeError(subscribe_error) => {
@@ -522,22 +1283,22 @@ impl Serializer for ControlMessage {
             }
             ControlMessage::Announce(announce) => {
                 let mut l = MessageType::Announce.serialize(w)?;
-                l += announce.serialize(w)?;
+                l += announce.serialize_with_version(version, w)?;
                 Ok(l)
             }
             ControlMessage::AnnounceOk(announce_ok) => {
                 let mut l = MessageType::AnnounceOk.serialize(w)?;
-                l += announce_ok.serialize(w)?;
+                l += announce_ok.serialize_with_version(version, w)?;
                 Ok(l)
             }
             ControlMessage::AnnounceError(announce_error) => {
                 let mut l = MessageType::AnnounceError.serialize(w)?;
-                l += announce_error.serialize(w)?;
+                l += announce_error.serialize_with_version(version, w)?;
                 Ok(l)
             }
             ControlMessage::UnAnnounce(unannounce) => {
                 let mut l = MessageType::UnAnnounce.serialize(w)?;
-                l += unannounce.serialize(w)?;
+                l += unannounce.serialize_with_version(version, w)?;
                 Ok(l)
             }
             ControlMessage::UnSubscribe(unsubscribe) => {
@@ -552,22 +1313,22 @@ impl Serializer for ControlMessage {
             }
             ControlMessage::AnnounceCancel(announce_cancel) => {
                 let mut l = MessageType::AnnounceCancel.serialize(w)?;
-                l += announce_cancel.serialize(w)?;
+                l += announce_cancel.serialize_with_version(version, w)?;
                 Ok(l)
             }
             ControlMessage::TrackStatusRequest(track_status_request) => {
                 let mut l = MessageType::TrackStatusRequest.serialize(w)?;
-                l += track_status_request.serialize(w)?;
+                l += track_status_request.serialize_with_version(version, w)?;
                 Ok(l)
             }
             ControlMessage::TrackStatus(track_status) => {
                 let mut l = MessageType::TrackStatus.serialize(w)?;
-                l += track_status.serialize(w)?;
+                l += track_status.serialize_with_version(version, w)?;
                 Ok(l)
             }
             ControlMessage::GoAway(go_away) => {
                 let mut l = MessageType::GoAway.serialize(w)?;
-                l += go_away.serialize(w)?;
+                l += go_away.serialize_with_version(version, w)?;
                 Ok(l)
             }
             ControlMessage::ClientSetup(client_setup) => {
@@ -583,3 +1344,545 @@ impl Serializer for ControlMessage {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Parameters, VarInt};
+
+    #[test]
+    fn test_message_type_category() {
+        let object_types = [
+            (
+                MessageType::ObjectStream,
+                ObjectForwardingPreference::Object,
+            ),
+            (
+                MessageType::ObjectDatagram,
+                ObjectForwardingPreference::Datagram,
+            ),
+            (
+                MessageType::StreamHeaderTrack,
+                ObjectForwardingPreference::Track,
+            ),
+            (
+                MessageType::StreamHeaderGroup,
+                ObjectForwardingPreference::Group,
+            ),
+        ];
+        for (message_type, forwarding_preference) in object_types {
+            assert!(message_type.is_object_type());
+            assert!(!message_type.is_control_type());
+            assert_eq!(
+                message_type.get_object_forwarding_preference(),
+                Ok(forwarding_preference)
+            );
+            assert_eq!(forwarding_preference.get_message_type(), message_type);
+        }
+
+        let mut control_types = 0;
+        for value in 0..=0x51u64 {
+            let Ok(message_type) = MessageType::try_from(value) else {
+                continue;
+            };
+            if object_types.iter().any(|(t, _)| *t == message_type) {
+                continue;
+            }
+            assert!(message_type.is_control_type());
+            assert!(!message_type.is_object_type());
+            assert_eq!(
+                message_type.get_object_forwarding_preference(),
+                Err(Error::ErrInvalidMessageType(value))
+            );
+            control_types += 1;
+        }
+        assert_eq!(control_types, 16);
+    }
+
+    #[test]
+    fn test_encoded_size() -> Result<()> {
+        for value in [
+            0u64,
+            63,
+            64,
+            16383,
+            16384,
+            (1 << 30) - 1,
+            1 << 30,
+            (1 << 62) - 1,
+        ] {
+            let var = VarInt::from_u64(value)?;
+            let mut buf = vec![];
+            assert_eq!(var.encoded_size()?, var.serialize(&mut buf)?);
+            assert_eq!(value.encoded_size()?, buf.len());
+        }
+        // A value that cannot be encoded has no size, so it fails up front
+        // rather than when it is serialized.
+        assert_eq!(
+            (1u64 << 62).encoded_size(),
+            Err(Error::ErrVarIntBoundsExceeded)
+        );
+        let subscribe = Subscribe {
+            subscribe_id: 1 << 62,
+            track_name: "abcd".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            subscribe.encoded_size(),
+            Err(Error::ErrVarIntBoundsExceeded)
+        );
+
+        let full_track_name = FullTrackName::new("foo".to_string(), "abcd".to_string());
+        let mut buf = vec![];
+        let l = full_track_name.serialize(&mut buf)?;
+        assert_eq!(full_track_name.encoded_size()?, l);
+        assert_eq!(full_track_name.encoded_size()?, buf.len());
+
+        let messages = [
+            ControlMessage::Subscribe(Subscribe {
+                subscribe_id: 1,
+                track_alias: 2,
+                track_namespace: "foo".into(),
+                track_name: "abcd".to_string(),
+                filter_type: FilterType::AbsoluteRange(FullSequence::new(4, 1), 7, Some(2)),
+                authorization_info: Some("bar".into()),
+                parameters: Parameters::new(),
+            }),
+            ControlMessage::Subscribe(Subscribe {
+                subscribe_id: 1 << 20,
+                track_alias: 2,
+                track_namespace: "foo".repeat(30).into(),
+                track_name: "abcd".to_string(),
+                filter_type: FilterType::LatestGroup,
+                authorization_info: None,
+                parameters: Parameters::new(),
+            }),
+            ControlMessage::AnnounceOk(AnnounceOk {
+                track_namespace: "foo".into(),
+            }),
+            ControlMessage::GoAway(GoAway {
+                new_session_uri: Some("https://example.com".to_string()),
+            }),
+        ];
+        for message in messages {
+            let mut buf = Vec::with_capacity(message.encoded_size()?);
+            let l = message.serialize(&mut buf)?;
+            assert_eq!(message.encoded_size()?, l);
+            assert_eq!(message.encoded_size()?, buf.len());
+            if let ControlMessage::Subscribe(subscribe) = &message {
+                assert_eq!(subscribe.encoded_size()?, l - 1);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_filter_type_absolute_range() -> Result<()> {
+        let tests = vec![
+            (
+                // bounded range, end object 2 is encoded as 3
+                vec![0x04, 0x04, 0x01, 0x07, 0x03],
+                FilterType::AbsoluteRange(
+                    FullSequence {
+                        group_id: 4,
+                        object_id: 1,
+                    },
+                    7,
+                    Some(2),
+                ),
+            ),
+            (
+                // open-ended range, end object 0 means the whole end group
+                vec![0x04, 0x04, 0x01, 0x07, 0x00],
+                FilterType::AbsoluteRange(
+                    FullSequence {
+                        group_id: 4,
+                        object_id: 1,
+                    },
+                    7,
+                    None,
+                ),
+            ),
+        ];
+
+        for (expected_packet, expected_filter_type) in tests {
+            let mut cursor: &[u8] = expected_packet.as_ref();
+            let (actual_filter_type, actual_len) = FilterType::deserialize(&mut cursor)?;
+            assert_eq!(expected_filter_type, actual_filter_type);
+            assert_eq!(expected_packet.len(), actual_len);
+
+            let mut actual_packet = vec![];
+            let actual_len = expected_filter_type.serialize(&mut actual_packet)?;
+            assert_eq!(expected_packet, actual_packet);
+            assert_eq!(expected_packet.len(), actual_len);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_round_trip() -> Result<()> {
+        // SUBSCRIBE_OK with subscribe_id = 1 in a two-byte varint.
+        let packet = [0x04, 0x40, 0x01, 0x03, 0x01, 0x0c, 0x14];
+        let (message, _) = try_decode_control(&packet)?;
+        message.check_round_trip()?;
+        assert_ne!(&message.to_bytes()?[..], &packet[..]);
+
+        Ok(())
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_is_object_message_alias() {
+        assert!(MessageType::ObjectStream.is_object_message());
+        assert!(!MessageType::Subscribe.is_object_message());
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_max_message_header_size() {
+        assert_eq!(MAX_MESSAGE_HEADER_SIZE, 2048);
+        assert_eq!(MAX_MESSSAGE_HEADER_SIZE, MAX_MESSAGE_HEADER_SIZE);
+        assert_eq!(
+            MessageType::GoAway.max_message_size(),
+            MAX_MESSAGE_HEADER_SIZE
+        );
+    }
+
+    #[test]
+    fn test_filter_type_contains() {
+        assert_eq!(
+            FilterType::absolute_start(4, 1),
+            FilterType::AbsoluteStart(FullSequence::new(4, 1))
+        );
+        assert_eq!(
+            FilterType::absolute_range(4, 1, 6, None),
+            FilterType::AbsoluteRange(FullSequence::new(4, 1), 6, None)
+        );
+        assert!(FilterType::latest_group().contains(FullSequence::new(0, 0)));
+        assert!(FilterType::latest_object().contains(FullSequence::new(9, 9)));
+
+        let start = FilterType::absolute_start(4, 1);
+        assert!(start.contains(FullSequence::new(4, 1)));
+        assert!(start.contains(FullSequence::new(5, 0)));
+        assert!(!start.contains(FullSequence::new(4, 0)));
+        assert!(!start.contains(FullSequence::new(3, 9)));
+
+        let range = FilterType::absolute_range(4, 1, 6, Some(2));
+        assert!(range.contains(FullSequence::new(4, 1)));
+        assert!(range.contains(FullSequence::new(5, 100)));
+        assert!(range.contains(FullSequence::new(6, 2)));
+        assert!(!range.contains(FullSequence::new(4, 0)));
+        assert!(!range.contains(FullSequence::new(6, 3)));
+        assert!(!range.contains(FullSequence::new(7, 0)));
+
+        let whole_end_group = FilterType::absolute_range(4, 1, 6, None);
+        assert!(whole_end_group.contains(FullSequence::new(6, 1000)));
+        assert!(!whole_end_group.contains(FullSequence::new(7, 0)));
+    }
+
+    #[test]
+    fn test_full_sequence_overflow() {
+        let last = FullSequence::new(u64::MAX, u64::MAX);
+        assert_eq!(last.checked_next_object(), None);
+        assert_eq!(last.checked_next_group(), None);
+        assert_eq!(
+            FullSequence::new(u64::MAX, 1).checked_next_object(),
+            Some(FullSequence::new(u64::MAX, 2))
+        );
+        assert_eq!(
+            FullSequence::new(1, u64::MAX).checked_next_group(),
+            Some(FullSequence::new(2, 0))
+        );
+
+        // Range checks compare positions and never step past them.
+        let range = FilterType::absolute_range(u64::MAX, 0, u64::MAX, Some(u64::MAX));
+        assert!(range.contains(last));
+        assert!(!range.contains(FullSequence::new(u64::MAX - 1, u64::MAX)));
+        assert!(FilterType::absolute_range(0, 0, u64::MAX, None).contains(last));
+        assert!(FilterType::absolute_start(u64::MAX, u64::MAX).contains(last));
+    }
+
+    #[test]
+    fn test_full_track_name_alias_hash() {
+        let name = FullTrackName::new("foo".to_string(), "abcd".to_string());
+        assert_eq!(name.alias_hash(), 0x2beb1effdf78f074);
+        assert_eq!(name.alias_hash(), name.clone().alias_hash());
+        assert_ne!(
+            name.alias_hash(),
+            FullTrackName::new("foo".to_string(), "abce".to_string()).alias_hash()
+        );
+        // The length prefixes keep the namespace and name apart.
+        assert_ne!(
+            FullTrackName::new("ab".to_string(), "c".to_string()).alias_hash(),
+            FullTrackName::new("a".to_string(), "bc".to_string()).alias_hash()
+        );
+        // An empty string and an empty tuple share an encoding but are
+        // different namespaces.
+        assert_ne!(
+            FullTrackName::new(String::new(), "abcd".to_string()).alias_hash(),
+            FullTrackName::with_namespace_tuple(TrackNamespace::default(), "abcd".to_string())
+                .alias_hash()
+        );
+    }
+
+    #[test]
+    fn test_priority_range() {
+        assert_eq!(Priority::try_from(0), Ok(Priority(0)));
+        assert_eq!(Priority::try_from(255), Ok(Priority(255)));
+        assert_eq!(Priority::try_from(256), Err(Error::ErrInvalidPriority(256)));
+        assert_eq!(
+            Priority::try_from(u64::MAX),
+            Err(Error::ErrInvalidPriority(u64::MAX))
+        );
+    }
+
+    #[test]
+    fn test_full_sequence_ordering() {
+        assert!(FullSequence::new(1, 5) < FullSequence::new(2, 0));
+        assert!(FullSequence::new(2, 0) < FullSequence::new(2, 1));
+        assert_eq!(
+            FullSequence::new(3, 3).max(FullSequence::new(3, 2)),
+            FullSequence::new(3, 3)
+        );
+
+        assert_eq!(
+            FullSequence::new(1, 5).next_object(),
+            FullSequence::new(1, 6)
+        );
+        assert_eq!(
+            FullSequence::new(1, 5).next_group(),
+            FullSequence::new(2, 0)
+        );
+    }
+
+    #[test]
+    fn test_try_decode_control_truncated() -> Result<()> {
+        let message = ControlMessage::Announce(announce::Announce {
+            track_namespace: "foo".into(),
+            authorization_info: Some("bar".into()),
+            parameters: Parameters::new(),
+        });
+        let mut packet = vec![];
+        message.serialize(&mut packet)?;
+        packet.push(0xff); // trailing bytes are not consumed
+
+        let (decoded, len) = try_decode_control(&packet)?;
+        assert_eq!(decoded, message);
+        assert_eq!(len, packet.len() - 1);
+
+        for end in 0..len {
+            let err = try_decode_control(&packet[..end]).unwrap_err();
+            assert!(
+                matches!(
+                    err.root(),
+                    Error::ErrUnexpectedEnd | Error::ErrParameterLengthMismatch { .. }
+                ),
+                "{:?} at {}",
+                err,
+                end
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_try_decode_control_arbitrary_input() {
+        // A fixed xorshift sequence stands in for fuzzer input.
+        let mut state = 0x2545f4914f6cdd1du64;
+        for _ in 0..2000 {
+            let mut data = vec![];
+            for _ in 0..(state % 48) {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                data.push(state as u8);
+            }
+            // Bias towards valid control message types.
+            if let Some(first) = data.first_mut() {
+                *first %= 0x12;
+            }
+            let _ = try_decode_control(&data);
+        }
+    }
+
+    #[test]
+    fn test_try_decode_control_huge_count() {
+        // CLIENT_SETUP declaring 2^32 versions in an eight-byte varint.
+        let data = [0x40, 0x40, 0xc0, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00];
+        assert!(matches!(
+            try_decode_control(&data).unwrap_err().root(),
+            Error::ErrUnexpectedEnd
+        ));
+
+        let mut parser = message_parser::MessageParser::new(false);
+        parser.process_data(&mut &data[..], true);
+        assert!(matches!(
+            parser.poll_event(),
+            Some(message_parser::MessageParserEvent::ParsingError(..))
+        ));
+    }
+
+    fn round_trip<T>(value: T) -> Result<()>
+    where
+        T: Serializer + Deserializer + PartialEq + core::fmt::Debug,
+    {
+        let mut buf = vec![];
+        let written = value.serialize(&mut buf)?;
+        assert_eq!(written, buf.len());
+        assert_eq!(written, value.encoded_size()?);
+        let (decoded, read) = T::deserialize(&mut buf.as_slice())?;
+        assert_eq!(decoded, value);
+        assert_eq!(read, written);
+        Ok(())
+    }
+
+    #[test]
+    fn test_serializer_deserializer_round_trip() -> Result<()> {
+        round_trip(VarInt::from_u32(16384))?;
+        round_trip(FullSequence::new(3, 7))?;
+        round_trip(FullTrackName::new("foo".to_string(), "abcd".to_string()))?;
+        round_trip(GroupOrder::Descending)?;
+        round_trip(Role::Publisher)?;
+        round_trip(ControlMessage::Subscribe(subscribe::Subscribe {
+            subscribe_id: 1,
+            track_alias: 2,
+            track_namespace: "foo".into(),
+            track_name: "abcd".to_string(),
+            filter_type: FilterType::LatestObject,
+            authorization_info: Some("bar".into()),
+            parameters: Parameters::new(),
+        }))?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_bytes() -> Result<()> {
+        let message = ControlMessage::Announce(announce::Announce {
+            track_namespace: "foo".into(),
+            authorization_info: Some("bar".into()),
+            parameters: Parameters::new(),
+        });
+        let mut buf = vec![];
+        message.serialize(&mut buf)?;
+        let bytes = message.to_bytes()?;
+        assert_eq!(&bytes[..], &buf[..]);
+        assert_eq!(bytes.len(), message.encoded_size()?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_namespace_tuple_single_element() -> Result<()> {
+        let wire = [
+            0x01, 0x03, 0x66, 0x6f, 0x6f, // track_namespace = ("foo")
+            0x04, 0x61, 0x62, 0x63, 0x64, // track_name = "abcd"
+        ];
+        let (name, len) =
+            FullTrackName::deserialize_with_version(Version::Draft06, &mut &wire[..])?;
+        assert_eq!(len, wire.len());
+        assert_eq!(
+            name,
+            FullTrackName::with_namespace_tuple(
+                TrackNamespace::from("foo".to_string()),
+                "abcd".to_string()
+            )
+        );
+        let mut buf = vec![];
+        assert_eq!(
+            name.serialize_with_version(Version::Draft06, &mut buf)?,
+            wire.len()
+        );
+        assert_eq!(&buf[..], &wire[..]);
+
+        // A legacy name is written as a one-element tuple.
+        let mut buf = vec![];
+        FullTrackName::new("foo".to_string(), "abcd".to_string())
+            .serialize_with_version(Version::Draft06, &mut buf)?;
+        assert_eq!(&buf[..], &wire[..]);
+
+        // Earlier versions keep the single-string layout, which a
+        // one-element tuple fits.
+        let mut buf = vec![];
+        assert_eq!(
+            name.serialize_with_version(Version::Draft05, &mut buf)?,
+            name.encoded_size()?
+        );
+        assert_eq!(&buf[..], &wire[1..]);
+        assert_eq!(
+            FullTrackName::deserialize_with_version(Version::Draft05, &mut &wire[1..])?,
+            (
+                FullTrackName::new("foo".to_string(), "abcd".to_string()),
+                wire.len() - 1
+            )
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_full_track_name_forms_agree() -> Result<()> {
+        let legacy = FullTrackName::new("foo".to_string(), "abcd".to_string());
+        let tuple = FullTrackName::with_namespace_tuple(
+            TrackNamespace::from("foo".to_string()),
+            "abcd".to_string(),
+        );
+        assert_eq!(legacy, tuple);
+        assert_eq!(legacy.partial_cmp(&tuple), Some(core::cmp::Ordering::Equal));
+        assert_eq!(legacy.alias_hash(), tuple.alias_hash());
+        let names = std::collections::HashSet::from([legacy.clone()]);
+        assert!(names.contains(&tuple));
+        assert_eq!(legacy.to_bytes()?, tuple.to_bytes()?);
+
+        let other = FullTrackName::with_namespace_tuple(
+            TrackNamespace::new(vec![Bytes::from_static(b"foo"), Bytes::new()]),
+            "abcd".to_string(),
+        );
+        assert_ne!(legacy, other);
+        assert_ne!(legacy.alias_hash(), other.alias_hash());
+        Ok(())
+    }
+
+    #[test]
+    fn test_namespace_tuple_three_elements() -> Result<()> {
+        let wire = [
+            0x03, // 3 elements
+            0x03, 0x66, 0x6f, 0x6f, // "foo"
+            0x00, // ""
+            0x02, 0xc3, 0x28, // not UTF-8
+            0x03, 0x62, 0x61, 0x72, // track_name = "bar"
+        ];
+        let namespace = TrackNamespace::new(vec![
+            Bytes::from_static(b"foo"),
+            Bytes::new(),
+            Bytes::from_static(&[0xc3, 0x28]),
+        ]);
+        let (name, len) =
+            FullTrackName::deserialize_with_version(Version::Draft06, &mut &wire[..])?;
+        assert_eq!(len, wire.len());
+        assert_eq!(name.namespace_tuple(), namespace);
+        assert_eq!(name.track_name, "bar");
+        let mut buf = vec![];
+        name.serialize_with_version(Version::Draft06, &mut buf)?;
+        assert_eq!(&buf[..], &wire[..]);
+
+        // Only the tuple layout can hold it.
+        assert!(name.serialize(&mut vec![]).is_err());
+        assert!(name.encoded_size().is_err());
+
+        // An empty tuple is allowed; too many elements are not.
+        assert_eq!(
+            TrackNamespace::deserialize(&mut &[0x00][..])?,
+            (TrackNamespace::default(), 1)
+        );
+        assert!(matches!(
+            TrackNamespace::deserialize(&mut &[0x21][..]),
+            Err(Error::ErrParseError(ErrorCode::ProtocolViolation, _))
+        ));
+        assert_eq!(
+            TrackNamespace::deserialize(&mut &wire[..4]),
+            Err(Error::ErrUnexpectedEnd)
+        );
+        Ok(())
+    }
+}
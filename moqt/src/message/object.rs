@@ -1,6 +1,9 @@
-use crate::message::MessageType;
+use crate::message::{MessageType, Priority, SendOrder};
+use crate::{Error, Result};
+use core::fmt::{Display, Formatter};
 
 #[derive(Default, Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub enum ObjectForwardingPreference {
     #[default]
     Object,
@@ -20,7 +23,23 @@ impl ObjectForwardingPreference {
     }
 }
 
+impl From<ObjectForwardingPreference> for MessageType {
+    fn from(value: ObjectForwardingPreference) -> Self {
+        value.get_message_type()
+    }
+}
+
+/// Fails with ErrInvalidMessageType for message types that do not carry objects.
+impl TryFrom<MessageType> for ObjectForwardingPreference {
+    type Error = Error;
+
+    fn try_from(value: MessageType) -> core::result::Result<Self, Self::Error> {
+        value.get_object_forwarding_preference()
+    }
+}
+
 #[derive(Default, Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub enum ObjectStatus {
     #[default]
     Normal = 0x0,
@@ -44,17 +63,331 @@ impl From<u64> for ObjectStatus {
     }
 }
 
+impl From<ObjectStatus> for u64 {
+    fn from(value: ObjectStatus) -> Self {
+        value as u64
+    }
+}
+
 /// The data contained in every Object message, although the message type
 /// implies some of the values. |payload_length| has no value if the length
 /// is unknown (because it runs to the end of the stream.)
 #[derive(Default, Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct ObjectHeader {
     pub subscribe_id: u64,
     pub track_alias: u64,
     pub group_id: u64,
     pub object_id: u64,
     pub object_send_order: u64,
+    /// Sent in place of `object_send_order` from Draft-05.
+    pub publisher_priority: Priority,
     pub object_status: ObjectStatus,
     pub object_forwarding_preference: ObjectForwardingPreference,
     pub object_payload_length: Option<u64>,
 }
+
+impl ObjectHeader {
+    pub fn builder() -> ObjectHeaderBuilder {
+        ObjectHeaderBuilder::default()
+    }
+
+    pub fn send_order(&self) -> SendOrder {
+        SendOrder(self.object_send_order)
+    }
+
+    /// `(track_alias, group_id, object_id)`, which identifies the object within
+    /// a session, for keying caches. Tuples order by track, then group, then
+    /// object.
+    pub fn cache_key(&self) -> (u64, u64, u64) {
+        (self.track_alias, self.group_id, self.object_id)
+    }
+
+    /// Checks the invariants the framer relies on: Track and Group forwarding
+    /// need the payload length up front, and only Normal objects carry a
+    /// payload.
+    pub fn validate(&self) -> Result<()> {
+        if self.object_payload_length.is_none()
+            && matches!(
+                self.object_forwarding_preference,
+                ObjectForwardingPreference::Track | ObjectForwardingPreference::Group
+            )
+        {
+            return Err(Error::ErrTrackGroupForwardPreferenceRequiresLength);
+        }
+        if self.object_status != ObjectStatus::Normal
+            && self.object_payload_length.is_some_and(|length| length > 0)
+        {
+            return Err(Error::ErrNonEmptyPayloadMustBeWithNormalObjectStatus);
+        }
+        Ok(())
+    }
+}
+
+/// A one-line summary for logs, such as
+/// `OBJ sub=3 alias=2 g=4 o=1 order=0 status=Normal pref=Group len=512`. An
+/// object whose payload runs to the end of the stream shows `len=-`.
+impl Display for ObjectHeader {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let status = match self.object_status {
+            ObjectStatus::Normal => "Normal",
+            ObjectStatus::ObjectDoesNotExist => "NoObject",
+            ObjectStatus::GroupDoesNotExist => "NoGroup",
+            ObjectStatus::EndOfGroup => "EndGroup",
+            ObjectStatus::EndOfTrack => "EndTrack",
+            ObjectStatus::Invalid => "Invalid",
+        };
+        write!(
+            f,
+            "OBJ sub={} alias={} g={} o={} order={} status={} pref={:?} len=",
+            self.subscribe_id,
+            self.track_alias,
+            self.group_id,
+            self.object_id,
+            self.object_send_order,
+            status,
+            self.object_forwarding_preference,
+        )?;
+        match self.object_payload_length {
+            Some(length) => write!(f, "{}", length),
+            None => write!(f, "-"),
+        }
+    }
+}
+
+/// Builds an [`ObjectHeader`], checking it with [`ObjectHeader::validate`].
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq)]
+pub struct ObjectHeaderBuilder {
+    header: ObjectHeader,
+}
+
+impl ObjectHeaderBuilder {
+    pub fn subscribe_id(mut self, subscribe_id: u64) -> Self {
+        self.header.subscribe_id = subscribe_id;
+        self
+    }
+
+    pub fn track_alias(mut self, track_alias: u64) -> Self {
+        self.header.track_alias = track_alias;
+        self
+    }
+
+    pub fn group_id(mut self, group_id: u64) -> Self {
+        self.header.group_id = group_id;
+        self
+    }
+
+    pub fn object_id(mut self, object_id: u64) -> Self {
+        self.header.object_id = object_id;
+        self
+    }
+
+    pub fn object_send_order(mut self, object_send_order: u64) -> Self {
+        self.header.object_send_order = object_send_order;
+        self
+    }
+
+    pub fn publisher_priority(mut self, publisher_priority: Priority) -> Self {
+        self.header.publisher_priority = publisher_priority;
+        self
+    }
+
+    pub fn object_status(mut self, object_status: ObjectStatus) -> Self {
+        self.header.object_status = object_status;
+        self
+    }
+
+    pub fn object_forwarding_preference(
+        mut self,
+        object_forwarding_preference: ObjectForwardingPreference,
+    ) -> Self {
+        self.header.object_forwarding_preference = object_forwarding_preference;
+        self
+    }
+
+    pub fn object_payload_length(mut self, object_payload_length: u64) -> Self {
+        self.header.object_payload_length = Some(object_payload_length);
+        self
+    }
+
+    pub fn build(self) -> Result<ObjectHeader> {
+        self.header.validate()?;
+        Ok(self.header)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::message::FullSequence;
+    use alloc::string::ToString;
+
+    #[test]
+    fn test_display() -> Result<()> {
+        let header = ObjectHeader::builder()
+            .subscribe_id(3)
+            .track_alias(2)
+            .group_id(4)
+            .object_id(1)
+            .object_forwarding_preference(ObjectForwardingPreference::Group)
+            .object_payload_length(512)
+            .build()?;
+        assert_eq!(
+            header.to_string(),
+            "OBJ sub=3 alias=2 g=4 o=1 order=0 status=Normal pref=Group len=512"
+        );
+
+        let end_of_group = ObjectHeader {
+            object_status: ObjectStatus::EndOfGroup,
+            object_payload_length: None,
+            ..header
+        };
+        assert_eq!(
+            end_of_group.to_string(),
+            "OBJ sub=3 alias=2 g=4 o=1 order=0 status=EndGroup pref=Group len=-"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cache_key() -> Result<()> {
+        let header = ObjectHeader::builder()
+            .subscribe_id(3)
+            .track_alias(2)
+            .group_id(4)
+            .object_id(1)
+            .build()?;
+        assert_eq!(header.cache_key(), (2, 4, 1));
+        assert_eq!(FullSequence::from_header(&header), FullSequence::new(4, 1));
+
+        let next = ObjectHeader {
+            object_id: 2,
+            ..header
+        };
+        assert!(header.cache_key() < next.cache_key());
+        Ok(())
+    }
+
+    #[test]
+    fn test_send_order() -> Result<()> {
+        let sooner = ObjectHeader::builder().object_send_order(1).build()?;
+        let later = ObjectHeader::builder().object_send_order(2).build()?;
+        assert_eq!(sooner.send_order(), SendOrder(1));
+        assert!(sooner.send_order() < later.send_order());
+
+        let mut headers = [later, sooner];
+        headers.sort_by_key(ObjectHeader::send_order);
+        assert_eq!(headers, [sooner, later]);
+
+        let mut queue = std::collections::BinaryHeap::new();
+        queue.push(core::cmp::Reverse(later.send_order()));
+        queue.push(core::cmp::Reverse(sooner.send_order()));
+        assert_eq!(queue.pop(), Some(core::cmp::Reverse(SendOrder(1))));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_forwarding_preference_round_trip() {
+        let preferences = [
+            ObjectForwardingPreference::Object,
+            ObjectForwardingPreference::Datagram,
+            ObjectForwardingPreference::Track,
+            ObjectForwardingPreference::Group,
+        ];
+        for preference in preferences {
+            let message_type = MessageType::from(preference);
+            assert!(message_type.is_object_type());
+            assert_eq!(
+                ObjectForwardingPreference::try_from(message_type),
+                Ok(preference)
+            );
+        }
+
+        let mut object_types = 0;
+        for value in 0..=0x51u64 {
+            let Ok(message_type) = MessageType::try_from(value) else {
+                continue;
+            };
+            match ObjectForwardingPreference::try_from(message_type) {
+                Ok(preference) => {
+                    assert_eq!(preference.get_message_type(), message_type);
+                    object_types += 1;
+                }
+                Err(err) => {
+                    assert!(message_type.is_control_type());
+                    assert_eq!(err, Error::ErrInvalidMessageType(value));
+                }
+            }
+        }
+        assert_eq!(object_types, preferences.len());
+    }
+
+    #[test]
+    fn test_object_status_mapping() {
+        let tests = [
+            (0x0, ObjectStatus::Normal),
+            (0x1, ObjectStatus::ObjectDoesNotExist),
+            (0x2, ObjectStatus::GroupDoesNotExist),
+            (0x3, ObjectStatus::EndOfGroup),
+            (0x4, ObjectStatus::EndOfTrack),
+        ];
+        for (value, status) in tests {
+            assert_eq!(ObjectStatus::from(value), status);
+            assert_eq!(u64::from(status), value);
+        }
+        assert_eq!(ObjectStatus::from(0x5), ObjectStatus::Invalid);
+        assert_eq!(ObjectStatus::from(u64::MAX), ObjectStatus::Invalid);
+    }
+
+    #[test]
+    fn test_object_header_builder() -> Result<()> {
+        let header = ObjectHeader::builder()
+            .subscribe_id(1)
+            .track_alias(2)
+            .group_id(3)
+            .object_id(4)
+            .object_send_order(5)
+            .object_forwarding_preference(ObjectForwardingPreference::Group)
+            .object_payload_length(3)
+            .build()?;
+        assert_eq!(
+            header,
+            ObjectHeader {
+                subscribe_id: 1,
+                track_alias: 2,
+                group_id: 3,
+                object_id: 4,
+                object_send_order: 5,
+                publisher_priority: Priority(0),
+                object_status: ObjectStatus::Normal,
+                object_forwarding_preference: ObjectForwardingPreference::Group,
+                object_payload_length: Some(3),
+            }
+        );
+
+        assert_eq!(
+            ObjectHeader::builder()
+                .object_status(ObjectStatus::EndOfGroup)
+                .object_payload_length(3)
+                .build(),
+            Err(Error::ErrNonEmptyPayloadMustBeWithNormalObjectStatus)
+        );
+        // Status-only objects on Track and Group streams have a zero length.
+        assert!(ObjectHeader::builder()
+            .object_status(ObjectStatus::EndOfGroup)
+            .object_forwarding_preference(ObjectForwardingPreference::Group)
+            .object_payload_length(0)
+            .build()
+            .is_ok());
+        assert_eq!(
+            ObjectHeader::builder()
+                .object_forwarding_preference(ObjectForwardingPreference::Track)
+                .build(),
+            Err(Error::ErrTrackGroupForwardPreferenceRequiresLength)
+        );
+
+        Ok(())
+    }
+}
@@ -1,64 +1,56 @@
+use crate::message::client_setup::role_parameter;
 use crate::message::message_parser::ErrorCode;
 use crate::message::{Role, Version};
 use crate::serde::parameters::ParameterKey;
 use crate::{Deserializer, Error, Parameters, Result, Serializer};
+use alloc::string::ToString;
 use bytes::{Buf, BufMut};
 
 #[derive(Default, Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct ServerSetup {
     pub supported_version: Version,
     pub role: Option<Role>,
 }
 
+impl ServerSetup {
+    /// Answers a CLIENT_SETUP that offered `offered` with the highest version
+    /// both endpoints support, or None if they have none in common.
+    pub fn negotiate(offered: &[Version], supported: &[Version], role: Role) -> Option<Self> {
+        supported
+            .iter()
+            .filter(|version| offered.contains(version))
+            .max_by_key(|version| u64::from(**version))
+            .map(|&supported_version| Self {
+                supported_version,
+                role: Some(role),
+            })
+    }
+
+    /// Checks that the selected version is one of the versions `offered` in
+    /// CLIENT_SETUP.
+    pub fn validate(&self, offered: &[Version]) -> Result<()> {
+        if !offered.contains(&self.supported_version) {
+            return Err(Error::ErrUnsupportedVersion(self.supported_version.into()));
+        }
+        Ok(())
+    }
+}
+
 impl Deserializer for ServerSetup {
     fn deserialize<R: Buf>(r: &mut R) -> Result<(Self, usize)> {
         let (supported_version, mut tl) = Version::deserialize(r)?;
 
-        let (num_params, npl) = u64::deserialize(r)?;
-        tl += npl;
-
-        let mut role: Option<Role> = None;
-
-        // Parse parameters
-        for _ in 0..num_params {
-            let (key, kl) = u64::deserialize(r)?;
-            tl += kl;
-            let (size, sl) = usize::deserialize(r)?;
-            tl += sl;
-
-            if r.remaining() < size {
-                return Err(Error::ErrBufferTooShort);
-            }
-
-            if key == ParameterKey::Role as u64 {
-                if role.is_some() {
-                    return Err(Error::ErrParseError(
-                        ErrorCode::ProtocolViolation,
-                        "ROLE parameter appears twice in SETUP".to_string(),
-                    ));
-                }
-                let (r, rl) = u64::deserialize(r)?;
-                tl += rl;
-
-                if rl != size {
-                    return Err(Error::ErrParseError(
-                        ErrorCode::ProtocolViolation,
-                        "Parameter length does not match varint encoding".to_string(),
-                    ));
-                }
-
-                role = Some(r.try_into().map_err(|_| {
-                    Error::ErrParseError(
-                        ErrorCode::ProtocolViolation,
-                        "Invalid ROLE parameter".to_string(),
-                    )
-                })?);
-            } else if key == ParameterKey::Path as u64 {
-                return Err(Error::ErrParseError(
-                    ErrorCode::ProtocolViolation,
-                    "PATH parameter in SERVER_SETUP".to_string(),
-                ));
-            }
+        let (parameters, pl) = Parameters::deserialize(r)
+            .map_err(|err| Parameters::duplicate_parameter_error(err, "SETUP"))?;
+        tl += pl;
+
+        let role = role_parameter(&parameters)?;
+        if parameters.contains(ParameterKey::Path) {
+            return Err(Error::ErrParseError(
+                ErrorCode::ProtocolViolation,
+                "PATH parameter in SERVER_SETUP".to_string(),
+            ));
         }
 
         if role.is_none() {
@@ -122,4 +114,53 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn test_server_setup_round_trip() -> Result<()> {
+        for role in [Role::Publisher, Role::Subscriber, Role::PubSub] {
+            let server_setup = ServerSetup {
+                supported_version: Version::Draft04,
+                role: Some(role),
+            };
+            let packet = server_setup.to_bytes()?;
+            // A single version varint, not a list.
+            assert_eq!(packet[..8], [192, 0, 0, 0, 255, 0, 0, 4]);
+            assert_eq!(ServerSetup::decode_from(&mut &packet[..])?, server_setup);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_server_setup_negotiate() -> Result<()> {
+        let offered = [Version::Draft03, Version::Draft04, Version::Draft05];
+        let server_setup = ServerSetup::negotiate(
+            &offered,
+            &[Version::Draft02, Version::Draft04, Version::Draft03],
+            Role::PubSub,
+        );
+        assert_eq!(
+            server_setup,
+            Some(ServerSetup {
+                supported_version: Version::Draft04,
+                role: Some(Role::PubSub),
+            })
+        );
+        server_setup.unwrap().validate(&offered)?;
+
+        assert_eq!(
+            ServerSetup::negotiate(&offered, &[Version::Draft02], Role::PubSub),
+            None
+        );
+        let server_setup = ServerSetup {
+            supported_version: Version::Draft02,
+            role: Some(Role::PubSub),
+        };
+        assert_eq!(
+            server_setup.validate(&offered),
+            Err(Error::ErrUnsupportedVersion(0xff000002))
+        );
+
+        Ok(())
+    }
 }
@@ -1,59 +1,48 @@
-use crate::message::message_parser::ErrorCode;
-use crate::message::FilterType;
+use crate::message::{require_non_empty, FilterType, FullTrackName, Namespace, Version};
+use crate::serde::checked_len;
 use crate::serde::parameters::ParameterKey;
-use crate::{Deserializer, Error, Parameters, Result, Serializer};
+use crate::{Deserializer, LazyString, Parameters, Result, Serializer};
+use alloc::string::String;
 use bytes::{Buf, BufMut};
 
+/// The track_name must be non-empty; an empty track_namespace is allowed.
 #[derive(Default, Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct Subscribe {
     pub subscribe_id: u64,
 
     pub track_alias: u64,
-    pub track_namespace: String,
+    pub track_namespace: Namespace,
     pub track_name: String,
 
     pub filter_type: FilterType,
 
-    pub authorization_info: Option<String>,
+    /// Kept as received; its UTF-8 is only checked when it is read.
+    pub authorization_info: Option<LazyString>,
+    /// Parameters other than AUTHORIZATION_INFO, preserved as received so
+    /// that a relay can rewrite them before forwarding.
+    pub parameters: Parameters,
 }
 
-impl Deserializer for Subscribe {
-    fn deserialize<R: Buf>(r: &mut R) -> Result<(Self, usize)> {
+impl Subscribe {
+    /// Reads a SUBSCRIBE in the layout used by `version`.
+    pub fn deserialize_with_version<R: Buf>(version: Version, r: &mut R) -> Result<(Self, usize)> {
         let (subscribe_id, sil) = u64::deserialize(r)?;
 
         let (track_alias, tal) = u64::deserialize(r)?;
-        let (track_namespace, tnsl) = String::deserialize(r)?;
-        let (track_name, tnl) = String::deserialize(r)?;
+        let (
+            FullTrackName {
+                track_namespace,
+                track_name,
+            },
+            ftnl,
+        ) = FullTrackName::deserialize_with_version(version, r)?;
+        require_non_empty(&track_name, "SUBSCRIBE", "track_name")?;
 
         let (filter_type, ftl) = FilterType::deserialize(r)?;
 
-        let mut authorization_info: Option<String> = None;
-        let (num_params, mut pl) = u64::deserialize(r)?;
-        // Parse parameters
-        for _ in 0..num_params {
-            let (key, kl) = u64::deserialize(r)?;
-            pl += kl;
-            let (size, sl) = usize::deserialize(r)?;
-            pl += sl;
-
-            if r.remaining() < size {
-                return Err(Error::ErrBufferTooShort);
-            }
-
-            if key == ParameterKey::AuthorizationInfo as u64 {
-                if authorization_info.is_some() {
-                    return Err(Error::ErrParseError(
-                        ErrorCode::ProtocolViolation,
-                        "AUTHORIZATION_INFO parameter appears twice in SUBSCRIBE".to_string(),
-                    ));
-                }
-                let mut buf = vec![0; size];
-                r.copy_to_slice(&mut buf);
-                pl += size;
-
-                authorization_info = Some(String::from_utf8(buf)?);
-            }
-        }
+        let (authorization_info, parameters, pl) =
+            Parameters::deserialize_with_authorization_info(r, "SUBSCRIBE")?;
 
         Ok((
             Self {
@@ -66,39 +55,67 @@ impl Deserializer for Subscribe {
                 filter_type,
 
                 authorization_info,
+                parameters,
             },
-            sil + tal + tnsl + tnl + ftl + pl,
+            checked_len(&[sil, tal, ftnl, ftl, pl])?,
         ))
     }
-}
 
-impl Serializer for Subscribe {
-    fn serialize<W: BufMut>(&self, w: &mut W) -> Result<usize> {
+    /// The parameter block as written: the preserved parameters plus
+    /// AUTHORIZATION_INFO. It is written even when it is empty, so its count
+    /// is always present.
+    fn wire_parameters(&self) -> Result<Parameters> {
+        let mut parameters = self.parameters.clone();
+        if let Some(authorization_info) = self.authorization_info.as_ref() {
+            parameters.insert(ParameterKey::AuthorizationInfo, authorization_info.clone())?;
+        }
+        Ok(parameters)
+    }
+
+    /// Writes a SUBSCRIBE in the layout used by `version`.
+    pub fn serialize_with_version<W: BufMut>(&self, version: Version, w: &mut W) -> Result<usize> {
         let mut l = self.subscribe_id.serialize(w)?;
 
         l += self.track_alias.serialize(w)?;
-        l += self.track_namespace.serialize(w)?;
+        l += self.track_namespace.serialize_with_version(version, w)?;
         l += self.track_name.serialize(w)?;
 
         l += self.filter_type.serialize(w)?;
 
-        if let Some(authorization_info) = self.authorization_info.as_ref() {
-            let mut parameters = Parameters::new();
-            parameters.insert(
-                ParameterKey::AuthorizationInfo,
-                authorization_info.to_string(),
-            )?;
-            l += parameters.serialize(w)?;
-        }
+        l += self.wire_parameters()?.serialize(w)?;
 
         Ok(l)
     }
 }
 
+/// Uses the Draft-04 layout, with a single-string namespace.
+impl Deserializer for Subscribe {
+    fn deserialize<R: Buf>(r: &mut R) -> Result<(Self, usize)> {
+        Self::deserialize_with_version(Version::Draft04, r)
+    }
+}
+
+impl Serializer for Subscribe {
+    fn serialize<W: BufMut>(&self, w: &mut W) -> Result<usize> {
+        self.serialize_with_version(Version::Draft04, w)
+    }
+
+    fn encoded_size(&self) -> Result<usize> {
+        Ok(self.subscribe_id.encoded_size()?
+            + self.track_alias.encoded_size()?
+            + self.track_namespace.encoded_size()?
+            + self.track_name.encoded_size()?
+            + self.filter_type.encoded_size()?
+            + self.wire_parameters()?.encoded_size()?)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::message::message_parser::ErrorCode;
     use crate::message::{ControlMessage, FullSequence};
+    use crate::Error;
     use std::io::Cursor;
 
     #[test]
@@ -118,15 +135,18 @@ mod test {
         let expected_message = ControlMessage::Subscribe(Subscribe {
             subscribe_id: 1,
             track_alias: 2,
-            track_namespace: "foo".to_string(),
+            track_namespace: "foo".into(),
             track_name: "abcd".to_string(),
             filter_type: FilterType::AbsoluteStart(FullSequence {
                 group_id: 4,
                 object_id: 1,
             }),
-            authorization_info: Some("bar".to_string()),
+            authorization_info: Some("bar".into()),
+            parameters: Parameters::new(),
         });
 
+        assert_eq!(expected_message.encoded_size()?, expected_packet.len());
+
         let mut cursor: Cursor<&[u8]> = Cursor::new(expected_packet.as_ref());
         let (actual_message, actual_len) = ControlMessage::deserialize(&mut cursor)?;
         assert_eq!(expected_message, actual_message);
@@ -138,4 +158,219 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn test_subscribe_no_parameters() -> Result<()> {
+        let expected_packet: Vec<u8> = vec![
+            0x01, 0x02, // id and alias
+            0x03, 0x66, 0x6f, 0x6f, // track_namespace = "foo"
+            0x04, 0x61, 0x62, 0x63, 0x64, // track_name = "abcd"
+            0x01, // Filter type: Latest Group
+            0x00, // no parameters
+        ];
+
+        let subscribe = Subscribe {
+            subscribe_id: 1,
+            track_alias: 2,
+            track_namespace: "foo".into(),
+            track_name: "abcd".to_string(),
+            filter_type: FilterType::LatestGroup,
+            authorization_info: None,
+            parameters: Parameters::new(),
+        };
+        assert_eq!(subscribe.encoded_size()?, expected_packet.len());
+        assert_eq!(&subscribe.to_bytes()?[..], &expected_packet[..]);
+
+        let (decoded, len) = Subscribe::deserialize(&mut &expected_packet[..])?;
+        assert_eq!(decoded, subscribe);
+        assert_eq!(len, expected_packet.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_truncated_subscribe_offset() -> Result<()> {
+        let truncated_packet: Vec<u8> = vec![
+            0x03, 0x01, 0x02, // id and alias
+            0x03, 0x66, 0x6f, 0x6f, // track_namespace = "foo"
+            0x04, 0x61, 0x62, 0x63, 0x64, // track_name = "abcd"
+            0x03, // Filter type: Absolute Start
+                  // Missing start_group and start_object
+        ];
+
+        // Decoding leaves the reader where it stopped; the public entry point
+        // records that offset.
+        let mut cursor: Cursor<&[u8]> = Cursor::new(truncated_packet.as_ref());
+        let err = ControlMessage::deserialize(&mut cursor).unwrap_err();
+        assert_eq!(err, Error::ErrUnexpectedEnd);
+        assert_eq!(cursor.position() as usize, truncated_packet.len());
+
+        let err = crate::try_decode_control(&truncated_packet).unwrap_err();
+        assert_eq!(err.offset(), Some(truncated_packet.len()));
+        assert_eq!(err.root(), &Error::ErrUnexpectedEnd);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_subscribe_empty_names() -> Result<()> {
+        let empty_name: &[u8] = &[
+            0x01, 0x02, // id and alias
+            0x03, 0x66, 0x6f, 0x6f, // track_namespace = "foo"
+            0x00, // track_name = ""
+            0x01, // Filter type: Latest Group
+            0x00, // no parameters
+        ];
+        assert_eq!(
+            Subscribe::deserialize(&mut &empty_name[..]),
+            Err(Error::ErrParseError(
+                ErrorCode::ProtocolViolation,
+                "SUBSCRIBE has empty track_name".to_string()
+            ))
+        );
+
+        let empty_namespace: &[u8] = &[
+            0x01, 0x02, // id and alias
+            0x00, // track_namespace = ""
+            0x04, 0x61, 0x62, 0x63, 0x64, // track_name = "abcd"
+            0x01, // Filter type: Latest Group
+            0x00, // no parameters
+        ];
+        let (subscribe, len) = Subscribe::deserialize(&mut &empty_namespace[..])?;
+        assert_eq!(len, empty_namespace.len());
+        assert!(subscribe.track_namespace.is_empty());
+        assert_eq!(subscribe.track_name, "abcd");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_subscribe_lazy_authorization_info() -> Result<()> {
+        let packet: Vec<u8> = vec![
+            0x01, 0x02, // id and alias
+            0x03, 0x66, 0x6f, 0x6f, // track_namespace = "foo"
+            0x04, 0x61, 0x62, 0x63, 0x64, // track_name = "abcd"
+            0x01, // Filter type: Latest Group
+            0x01, // 1 parameter
+            0x02, 0x02, 0xc3, 0x28, // authorization_info = invalid UTF-8
+        ];
+
+        // The message decodes; the value only fails when it is read.
+        let (subscribe, len) = Subscribe::deserialize(&mut &packet[..])?;
+        assert_eq!(len, packet.len());
+        let authorization_info = subscribe.authorization_info.as_ref().unwrap();
+        assert_eq!(authorization_info.as_bytes(), &[0xc3, 0x28]);
+        assert!(matches!(
+            authorization_info.as_str(),
+            Err(Error::ErrInvalidUtf8(_))
+        ));
+        assert_eq!(&subscribe.to_bytes()?[..], &packet[..]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_subscribe_relay_rewrites_parameters() -> Result<()> {
+        let received: Vec<u8> = vec![
+            0x01, 0x02, // id and alias
+            0x03, 0x66, 0x6f, 0x6f, // track_namespace = "foo"
+            0x04, 0x61, 0x62, 0x63, 0x64, // track_name = "abcd"
+            0x01, // Filter type: Latest Group
+            0x03, // 3 parameters
+            0x02, 0x03, 0x62, 0x61, 0x72, // authorization_info = "bar"
+            0x03, 0x02, 0x40, 0x64, // delivery_timeout = 100
+            0x20, 0x02, 0xbe, 0xef, // unknown parameter
+        ];
+        let (mut subscribe, len) = Subscribe::deserialize(&mut &received[..])?;
+        assert_eq!(len, received.len());
+        assert_eq!(subscribe.authorization_info, Some("bar".into()));
+        assert_eq!(
+            subscribe
+                .parameters
+                .get_varint(ParameterKey::DeliveryTimeout),
+            Some(100)
+        );
+        assert_eq!(&subscribe.to_bytes()?[..], &received[..]);
+
+        // The relay imposes its own delivery timeout and drops the parameter
+        // it does not understand, keeping everything else.
+        let mut relay = Parameters::new();
+        relay.insert_varint(ParameterKey::DeliveryTimeout, 50)?;
+        subscribe.parameters.merge(&relay, true);
+        assert_eq!(
+            subscribe.parameters.remove_raw(0x20),
+            Some(vec![0xbe, 0xef])
+        );
+
+        let forwarded: Vec<u8> = vec![
+            0x01, 0x02, // id and alias
+            0x03, 0x66, 0x6f, 0x6f, // track_namespace = "foo"
+            0x04, 0x61, 0x62, 0x63, 0x64, // track_name = "abcd"
+            0x01, // Filter type: Latest Group
+            0x02, // 2 parameters
+            0x02, 0x03, 0x62, 0x61, 0x72, // authorization_info = "bar"
+            0x03, 0x01, 0x32, // delivery_timeout = 50
+        ];
+        assert_eq!(subscribe.encoded_size()?, forwarded.len());
+        assert_eq!(&subscribe.to_bytes()?[..], &forwarded[..]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_subscribe_namespace_tuple() -> Result<()> {
+        let expected_packet: Vec<u8> = vec![
+            0x03, 0x01, 0x02, // id and alias
+            0x01, 0x03, 0x66, 0x6f, 0x6f, // track_namespace = ("foo")
+            0x04, 0x61, 0x62, 0x63, 0x64, // track_name = "abcd"
+            0x01, // Filter type: Latest Group
+            0x00, // no parameters
+        ];
+
+        let expected_message = ControlMessage::Subscribe(Subscribe {
+            subscribe_id: 1,
+            track_alias: 2,
+            track_namespace: "foo".into(),
+            track_name: "abcd".to_string(),
+            filter_type: FilterType::LatestGroup,
+            authorization_info: None,
+            parameters: Parameters::new(),
+        });
+
+        // A legacy namespace is written as a one-element tuple and reads
+        // back as the same namespace.
+        let mut actual_packet = vec![];
+        let _ = expected_message.serialize_with_version(Version::Draft06, &mut actual_packet)?;
+        assert_eq!(expected_packet, actual_packet);
+
+        let mut cursor: Cursor<&[u8]> = Cursor::new(expected_packet.as_ref());
+        let (actual_message, actual_len) =
+            ControlMessage::deserialize_with_version(Version::Draft06, &mut cursor)?;
+        assert_eq!(expected_message, actual_message);
+        assert_eq!(expected_packet.len(), actual_len);
+        let ControlMessage::Subscribe(subscribe) = actual_message else {
+            unreachable!()
+        };
+        assert!(matches!(subscribe.track_namespace, Namespace::Tuple(_)));
+
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_subscribe_json_round_trip() -> Result<()> {
+        let subscribe = Subscribe {
+            subscribe_id: 1,
+            track_alias: 2,
+            track_namespace: "foo".into(),
+            track_name: "abcd".to_string(),
+            filter_type: FilterType::AbsoluteRange(FullSequence::new(4, 1), 7, Some(3)),
+            authorization_info: Some("bar".into()),
+            parameters: Parameters::new(),
+        };
+        let json = serde_json::to_string(&subscribe).unwrap();
+        let decoded: Subscribe = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, subscribe);
+        Ok(())
+    }
 }
@@ -1,9 +1,12 @@
 use crate::message::message_parser::ErrorCode;
 use crate::message::FullSequence;
 use crate::{Deserializer, Error, Result, Serializer};
+use alloc::format;
+use alloc::string::String;
 use bytes::{Buf, BufMut};
 
 #[derive(Default, Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub enum SubscribeDoneCode {
     #[default]
     Unsubscribed = 0x0,
@@ -15,16 +18,48 @@ pub enum SubscribeDoneCode {
     Expired = 0x6,
 }
 
+impl TryFrom<u64> for SubscribeDoneCode {
+    type Error = Error;
+
+    fn try_from(value: u64) -> core::result::Result<Self, Self::Error> {
+        match value {
+            0x0 => Ok(SubscribeDoneCode::Unsubscribed),
+            0x1 => Ok(SubscribeDoneCode::InternalError),
+            0x2 => Ok(SubscribeDoneCode::Unauthorized),
+            0x3 => Ok(SubscribeDoneCode::TrackEnded),
+            0x4 => Ok(SubscribeDoneCode::SubscriptionEnded),
+            0x5 => Ok(SubscribeDoneCode::GoingAway),
+            0x6 => Ok(SubscribeDoneCode::Expired),
+            _ => Err(Error::ErrInvalidErrorCode(value)),
+        }
+    }
+}
+
 #[derive(Default, Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct SubscribeDone {
     pub subscribe_id: u64,
 
     pub status_code: u64,
     pub reason_phrase: String,
 
+    /// The last group and object sent. None encodes ContentExists = 0.
     pub final_group_object: Option<FullSequence>,
 }
 
+impl SubscribeDone {
+    /// Returns the typed status code, or None if the peer sent a code this
+    /// implementation does not know. The raw value is kept in |status_code|.
+    pub fn code(&self) -> Option<SubscribeDoneCode> {
+        SubscribeDoneCode::try_from(self.status_code).ok()
+    }
+
+    /// Returns true if any objects were delivered for the subscription.
+    pub fn content_exists(&self) -> bool {
+        self.final_group_object.is_some()
+    }
+}
+
 impl Deserializer for SubscribeDone {
     fn deserialize<R: Buf>(r: &mut R) -> Result<(Self, usize)> {
         let (subscribe_id, sil) = u64::deserialize(r)?;
@@ -117,4 +152,37 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn test_subscribe_done_without_content() -> Result<()> {
+        let expected_packet: Vec<u8> = vec![
+            0x0b, 0x02, 0x03, // subscribe_id = 2, error_code = 3,
+            0x02, 0x68, 0x69, // reason_phrase = "hi"
+            0x00, // no content
+        ];
+
+        let expected_message = ControlMessage::SubscribeDone(SubscribeDone {
+            subscribe_id: 2,
+            status_code: SubscribeDoneCode::TrackEnded as u64,
+            reason_phrase: "hi".to_string(),
+            final_group_object: None,
+        });
+
+        let mut cursor: Cursor<&[u8]> = Cursor::new(expected_packet.as_ref());
+        let (actual_message, actual_len) = ControlMessage::deserialize(&mut cursor)?;
+        assert_eq!(expected_message, actual_message);
+        assert_eq!(expected_packet.len(), actual_len);
+        if let ControlMessage::SubscribeDone(subscribe_done) = &actual_message {
+            assert!(!subscribe_done.content_exists());
+            assert_eq!(subscribe_done.code(), Some(SubscribeDoneCode::TrackEnded));
+        } else {
+            unreachable!();
+        }
+
+        let mut actual_packet = vec![];
+        let _ = expected_message.serialize(&mut actual_packet)?;
+        assert_eq!(expected_packet, actual_packet);
+
+        Ok(())
+    }
 }
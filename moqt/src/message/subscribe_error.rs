@@ -1,15 +1,44 @@
-use crate::{Deserializer, Result, Serializer};
+use crate::{Deserializer, Error, Result, Serializer};
+use alloc::string::String;
 use bytes::{Buf, BufMut};
+use core::fmt::{Display, Formatter};
 
 #[derive(Default, Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub enum SubscribeErrorCode {
     #[default]
     InternalError = 0,
     InvalidRange = 1,
     RetryTrackAlias = 2,
+    TrackDoesNotExist = 3,
+    Unauthorized = 4,
+    Timeout = 5,
+}
+
+impl TryFrom<u64> for SubscribeErrorCode {
+    type Error = Error;
+
+    fn try_from(value: u64) -> core::result::Result<Self, Self::Error> {
+        match value {
+            0x0 => Ok(SubscribeErrorCode::InternalError),
+            0x1 => Ok(SubscribeErrorCode::InvalidRange),
+            0x2 => Ok(SubscribeErrorCode::RetryTrackAlias),
+            0x3 => Ok(SubscribeErrorCode::TrackDoesNotExist),
+            0x4 => Ok(SubscribeErrorCode::Unauthorized),
+            0x5 => Ok(SubscribeErrorCode::Timeout),
+            _ => Err(Error::ErrInvalidErrorCode(value)),
+        }
+    }
+}
+
+impl Display for SubscribeErrorCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:?}", *self)
+    }
 }
 
 #[derive(Default, Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct SubscribeError {
     pub subscribe_id: u64,
 
@@ -19,6 +48,21 @@ pub struct SubscribeError {
     pub track_alias: u64,
 }
 
+impl SubscribeError {
+    /// Returns the typed error code, or None if the peer sent a code this
+    /// implementation does not know. The raw value is kept in |error_code|.
+    pub fn code(&self) -> Option<SubscribeErrorCode> {
+        SubscribeErrorCode::try_from(self.error_code).ok()
+    }
+
+    /// Returns the track alias to retry the subscription with, which is only
+    /// meaningful for the RetryTrackAlias code. Draft-04 carries the alias in
+    /// every SUBSCRIBE_ERROR, so it is always encoded.
+    pub fn retry_track_alias(&self) -> Option<u64> {
+        (self.code() == Some(SubscribeErrorCode::RetryTrackAlias)).then_some(self.track_alias)
+    }
+}
+
 impl Deserializer for SubscribeError {
     fn deserialize<R: Buf>(r: &mut R) -> Result<(Self, usize)> {
         let (subscribe_id, sil) = u64::deserialize(r)?;
@@ -88,4 +132,66 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn test_subscribe_error_code() -> Result<()> {
+        let known_packet: Vec<u8> = vec![
+            0x02, // subscribe_id = 2
+            0x03, // error_code = TrackDoesNotExist
+            0x03, 0x62, 0x61, 0x72, // reason_phrase = "bar"
+            0x04, // track_alias = 4,
+        ];
+        let (known, _) = SubscribeError::deserialize(&mut known_packet.as_slice())?;
+        assert_eq!(known.code(), Some(SubscribeErrorCode::TrackDoesNotExist));
+        assert_eq!(known.code().unwrap().to_string(), "TrackDoesNotExist");
+
+        let unknown_packet: Vec<u8> = vec![
+            0x02, // subscribe_id = 2
+            0x40, 0x99, // error_code = 0x99
+            0x03, 0x62, 0x61, 0x72, // reason_phrase = "bar"
+            0x04, // track_alias = 4,
+        ];
+        let (unknown, _) = SubscribeError::deserialize(&mut unknown_packet.as_slice())?;
+        assert_eq!(unknown.code(), None);
+        assert_eq!(unknown.error_code, 0x99);
+        assert_eq!(
+            SubscribeErrorCode::try_from(0x99),
+            Err(Error::ErrInvalidErrorCode(0x99))
+        );
+
+        let mut actual_packet = vec![];
+        let _ = unknown.serialize(&mut actual_packet)?;
+        assert_eq!(unknown_packet, actual_packet);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_subscribe_error_retry_track_alias() -> Result<()> {
+        let retry_packet: Vec<u8> = vec![
+            0x02, // subscribe_id = 2
+            0x02, // error_code = RetryTrackAlias
+            0x03, 0x62, 0x61, 0x72, // reason_phrase = "bar"
+            0x07, // track_alias = 7,
+        ];
+        let (retry, _) = SubscribeError::deserialize(&mut retry_packet.as_slice())?;
+        assert_eq!(retry.retry_track_alias(), Some(7));
+        let mut actual_packet = vec![];
+        let _ = retry.serialize(&mut actual_packet)?;
+        assert_eq!(retry_packet, actual_packet);
+
+        let other = SubscribeError {
+            error_code: SubscribeErrorCode::TrackDoesNotExist as u64,
+            ..retry.clone()
+        };
+        assert_eq!(other.retry_track_alias(), None);
+
+        // The alias is required on the wire.
+        assert_eq!(
+            SubscribeError::deserialize(&mut &retry_packet[..6]),
+            Err(Error::ErrUnexpectedEnd)
+        );
+
+        Ok(())
+    }
 }
@@ -1,23 +1,54 @@
 use crate::message::message_parser::ErrorCode;
-use crate::message::FullSequence;
+use crate::message::{FullSequence, GroupOrder, Version};
 use crate::{Deserializer, Error, Result, Serializer};
+use alloc::format;
+use alloc::string::ToString;
 use bytes::{Buf, BufMut};
 
 #[derive(Default, Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct SubscribeOk {
     pub subscribe_id: u64,
 
+    /// Time in milliseconds after which the subscription is no longer valid,
+    /// or 0 if it does not expire.
     pub expires: u64,
 
+    /// Present iff the version carries it (see
+    /// [`Version::uses_group_order`]).
+    pub group_order: Option<GroupOrder>,
+
+    /// The largest group and object, present iff ContentExists is set.
     pub largest_group_object: Option<FullSequence>,
 }
 
-impl Deserializer for SubscribeOk {
-    fn deserialize<R: Buf>(r: &mut R) -> Result<(Self, usize)> {
+impl SubscribeOk {
+    pub fn content_exists(&self) -> bool {
+        self.largest_group_object.is_some()
+    }
+
+    /// Reads a SUBSCRIBE_OK in the layout used by `version`.
+    pub fn deserialize_with_version<R: Buf>(version: Version, r: &mut R) -> Result<(Self, usize)> {
         let (subscribe_id, sil) = u64::deserialize(r)?;
 
         let (expires, el) = u64::deserialize(r)?;
 
+        let (group_order, gol) = if version.uses_group_order() {
+            let (group_order, gol) = GroupOrder::deserialize(r).map_err(|err| {
+                if let Error::ErrInvalidGroupOrder(o) = err {
+                    Error::ErrParseError(
+                        ErrorCode::ProtocolViolation,
+                        format!("SUBSCRIBE_OK GroupOrder has invalid value {}", o),
+                    )
+                } else {
+                    err
+                }
+            })?;
+            (Some(group_order), gol)
+        } else {
+            (None, 0)
+        };
+
         let (exist, l) = bool::deserialize(r).map_err(|err| {
             if let Error::ErrInvalidBooleanValue(b) = err {
                 Error::ErrParseError(
@@ -28,7 +59,7 @@ impl Deserializer for SubscribeOk {
                 err
             }
         })?;
-        let mut tl = sil + el + l;
+        let mut tl = sil + el + gol + l;
         let largest_group_object = if exist {
             let (largest_group_object, lgol) = FullSequence::deserialize(r)?;
             tl += lgol;
@@ -43,19 +74,36 @@ impl Deserializer for SubscribeOk {
 
                 expires,
 
+                group_order,
+
                 largest_group_object,
             },
             tl,
         ))
     }
-}
 
-impl Serializer for SubscribeOk {
-    fn serialize<W: BufMut>(&self, w: &mut W) -> Result<usize> {
+    /// Writes a SUBSCRIBE_OK in the layout used by `version`, which must agree
+    /// with whether `group_order` is set.
+    pub fn serialize_with_version<W: BufMut>(&self, version: Version, w: &mut W) -> Result<usize> {
         let mut l = self.subscribe_id.serialize(w)?;
 
         l += self.expires.serialize(w)?;
 
+        l += match (version.uses_group_order(), self.group_order) {
+            (true, Some(group_order)) => group_order.serialize(w)?,
+            (false, None) => 0,
+            (true, None) => {
+                return Err(Error::ErrFrameError(
+                    "SUBSCRIBE_OK requires group_order".to_string(),
+                ))
+            }
+            (false, Some(_)) => {
+                return Err(Error::ErrFrameError(
+                    "SUBSCRIBE_OK cannot carry group_order".to_string(),
+                ))
+            }
+        };
+
         l += if let Some(largest_group_object) = self.largest_group_object.as_ref() {
             true.serialize(w)? + largest_group_object.serialize(w)?
         } else {
@@ -66,6 +114,19 @@ impl Serializer for SubscribeOk {
     }
 }
 
+/// Uses the Draft-04 layout, which has no group order.
+impl Deserializer for SubscribeOk {
+    fn deserialize<R: Buf>(r: &mut R) -> Result<(Self, usize)> {
+        Self::deserialize_with_version(Version::Draft04, r)
+    }
+}
+
+impl Serializer for SubscribeOk {
+    fn serialize<W: BufMut>(&self, w: &mut W) -> Result<usize> {
+        self.serialize_with_version(Version::Draft04, w)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -82,6 +143,7 @@ mod test {
         let expected_message = ControlMessage::SubscribeOk(SubscribeOk {
             subscribe_id: 1,
             expires: 3,
+            group_order: None,
             largest_group_object: Some(FullSequence {
                 group_id: 12,
                 object_id: 20,
@@ -99,4 +161,97 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn test_subscribe_ok_without_content() -> Result<()> {
+        let expected_packet: Vec<u8> = vec![
+            0x04, 0x01, 0x03, // subscribe_id = 1, expires = 3
+            0x00, // content_exists = false
+        ];
+
+        let expected_message = ControlMessage::SubscribeOk(SubscribeOk {
+            subscribe_id: 1,
+            expires: 3,
+            group_order: None,
+            largest_group_object: None,
+        });
+        if let ControlMessage::SubscribeOk(subscribe_ok) = &expected_message {
+            assert!(!subscribe_ok.content_exists());
+        }
+
+        let mut cursor: Cursor<&[u8]> = Cursor::new(expected_packet.as_ref());
+        let (actual_message, actual_len) = ControlMessage::deserialize(&mut cursor)?;
+        assert_eq!(expected_message, actual_message);
+        assert_eq!(expected_packet.len(), actual_len);
+
+        let mut actual_packet = vec![];
+        let _ = expected_message.serialize(&mut actual_packet)?;
+        assert_eq!(expected_packet, actual_packet);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_subscribe_ok_group_order() -> Result<()> {
+        let expected_packet: Vec<u8> = vec![
+            0x04, 0x01, 0x03, // subscribe_id = 1, expires = 3
+            0x02, // group_order = descending
+            0x01, 0x0c, 0x14, // largest_group_id = 12, largest_object_id = 20,
+        ];
+
+        let expected_message = ControlMessage::SubscribeOk(SubscribeOk {
+            subscribe_id: 1,
+            expires: 3,
+            group_order: Some(GroupOrder::Descending),
+            largest_group_object: Some(FullSequence {
+                group_id: 12,
+                object_id: 20,
+            }),
+        });
+
+        let mut cursor: Cursor<&[u8]> = Cursor::new(expected_packet.as_ref());
+        let (actual_message, actual_len) =
+            ControlMessage::deserialize_with_version(Version::Draft05, &mut cursor)?;
+        assert_eq!(expected_message, actual_message);
+        assert_eq!(expected_packet.len(), actual_len);
+
+        let mut actual_packet = vec![];
+        let _ = expected_message.serialize_with_version(Version::Draft05, &mut actual_packet)?;
+        assert_eq!(expected_packet, actual_packet);
+
+        // The group order must be present exactly when the version has it.
+        assert!(expected_message.serialize(&mut vec![]).is_err());
+        let ControlMessage::SubscribeOk(subscribe_ok) = expected_message else {
+            unreachable!()
+        };
+        let without_group_order = SubscribeOk {
+            group_order: None,
+            ..subscribe_ok
+        };
+        assert!(without_group_order
+            .serialize_with_version(Version::Draft05, &mut vec![])
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_subscribe_ok_invalid_group_order() -> Result<()> {
+        let packet: Vec<u8> = vec![
+            0x01, 0x03, // subscribe_id = 1, expires = 3
+            0x03, // group_order = invalid
+            0x00, // content_exists = false
+        ];
+
+        let mut cursor: Cursor<&[u8]> = Cursor::new(packet.as_ref());
+        assert_eq!(
+            SubscribeOk::deserialize_with_version(Version::Draft05, &mut cursor).unwrap_err(),
+            Error::ErrParseError(
+                ErrorCode::ProtocolViolation,
+                "SUBSCRIBE_OK GroupOrder has invalid value 3".to_string()
+            )
+        );
+
+        Ok(())
+    }
 }
@@ -1,22 +1,82 @@
 use crate::message::message_parser::ErrorCode;
-use crate::message::FullSequence;
+use crate::message::subscribe::Subscribe;
+use crate::message::{FilterType, FullSequence, Priority, Version};
 use crate::serde::parameters::ParameterKey;
-use crate::{Deserializer, Parameters, Serializer};
+use crate::{Deserializer, LazyString, Parameters, Serializer};
 use crate::{Error, Result};
+use alloc::string::ToString;
 use bytes::{Buf, BufMut};
 
 #[derive(Default, Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct SubscribeUpdate {
     pub subscribe_id: u64,
 
+    /// The first object delivered. On the wire both ids are absolute, as in
+    /// an AbsoluteStart filter.
     pub start_group_object: FullSequence,
+    /// The last object delivered, inclusive, or None for no end. An
+    /// object_id of u64::MAX covers the whole end group. On the wire each id
+    /// is sent plus one, so that an end group of zero means no end and an end
+    /// object of zero means the whole group.
     pub end_group_object: Option<FullSequence>,
 
-    pub authorization_info: Option<String>,
+    /// Present iff the version carries it (see
+    /// [`Version::uses_subscriber_priority`]).
+    pub subscriber_priority: Option<Priority>,
+
+    /// Kept as received; its UTF-8 is only checked when it is read.
+    pub authorization_info: Option<LazyString>,
+    /// Parameters other than AUTHORIZATION_INFO, preserved as received.
+    pub parameters: Parameters,
 }
 
-impl Deserializer for SubscribeUpdate {
-    fn deserialize<R: Buf>(r: &mut R) -> Result<(Self, usize)> {
+impl SubscribeUpdate {
+    /// Checks that this update only narrows |original|, the SUBSCRIBE it
+    /// modifies. Starts relative to the live edge (LatestGroup/LatestObject)
+    /// are resolved by the publisher, so only absolute starts are compared.
+    pub fn validate_against(&self, original: &Subscribe) -> Result<()> {
+        if self.subscribe_id != original.subscribe_id {
+            return Err(Error::ErrParseError(
+                ErrorCode::ProtocolViolation,
+                "SUBSCRIBE_UPDATE subscribe_id does not match SUBSCRIBE".to_string(),
+            ));
+        }
+
+        let (original_start, original_end) = match original.filter_type {
+            FilterType::LatestGroup | FilterType::LatestObject => (None, None),
+            FilterType::AbsoluteStart(start) => (Some(start), None),
+            FilterType::AbsoluteRange(start, end_group, end_object) => (
+                Some(start),
+                Some(FullSequence {
+                    group_id: end_group,
+                    object_id: end_object.unwrap_or(u64::MAX),
+                }),
+            ),
+        };
+
+        if original_start.is_some_and(|start| self.start_group_object < start) {
+            return Err(Error::ErrParseError(
+                ErrorCode::ProtocolViolation,
+                "SUBSCRIBE_UPDATE starts before SUBSCRIBE".to_string(),
+            ));
+        }
+        if let Some(original_end) = original_end {
+            if self.end_group_object.is_none_or(|end| end > original_end) {
+                return Err(Error::ErrParseError(
+                    ErrorCode::ProtocolViolation,
+                    "SUBSCRIBE_UPDATE ends after SUBSCRIBE".to_string(),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl SubscribeUpdate {
+    /// Reads a SUBSCRIBE_UPDATE in the layout used by `version`.
+    pub fn deserialize_with_version<R: Buf>(version: Version, r: &mut R) -> Result<(Self, usize)> {
         let (subscribe_id, sil) = u64::deserialize(r)?;
 
         let (start, sgol) = FullSequence::deserialize(r)?;
@@ -58,34 +118,15 @@ impl Deserializer for SubscribeUpdate {
             Some(end)
         };
 
-        let mut authorization_info: Option<String> = None;
-        let (num_params, mut pl) = u64::deserialize(r)?;
-        // Parse parameters
-        for _ in 0..num_params {
-            let (key, kl) = u64::deserialize(r)?;
-            pl += kl;
-            let (size, sl) = usize::deserialize(r)?;
-            pl += sl;
-
-            if r.remaining() < size {
-                return Err(Error::ErrBufferTooShort);
-            }
-
-            if key == ParameterKey::AuthorizationInfo as u64 {
-                if authorization_info.is_some() {
-                    return Err(Error::ErrParseError(
-                        ErrorCode::ProtocolViolation,
-                        "AUTHORIZATION_INFO parameter appears twice in SUBSCRIBE_UPDATE"
-                            .to_string(),
-                    ));
-                }
-                let mut buf = vec![0; size];
-                r.copy_to_slice(&mut buf);
-                pl += size;
+        let (subscriber_priority, spl) = if version.uses_subscriber_priority() {
+            let (subscriber_priority, spl) = Priority::deserialize(r)?;
+            (Some(subscriber_priority), spl)
+        } else {
+            (None, 0)
+        };
 
-                authorization_info = Some(String::from_utf8(buf)?);
-            }
-        }
+        let (authorization_info, parameters, pl) =
+            Parameters::deserialize_with_authorization_info(r, "SUBSCRIBE_UPDATE")?;
 
         Ok((
             Self {
@@ -94,15 +135,18 @@ impl Deserializer for SubscribeUpdate {
                 start_group_object: start,
                 end_group_object: end,
 
+                subscriber_priority,
+
                 authorization_info,
+                parameters,
             },
-            sil + sgol + egol + pl,
+            sil + sgol + egol + spl + pl,
         ))
     }
-}
 
-impl Serializer for SubscribeUpdate {
-    fn serialize<W: BufMut>(&self, w: &mut W) -> Result<usize> {
+    /// Writes a SUBSCRIBE_UPDATE in the layout used by `version`, which must
+    /// agree with whether `subscriber_priority` is set.
+    pub fn serialize_with_version<W: BufMut>(&self, version: Version, w: &mut W) -> Result<usize> {
         let mut l = self.subscribe_id.serialize(w)?;
 
         l += self.start_group_object.serialize(w)?;
@@ -134,19 +178,45 @@ impl Serializer for SubscribeUpdate {
             .serialize(w)?;
         }
 
+        l += match (version.uses_subscriber_priority(), self.subscriber_priority) {
+            (true, Some(subscriber_priority)) => subscriber_priority.serialize(w)?,
+            (false, None) => 0,
+            (true, None) => {
+                return Err(Error::ErrFrameError(
+                    "SUBSCRIBE_UPDATE requires subscriber_priority".to_string(),
+                ))
+            }
+            (false, Some(_)) => {
+                return Err(Error::ErrFrameError(
+                    "SUBSCRIBE_UPDATE cannot carry subscriber_priority".to_string(),
+                ))
+            }
+        };
+
+        // The parameter count is required even when there are none.
+        let mut parameters = self.parameters.clone();
         if let Some(authorization_info) = self.authorization_info.as_ref() {
-            let mut parameters = Parameters::new();
-            parameters.insert(
-                ParameterKey::AuthorizationInfo,
-                authorization_info.to_string(),
-            )?;
-            l += parameters.serialize(w)?;
+            parameters.insert(ParameterKey::AuthorizationInfo, authorization_info.clone())?;
         }
+        l += parameters.serialize(w)?;
 
         Ok(l)
     }
 }
 
+/// Uses the Draft-04 layout, which has no subscriber priority.
+impl Deserializer for SubscribeUpdate {
+    fn deserialize<R: Buf>(r: &mut R) -> Result<(Self, usize)> {
+        Self::deserialize_with_version(Version::Draft04, r)
+    }
+}
+
+impl Serializer for SubscribeUpdate {
+    fn serialize<W: BufMut>(&self, w: &mut W) -> Result<usize> {
+        self.serialize_with_version(Version::Draft04, w)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -171,7 +241,9 @@ mod test {
                 group_id: 4,
                 object_id: 5,
             }),
-            authorization_info: Some("bar".to_string()),
+            subscriber_priority: None,
+            authorization_info: Some("bar".into()),
+            parameters: Parameters::new(),
         });
 
         let mut cursor: Cursor<&[u8]> = Cursor::new(expected_packet.as_ref());
@@ -185,4 +257,164 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn test_subscribe_update_parameters() -> Result<()> {
+        // Open-ended, with no parameters at all.
+        let packet = [
+            0x02, 0x03, 0x01, 0x00, 0x00, // start (3, 1), no end
+            0x00, // no parameters
+        ];
+        let (update, len) = SubscribeUpdate::deserialize(&mut &packet[..])?;
+        assert_eq!(len, packet.len());
+        assert_eq!(
+            update,
+            SubscribeUpdate {
+                subscribe_id: 2,
+                start_group_object: FullSequence::new(3, 1),
+                end_group_object: None,
+                subscriber_priority: None,
+                authorization_info: None,
+                parameters: Parameters::new(),
+            }
+        );
+        assert_eq!(&update.to_bytes()?[..], &packet[..]);
+
+        // An end object of zero is the whole group; other parameters are kept.
+        let packet = [
+            0x02, 0x03, 0x01, 0x05, 0x00, // start (3, 1), end all of group 4
+            0x02, // 2 parameters
+            0x02, 0x03, 0x62, 0x61, 0x72, // authorization_info = "bar"
+            0x03, 0x02, 0x40, 0x64, // delivery_timeout = 100
+        ];
+        let (update, len) = SubscribeUpdate::deserialize(&mut &packet[..])?;
+        assert_eq!(len, packet.len());
+        assert_eq!(
+            update.end_group_object,
+            Some(FullSequence::new(4, u64::MAX))
+        );
+        assert_eq!(update.authorization_info, Some("bar".into()));
+        assert!(!update.parameters.contains(ParameterKey::AuthorizationInfo));
+        assert_eq!(
+            update.parameters.get_varint(ParameterKey::DeliveryTimeout),
+            Some(100)
+        );
+        assert_eq!(&update.to_bytes()?[..], &packet[..]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_subscribe_update_subscriber_priority() -> Result<()> {
+        let expected_packet: Vec<u8> = vec![
+            0x02, 0x02, 0x03, 0x01, 0x05, 0x06, // start and end sequences
+            0x80, // subscriber_priority = 128
+            0x00, // no parameters
+        ];
+
+        let expected_message = ControlMessage::SubscribeUpdate(SubscribeUpdate {
+            subscribe_id: 2,
+            start_group_object: FullSequence::new(3, 1),
+            end_group_object: Some(FullSequence::new(4, 5)),
+            subscriber_priority: Some(Priority(128)),
+            authorization_info: None,
+            parameters: Parameters::new(),
+        });
+
+        let mut cursor: Cursor<&[u8]> = Cursor::new(expected_packet.as_ref());
+        let (actual_message, actual_len) =
+            ControlMessage::deserialize_with_version(Version::Draft05, &mut cursor)?;
+        assert_eq!(expected_message, actual_message);
+        assert_eq!(expected_packet.len(), actual_len);
+
+        let mut actual_packet = vec![];
+        let _ = expected_message.serialize_with_version(Version::Draft05, &mut actual_packet)?;
+        assert_eq!(expected_packet, actual_packet);
+
+        // The priority must be present exactly when the version has it.
+        assert!(expected_message.serialize(&mut vec![]).is_err());
+        let ControlMessage::SubscribeUpdate(subscribe_update) = expected_message else {
+            unreachable!()
+        };
+        let without_priority = SubscribeUpdate {
+            subscriber_priority: None,
+            ..subscribe_update
+        };
+        assert!(without_priority
+            .serialize_with_version(Version::Draft05, &mut vec![])
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_subscribe_update_validate_against() -> Result<()> {
+        let original = Subscribe {
+            subscribe_id: 2,
+            track_alias: 1,
+            track_namespace: "foo".into(),
+            track_name: "abcd".to_string(),
+            filter_type: FilterType::AbsoluteRange(FullSequence::new(3, 1), 8, None),
+            authorization_info: None,
+            parameters: Parameters::new(),
+        };
+
+        // Narrowing both ends is legal, as is keeping the original range.
+        let narrowed = SubscribeUpdate {
+            subscribe_id: 2,
+            start_group_object: FullSequence::new(4, 0),
+            end_group_object: Some(FullSequence::new(6, 5)),
+            subscriber_priority: None,
+            authorization_info: None,
+            parameters: Parameters::new(),
+        };
+        narrowed.validate_against(&original)?;
+        let unchanged = SubscribeUpdate {
+            subscribe_id: 2,
+            start_group_object: FullSequence::new(3, 1),
+            end_group_object: Some(FullSequence::new(8, u64::MAX)),
+            subscriber_priority: None,
+            authorization_info: None,
+            parameters: Parameters::new(),
+        };
+        unchanged.validate_against(&original)?;
+
+        let widened = [
+            // Wrong subscription.
+            SubscribeUpdate {
+                subscribe_id: 3,
+                ..narrowed.clone()
+            },
+            // Starts earlier.
+            SubscribeUpdate {
+                start_group_object: FullSequence::new(3, 0),
+                ..narrowed.clone()
+            },
+            // Ends later.
+            SubscribeUpdate {
+                end_group_object: Some(FullSequence::new(9, 0)),
+                ..narrowed.clone()
+            },
+            // Open-ended.
+            SubscribeUpdate {
+                end_group_object: None,
+                ..narrowed.clone()
+            },
+        ];
+        for update in widened {
+            assert!(matches!(
+                update.validate_against(&original),
+                Err(Error::ErrParseError(ErrorCode::ProtocolViolation, _))
+            ));
+        }
+
+        // Any end narrows an open-ended subscription.
+        let open = Subscribe {
+            filter_type: FilterType::LatestObject,
+            ..original
+        };
+        narrowed.validate_against(&open)?;
+
+        Ok(())
+    }
 }
@@ -1,8 +1,10 @@
-use crate::message::FullSequence;
+use crate::message::{FullSequence, FullTrackName, Namespace, Version};
 use crate::{Deserializer, Result, Serializer};
+use alloc::string::String;
 use bytes::{Buf, BufMut};
 
 #[derive(Default, Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub enum TrackStatusCode {
     #[default]
     InProgress = 0x0,
@@ -13,17 +15,24 @@ pub enum TrackStatusCode {
 }
 
 #[derive(Default, Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct TrackStatus {
-    pub track_namespace: String,
+    pub track_namespace: Namespace,
     pub track_name: String,
     pub status_code: u64,
     pub last_group_object: FullSequence,
 }
 
-impl Deserializer for TrackStatus {
-    fn deserialize<R: Buf>(r: &mut R) -> Result<(Self, usize)> {
-        let (track_namespace, tnsl) = String::deserialize(r)?;
-        let (track_name, tnl) = String::deserialize(r)?;
+impl TrackStatus {
+    /// Reads a TRACK_STATUS in the layout used by `version`.
+    pub fn deserialize_with_version<R: Buf>(version: Version, r: &mut R) -> Result<(Self, usize)> {
+        let (
+            FullTrackName {
+                track_namespace,
+                track_name,
+            },
+            ftnl,
+        ) = FullTrackName::deserialize_with_version(version, r)?;
         let (status_code, scl) = u64::deserialize(r)?;
         let (last_group_object, lgol) = FullSequence::deserialize(r)?;
         Ok((
@@ -33,14 +42,13 @@ impl Deserializer for TrackStatus {
                 status_code,
                 last_group_object,
             },
-            tnsl + tnl + scl + lgol,
+            ftnl + scl + lgol,
         ))
     }
-}
 
-impl Serializer for TrackStatus {
-    fn serialize<W: BufMut>(&self, w: &mut W) -> Result<usize> {
-        let mut l = self.track_namespace.serialize(w)?;
+    /// Writes a TRACK_STATUS in the layout used by `version`.
+    pub fn serialize_with_version<W: BufMut>(&self, version: Version, w: &mut W) -> Result<usize> {
+        let mut l = self.track_namespace.serialize_with_version(version, w)?;
         l += self.track_name.serialize(w)?;
         l += self.status_code.serialize(w)?;
         l += self.last_group_object.serialize(w)?;
@@ -48,6 +56,19 @@ impl Serializer for TrackStatus {
     }
 }
 
+/// Uses the Draft-04 layout, with a single-string namespace.
+impl Deserializer for TrackStatus {
+    fn deserialize<R: Buf>(r: &mut R) -> Result<(Self, usize)> {
+        Self::deserialize_with_version(Version::Draft04, r)
+    }
+}
+
+impl Serializer for TrackStatus {
+    fn serialize<W: BufMut>(&self, w: &mut W) -> Result<usize> {
+        self.serialize_with_version(Version::Draft04, w)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -63,7 +84,7 @@ mod test {
         ];
 
         let expected_message = ControlMessage::TrackStatus(TrackStatus {
-            track_namespace: "foo".to_string(),
+            track_namespace: "foo".into(),
             track_name: "abcd".to_string(),
             status_code: TrackStatusCode::InProgress as u64,
             last_group_object: FullSequence {
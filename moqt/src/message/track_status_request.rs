@@ -1,31 +1,52 @@
+use crate::message::{FullTrackName, Namespace, Version};
 use crate::{Deserializer, Result, Serializer};
+use alloc::string::String;
 use bytes::{Buf, BufMut};
 
 #[derive(Default, Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct TrackStatusRequest {
-    pub track_namespace: String,
+    pub track_namespace: Namespace,
     pub track_name: String,
 }
 
-impl Deserializer for TrackStatusRequest {
-    fn deserialize<R: Buf>(r: &mut R) -> Result<(Self, usize)> {
-        let (track_namespace, tnsl) = String::deserialize(r)?;
-        let (track_name, tnl) = String::deserialize(r)?;
+impl TrackStatusRequest {
+    /// Reads a TRACK_STATUS_REQUEST in the layout used by `version`.
+    pub fn deserialize_with_version<R: Buf>(version: Version, r: &mut R) -> Result<(Self, usize)> {
+        let (
+            FullTrackName {
+                track_namespace,
+                track_name,
+            },
+            ftnl,
+        ) = FullTrackName::deserialize_with_version(version, r)?;
         Ok((
             Self {
                 track_namespace,
                 track_name,
             },
-            tnsl + tnl,
+            ftnl,
         ))
     }
+
+    /// Writes a TRACK_STATUS_REQUEST in the layout used by `version`.
+    pub fn serialize_with_version<W: BufMut>(&self, version: Version, w: &mut W) -> Result<usize> {
+        let mut l = self.track_namespace.serialize_with_version(version, w)?;
+        l += self.track_name.serialize(w)?;
+        Ok(l)
+    }
+}
+
+/// Uses the Draft-04 layout, with a single-string namespace.
+impl Deserializer for TrackStatusRequest {
+    fn deserialize<R: Buf>(r: &mut R) -> Result<(Self, usize)> {
+        Self::deserialize_with_version(Version::Draft04, r)
+    }
 }
 
 impl Serializer for TrackStatusRequest {
     fn serialize<W: BufMut>(&self, w: &mut W) -> Result<usize> {
-        let mut l = self.track_namespace.serialize(w)?;
-        l += self.track_name.serialize(w)?;
-        Ok(l)
+        self.serialize_with_version(Version::Draft04, w)
     }
 }
 
@@ -43,7 +64,7 @@ mod test {
         ];
 
         let expected_message = ControlMessage::TrackStatusRequest(TrackStatusRequest {
-            track_namespace: "foo".to_string(),
+            track_namespace: "foo".into(),
             track_name: "abcd".to_string(),
         });
 
@@ -1,21 +1,36 @@
+use crate::message::{Namespace, Version};
 use crate::{Deserializer, Result, Serializer};
 use bytes::{Buf, BufMut};
 
 #[derive(Default, Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct UnAnnounce {
-    pub track_namespace: String,
+    pub track_namespace: Namespace,
 }
 
+impl UnAnnounce {
+    /// Reads an UNANNOUNCE in the layout used by `version`.
+    pub fn deserialize_with_version<R: Buf>(version: Version, r: &mut R) -> Result<(Self, usize)> {
+        let (track_namespace, tnsl) = Namespace::deserialize_with_version(version, r)?;
+        Ok((Self { track_namespace }, tnsl))
+    }
+
+    /// Writes an UNANNOUNCE in the layout used by `version`.
+    pub fn serialize_with_version<W: BufMut>(&self, version: Version, w: &mut W) -> Result<usize> {
+        self.track_namespace.serialize_with_version(version, w)
+    }
+}
+
+/// Uses the Draft-04 layout, with a single-string namespace.
 impl Deserializer for UnAnnounce {
     fn deserialize<R: Buf>(r: &mut R) -> Result<(Self, usize)> {
-        let (track_namespace, tnsl) = String::deserialize(r)?;
-        Ok((Self { track_namespace }, tnsl))
+        Self::deserialize_with_version(Version::Draft04, r)
     }
 }
 
 impl Serializer for UnAnnounce {
     fn serialize<W: BufMut>(&self, w: &mut W) -> Result<usize> {
-        self.track_namespace.serialize(w)
+        self.serialize_with_version(Version::Draft04, w)
     }
 }
 
@@ -32,7 +47,7 @@ mod test {
         ];
 
         let expected_message = ControlMessage::UnAnnounce(UnAnnounce {
-            track_namespace: "foo".to_string(),
+            track_namespace: "foo".into(),
         });
 
         let mut cursor: Cursor<&[u8]> = Cursor::new(expected_packet.as_ref());
@@ -46,4 +61,15 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn test_unannounce_truncated() -> Result<()> {
+        let packet: Vec<u8> = vec![
+            0x09, 0x03, 0x66, 0x6f, // track_namespace cut short
+        ];
+        let mut cursor: Cursor<&[u8]> = Cursor::new(packet.as_ref());
+        assert!(ControlMessage::deserialize(&mut cursor).is_err());
+
+        Ok(())
+    }
 }
@@ -2,6 +2,7 @@ use crate::{Deserializer, Result, Serializer};
 use bytes::{Buf, BufMut};
 
 #[derive(Default, Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct UnSubscribe {
     pub subscribe_id: u64,
 }
@@ -44,4 +45,24 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn test_unsubscribe_long_varint() -> Result<()> {
+        let expected_packet: Vec<u8> = vec![
+            0x0a, 0x41, 0x2c, // subscribe_id = 300
+        ];
+
+        let expected_message = ControlMessage::UnSubscribe(UnSubscribe { subscribe_id: 300 });
+
+        let mut cursor: Cursor<&[u8]> = Cursor::new(expected_packet.as_ref());
+        let (actual_message, actual_len) = ControlMessage::deserialize(&mut cursor)?;
+        assert_eq!(expected_message, actual_message);
+        assert_eq!(expected_packet.len(), actual_len);
+        assert_eq!(&expected_message.to_bytes()?[..], &expected_packet[..]);
+
+        let mut cursor: Cursor<&[u8]> = Cursor::new(&expected_packet[..2]);
+        assert!(ControlMessage::deserialize(&mut cursor).is_err());
+
+        Ok(())
+    }
 }
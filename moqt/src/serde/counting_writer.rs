@@ -0,0 +1,102 @@
+use bytes::buf::UninitSlice;
+use bytes::{Buf, BufMut};
+
+const SCRATCH_SIZE: usize = 64;
+
+/// A BufMut that discards everything written to it and only tallies the number
+/// of bytes, so serializing into it yields the encoded length of a value
+/// without allocating.
+#[derive(Debug)]
+pub struct CountingWriter {
+    written: usize,
+    // Bytes written through chunk_mut() land here and are overwritten.
+    scratch: [u8; SCRATCH_SIZE],
+}
+
+impl Default for CountingWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CountingWriter {
+    pub fn new() -> Self {
+        Self {
+            written: 0,
+            scratch: [0; SCRATCH_SIZE],
+        }
+    }
+
+    /// Returns the number of bytes written so far.
+    pub fn written(&self) -> usize {
+        self.written
+    }
+}
+
+unsafe impl BufMut for CountingWriter {
+    fn remaining_mut(&self) -> usize {
+        usize::MAX - self.written
+    }
+
+    unsafe fn advance_mut(&mut self, cnt: usize) {
+        self.written += cnt;
+    }
+
+    fn chunk_mut(&mut self) -> &mut UninitSlice {
+        UninitSlice::new(&mut self.scratch)
+    }
+
+    fn put<T: Buf>(&mut self, mut src: T)
+    where
+        Self: Sized,
+    {
+        self.written += src.remaining();
+        src.advance(src.remaining());
+    }
+
+    fn put_slice(&mut self, src: &[u8]) {
+        self.written += src.len();
+    }
+
+    fn put_bytes(&mut self, _val: u8, cnt: usize) {
+        self.written += cnt;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::message::subscribe::Subscribe;
+    use crate::message::{ControlMessage, FilterType, FullSequence};
+    use crate::{Parameters, Result, Serializer};
+    use bytes::Bytes;
+
+    #[test]
+    fn test_counting_writer() -> Result<()> {
+        let message = ControlMessage::Subscribe(Subscribe {
+            subscribe_id: 1,
+            track_alias: 2,
+            track_namespace: "foo".repeat(40).into(),
+            track_name: "abcd".to_string(),
+            filter_type: FilterType::AbsoluteStart(FullSequence::new(4, 1)),
+            authorization_info: Some("bar".into()),
+            parameters: Parameters::new(),
+        });
+
+        let mut buf = vec![];
+        let l = message.serialize(&mut buf)?;
+        let mut writer = CountingWriter::new();
+        assert_eq!(message.serialize(&mut writer)?, l);
+        assert_eq!(writer.written(), buf.len());
+
+        let mut writer = CountingWriter::new();
+        writer.put_u8(0x01);
+        writer.put_u64(0x02);
+        writer.put(Bytes::from_static(&[0u8; 100]));
+        writer.put_slice(&[0u8; 3]);
+        writer.put_bytes(0, 5);
+        assert_eq!(writer.written(), 1 + 8 + 100 + 3 + 5);
+
+        Ok(())
+    }
+}
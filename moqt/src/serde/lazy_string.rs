@@ -0,0 +1,82 @@
+use crate::serde::{Deserializer, Serializer};
+use crate::{Error, Result};
+use alloc::string::String;
+use bytes::{Buf, BufMut, Bytes};
+
+/// A length-prefixed string whose UTF-8 is only checked when it is read. It
+/// decodes like `String` but keeps the raw bytes, so a large value that is
+/// never inspected is not scanned.
+#[derive(Default, Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct LazyString(Bytes);
+
+impl LazyString {
+    pub fn new(bytes: Bytes) -> Self {
+        Self(bytes)
+    }
+
+    /// Validates and returns the string, failing with `ErrInvalidUtf8` if the
+    /// bytes are not UTF-8.
+    pub fn as_str(&self) -> Result<&str> {
+        Ok(core::str::from_utf8(&self.0)?)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<String> for LazyString {
+    fn from(value: String) -> Self {
+        Self(Bytes::from(value))
+    }
+}
+
+impl From<&str> for LazyString {
+    fn from(value: &str) -> Self {
+        Self(Bytes::copy_from_slice(value.as_bytes()))
+    }
+}
+
+impl Deserializer for LazyString {
+    fn deserialize<B: Buf>(r: &mut B) -> Result<(Self, usize)> {
+        let (size, l) = usize::deserialize(r)?;
+        if r.remaining() < size {
+            return Err(Error::ErrUnexpectedEnd);
+        }
+        Ok((Self(r.copy_to_bytes(size)), size + l))
+    }
+}
+
+impl Serializer for LazyString {
+    fn serialize<B: BufMut>(&self, w: &mut B) -> Result<usize> {
+        let l = self.0.len().serialize(w)?;
+        if w.remaining_mut() < self.0.len() {
+            return Err(Error::ErrBufferTooShort);
+        }
+        w.put_slice(&self.0);
+        Ok(l + self.0.len())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_lazy_string() -> Result<()> {
+        let packet = [0x03, 0x66, 0x6f, 0x6f];
+        let (value, len) = LazyString::deserialize(&mut &packet[..])?;
+        assert_eq!(len, 4);
+        assert_eq!(value.as_str()?, "foo");
+        assert_eq!(&value.to_bytes()?[..], &packet[..]);
+
+        // Decoding does not look at the bytes; reading them does.
+        let invalid = [0x02, 0xc3, 0x28];
+        let (value, _) = LazyString::deserialize(&mut &invalid[..])?;
+        assert_eq!(value.as_bytes(), &[0xc3, 0x28]);
+        assert!(matches!(value.as_str(), Err(Error::ErrInvalidUtf8(_))));
+
+        Ok(())
+    }
+}
@@ -1,6 +1,11 @@
+use crate::serde::counting_writer::CountingWriter;
 use crate::{Error, Result};
-use bytes::{Buf, BufMut, Bytes};
+use alloc::string::String;
+use alloc::vec;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
 
+pub mod counting_writer;
+pub mod lazy_string;
 pub mod parameters;
 pub mod varint;
 
@@ -9,14 +14,50 @@ pub trait Deserializer {
     where
         Self: Sized,
         B: Buf;
+
+    /// Like |deserialize|, for callers that only need the value. |r| is still
+    /// advanced past the consumed bytes.
+    fn decode_from<B>(r: &mut B) -> Result<Self>
+    where
+        Self: Sized,
+        B: Buf,
+    {
+        Self::deserialize(r).map(|(value, _)| value)
+    }
 }
 
 pub trait Serializer {
     fn serialize<B: BufMut>(&self, w: &mut B) -> Result<usize>;
+
+    /// Returns the number of bytes |serialize| writes, so callers can size
+    /// buffers up front. Values that cannot be serialized fail with the same
+    /// error as |serialize|.
+    fn encoded_size(&self) -> Result<usize> {
+        let mut sink = CountingWriter::new();
+        self.serialize(&mut sink)?;
+        Ok(sink.written())
+    }
+
+    /// Serializes into a buffer sized by |encoded_size|.
+    fn to_bytes(&self) -> Result<Bytes> {
+        let mut buffer = BytesMut::with_capacity(self.encoded_size()?);
+        self.serialize(&mut buffer)?;
+        Ok(buffer.freeze())
+    }
+}
+
+/// Sums the lengths consumed by the fields of a message, failing with
+/// ErrLengthOverflow rather than wrapping.
+pub(crate) fn checked_len(lens: &[usize]) -> Result<usize> {
+    lens.iter()
+        .try_fold(0usize, |total, &len| total.checked_add(len))
+        .ok_or(Error::ErrLengthOverflow)
 }
 
+/// Booleans such as ContentExists in SUBSCRIBE_OK are a single byte holding 0
+/// or 1, not a varint. The two encodings coincide for these values, but any
+/// other byte is rejected with `ErrInvalidBooleanValue`.
 impl Serializer for bool {
-    /// Encode a varint to the given writer.
     fn serialize<W: BufMut>(&self, w: &mut W) -> Result<usize> {
         if !w.has_remaining_mut() {
             return Err(Error::ErrBufferTooShort);
@@ -29,7 +70,7 @@ impl Serializer for bool {
 impl Deserializer for bool {
     fn deserialize<R: Buf>(r: &mut R) -> Result<(Self, usize)> {
         if !r.has_remaining() {
-            return Err(Error::ErrBufferTooShort);
+            return Err(Error::ErrUnexpectedEnd);
         }
         let b = r.get_u8();
         match b {
@@ -58,11 +99,42 @@ impl Deserializer for Bytes {
     }
 }
 
+/// A varint length followed by that many bytes: the layout of `String`
+/// without the UTF-8 requirement, for binary parameters and names. A bare
+/// `Bytes` is an object payload, which runs unprefixed.
+#[derive(Default, Debug, Clone, Eq, PartialEq, Hash)]
+pub struct LengthPrefixedBytes(pub Bytes);
+
+impl Deserializer for LengthPrefixedBytes {
+    fn deserialize<R: Buf>(r: &mut R) -> Result<(Self, usize)> {
+        let (size, l) = usize::deserialize(r)?;
+        if r.remaining() < size {
+            return Err(Error::ErrUnexpectedEnd);
+        }
+        Ok((Self(r.copy_to_bytes(size)), checked_len(&[size, l])?))
+    }
+}
+
+impl Serializer for LengthPrefixedBytes {
+    fn serialize<W: BufMut>(&self, w: &mut W) -> Result<usize> {
+        let l = self.0.len().serialize(w)?;
+        if w.remaining_mut() < self.0.len() {
+            return Err(Error::ErrBufferTooShort);
+        }
+        w.put_slice(&self.0);
+        Ok(l + self.0.len())
+    }
+
+    fn encoded_size(&self) -> Result<usize> {
+        Ok(self.0.len().encoded_size()? + self.0.len())
+    }
+}
+
 impl Deserializer for String {
     fn deserialize<B: Buf>(r: &mut B) -> Result<(Self, usize)> {
         let (size, l) = usize::deserialize(r)?;
         if r.remaining() < size {
-            return Err(Error::ErrBufferTooShort);
+            return Err(Error::ErrUnexpectedEnd);
         }
 
         let mut buf = vec![0; size];
@@ -82,4 +154,64 @@ impl Serializer for String {
         w.put(self.as_ref());
         Ok(l + self.len())
     }
+
+    fn encoded_size(&self) -> Result<usize> {
+        Ok(self.len().encoded_size()? + self.len())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_length_sum_overflow() {
+        // Field lengths as large as those a 32-bit target could accumulate.
+        let lens = [usize::MAX / 2, usize::MAX / 2, 1, 1, 0, 0];
+        assert_eq!(checked_len(&lens), Err(Error::ErrLengthOverflow));
+        assert_eq!(checked_len(&lens[..3]), Ok(usize::MAX));
+    }
+
+    #[test]
+    fn test_bool() -> Result<()> {
+        for (value, byte) in [(false, 0x00), (true, 0x01)] {
+            let mut packet = vec![];
+            assert_eq!(value.serialize(&mut packet)?, 1);
+            assert_eq!(packet, [byte]);
+            assert_eq!(bool::deserialize(&mut packet.as_slice())?, (value, 1));
+        }
+
+        assert_eq!(
+            bool::deserialize(&mut &[0x02][..]),
+            Err(Error::ErrInvalidBooleanValue(2))
+        );
+        assert_eq!(
+            bool::deserialize(&mut &[][..]),
+            Err(Error::ErrUnexpectedEnd)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_length_prefixed_bytes() -> Result<()> {
+        let packet = [0x03, 0xc3, 0x28, 0xff];
+        let value = LengthPrefixedBytes(Bytes::from_static(&[0xc3, 0x28, 0xff]));
+        assert_eq!(value.encoded_size()?, packet.len());
+        assert_eq!(&value.to_bytes()?[..], &packet[..]);
+        assert_eq!(
+            LengthPrefixedBytes::deserialize(&mut &packet[..])?,
+            (value.clone(), packet.len())
+        );
+
+        // Bare Bytes is a payload and carries no prefix.
+        assert_eq!(&value.0.to_bytes()?[..], &packet[1..]);
+
+        assert_eq!(
+            LengthPrefixedBytes::deserialize(&mut &packet[..3]),
+            Err(Error::ErrUnexpectedEnd)
+        );
+
+        Ok(())
+    }
 }
@@ -1,43 +1,75 @@
+use crate::message::message_parser::ErrorCode;
+use crate::serde::lazy_string::LazyString;
 use crate::serde::{Deserializer, Serializer};
 use crate::{Error, Result};
-use bytes::BufMut;
-use std::collections::HashMap;
-use std::io::Cursor;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::ToString;
+use alloc::vec;
+use alloc::vec::Vec;
+use bytes::{Buf, BufMut, Bytes};
+
+/// The most parameters a message may carry. Draft-04 defines five, so a
+/// larger count is rejected before any are read.
+pub const MAX_PARAMETERS: u64 = 64;
 
 #[derive(Default, Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub enum ParameterKey {
     #[default]
     Role = 0,
     Path = 1,
     AuthorizationInfo = 2,
+    DeliveryTimeout = 3,
+    MaxCacheDuration = 4,
 }
 
 impl TryFrom<u64> for ParameterKey {
     type Error = Error;
 
-    fn try_from(value: u64) -> std::result::Result<Self, Self::Error> {
+    fn try_from(value: u64) -> core::result::Result<Self, Self::Error> {
         match value {
-            0x1 => Ok(ParameterKey::Role),
-            0x2 => Ok(ParameterKey::Path),
-            0x3 => Ok(ParameterKey::AuthorizationInfo),
+            0x0 => Ok(ParameterKey::Role),
+            0x1 => Ok(ParameterKey::Path),
+            0x2 => Ok(ParameterKey::AuthorizationInfo),
+            0x3 => Ok(ParameterKey::DeliveryTimeout),
+            0x4 => Ok(ParameterKey::MaxCacheDuration),
             _ => Err(Error::ErrUnsupportedParameter(value)),
         }
     }
 }
 
+impl ParameterKey {
+    /// Whether the parameter's value is a single varint.
+    pub fn is_varint(&self) -> bool {
+        matches!(
+            self,
+            ParameterKey::Role | ParameterKey::DeliveryTimeout | ParameterKey::MaxCacheDuration
+        )
+    }
+
+    /// The parameter's name as written in the draft.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ParameterKey::Role => "ROLE",
+            ParameterKey::Path => "PATH",
+            ParameterKey::AuthorizationInfo => "AUTHORIZATION_INFO",
+            ParameterKey::DeliveryTimeout => "DELIVERY_TIMEOUT",
+            ParameterKey::MaxCacheDuration => "MAX_CACHE_DURATION",
+        }
+    }
+}
+
 #[derive(Default, Debug, Clone, Eq, PartialEq)]
-pub struct Parameters(pub HashMap<u64, Vec<u8>>);
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Parameters(pub BTreeMap<u64, Vec<u8>>);
 
 impl Serializer for Parameters {
     fn serialize<W: BufMut>(&self, w: &mut W) -> Result<usize> {
         let mut l = self.0.len().serialize(w)?;
 
-        #[allow(clippy::map_clone)]
-        let mut kinds: Vec<u64> = self.0.keys().map(|key| *key).collect();
-        kinds.sort();
-        for kind in kinds {
+        for (&kind, value) in self.0.iter() {
             l += kind.serialize(w)?;
-            let value = &self.0[&kind];
             if !(kind == ParameterKey::Path as u64
                 || kind == ParameterKey::AuthorizationInfo as u64)
             {
@@ -54,6 +86,12 @@ impl Serializer for Parameters {
     }
 }
 
+impl Deserializer for Parameters {
+    fn deserialize<R: Buf>(r: &mut R) -> Result<(Self, usize)> {
+        Parameters::deserialize_with(r, Parameters::allows_duplicate)
+    }
+}
+
 impl Parameters {
     pub fn new() -> Self {
         Self::default()
@@ -61,7 +99,7 @@ impl Parameters {
 
     pub fn insert<P: Serializer>(&mut self, key: ParameterKey, p: P) -> Result<()> {
         if self.contains(key) {
-            return Err(Error::ErrDuplicateParameter);
+            return Err(Error::ErrDuplicateParameter(key as u64));
         }
         let mut value = Vec::new();
         p.serialize(&mut value)?;
@@ -69,19 +107,205 @@ impl Parameters {
         Ok(())
     }
 
+    pub fn insert_varint(&mut self, key: ParameterKey, value: u64) -> Result<()> {
+        self.insert(key, value)
+    }
+
+    /// Returns the value bytes of a parameter. Path and AuthorizationInfo are
+    /// stored with their length, which is stripped here.
+    pub fn get_bytes(&self, key: ParameterKey) -> Option<&[u8]> {
+        let mut value = self.0.get(&(key as u64))?.as_slice();
+        if key == ParameterKey::Path || key == ParameterKey::AuthorizationInfo {
+            let (size, _) = usize::deserialize(&mut value).ok()?;
+            return value.get(..size);
+        }
+        Some(value)
+    }
+
+    /// Returns a parameter whose value is a single varint, or none if it is
+    /// absent or not exactly one varint.
+    pub fn get_varint(&self, key: ParameterKey) -> Option<u64> {
+        let mut value = self.get_bytes(key)?;
+        let (v, _) = u64::deserialize(&mut value).ok()?;
+        if !value.is_empty() {
+            return None;
+        }
+        Some(v)
+    }
+
+    /// Returns a parameter whose value is UTF-8, or none if it is absent or
+    /// not valid UTF-8.
+    pub fn get_string(&self, key: ParameterKey) -> Option<&str> {
+        core::str::from_utf8(self.get_bytes(key)?).ok()
+    }
+
+    /// Removes a parameter as a [`LazyString`], deferring UTF-8 validation
+    /// until the value is read, so a large value that is never inspected is
+    /// not scanned. The stored bytes are moved, not copied. Messages decode
+    /// AUTHORIZATION_INFO this way.
+    pub fn remove_lazy_string(&mut self, key: ParameterKey) -> Option<LazyString> {
+        let value = Bytes::from(self.0.remove(&(key as u64))?);
+        if key == ParameterKey::Path || key == ParameterKey::AuthorizationInfo {
+            let (size, sl) = usize::deserialize(&mut &value[..]).ok()?;
+            let end = sl.checked_add(size).filter(|&end| end <= value.len())?;
+            return Some(LazyString::new(value.slice(sl..end)));
+        }
+        Some(LazyString::new(value))
+    }
+
+    /// Compares the AUTHORIZATION_INFO value against `expected` in time that
+    /// depends only on the lengths, so a relay checking a token does not
+    /// leak how much of it matched. Returns false if the parameter is absent.
+    pub fn auth_info_eq_ct(&self, expected: &[u8]) -> bool {
+        match self.get_bytes(ParameterKey::AuthorizationInfo) {
+            Some(actual) => constant_time_eq(actual, expected),
+            None => false,
+        }
+    }
+
     pub fn contains(&self, key: ParameterKey) -> bool {
         self.0.contains_key(&(key as u64))
     }
 
+    /// Parses a parameters block, returning `ErrDuplicateParameter` for a
+    /// repeated key unless `allows_duplicate` accepts it. Only the first value
+    /// of an accepted repeat is kept. Known varint parameters must hold exactly
+    /// one varint. A parameter longer than the remaining input fails with
+    /// `ErrParameterLengthMismatch`, which on a stream may only mean that the
+    /// rest of the message has not arrived yet. More than [`MAX_PARAMETERS`]
+    /// parameters is a protocol violation.
+    pub fn deserialize_with<R: Buf, F: Fn(u64) -> bool>(
+        r: &mut R,
+        allows_duplicate: F,
+    ) -> Result<(Self, usize)> {
+        let mut parameters = Parameters::new();
+        let (num_params, mut pl) = u64::deserialize(r)?;
+        if num_params > MAX_PARAMETERS {
+            return Err(Error::ErrParseError(
+                ErrorCode::ProtocolViolation,
+                format!("Too many parameters: {}", num_params),
+            ));
+        }
+        for _ in 0..num_params {
+            let (key, kl) = u64::deserialize(r)?;
+            pl += kl;
+            let (size, sl) = usize::deserialize(r)?;
+            pl += sl;
+
+            if r.remaining() < size {
+                return Err(Error::ErrParameterLengthMismatch { key, len: size });
+            }
+            let mut buf = vec![0; size];
+            r.copy_to_slice(&mut buf);
+            pl += size;
+
+            if ParameterKey::try_from(key).is_ok_and(|key| key.is_varint()) {
+                let mut value = buf.as_slice();
+                if u64::deserialize(&mut value).is_err() || !value.is_empty() {
+                    return Err(Error::ErrParseError(
+                        ErrorCode::ParameterLengthMismatch,
+                        "Parameter length does not match varint encoding".to_string(),
+                    ));
+                }
+            }
+            if parameters.0.contains_key(&key) {
+                if allows_duplicate(key) {
+                    continue;
+                }
+                return Err(Error::ErrDuplicateParameter(key));
+            }
+            if key == ParameterKey::Path as u64 || key == ParameterKey::AuthorizationInfo as u64 {
+                // Stored with its length, as insert() would store it.
+                let mut value = Vec::with_capacity(size + 8);
+                size.serialize(&mut value)?;
+                value.extend_from_slice(&buf);
+                parameters.0.insert(key, value);
+            } else {
+                parameters.0.insert(key, buf);
+            }
+        }
+        Ok((parameters, pl))
+    }
+
+    /// The default duplicate policy: parameters this crate knows may appear at
+    /// most once, while unknown parameters may repeat.
+    pub fn allows_duplicate(key: u64) -> bool {
+        ParameterKey::try_from(key).is_err()
+    }
+
+    /// Parses the parameters block of a control message, splitting off
+    /// AUTHORIZATION_INFO. A repeated known parameter is a protocol violation
+    /// in `message`; every other parameter is kept as received.
+    pub(crate) fn deserialize_with_authorization_info<R: Buf>(
+        r: &mut R,
+        message: &str,
+    ) -> Result<(Option<LazyString>, Self, usize)> {
+        let (mut parameters, pl) = Parameters::deserialize(r)
+            .map_err(|err| Parameters::duplicate_parameter_error(err, message))?;
+        let authorization_info = parameters.remove_lazy_string(ParameterKey::AuthorizationInfo);
+        Ok((authorization_info, parameters, pl))
+    }
+
+    /// Turns `ErrDuplicateParameter` into the protocol violation reported for
+    /// `message`, passing other errors through.
+    pub(crate) fn duplicate_parameter_error(err: Error, message: &str) -> Error {
+        match err {
+            Error::ErrDuplicateParameter(key) => Error::ErrParseError(
+                ErrorCode::ProtocolViolation,
+                match ParameterKey::try_from(key) {
+                    Ok(key) => format!("{} parameter appears twice in {}", key.name(), message),
+                    Err(_) => format!("Parameter {} appears twice in {}", key, message),
+                },
+            ),
+            err => err,
+        }
+    }
+
     pub fn remove<P: Deserializer>(&mut self, key: ParameterKey) -> Result<Option<P>> {
         if let Some(value) = self.0.remove(&(key as u64)) {
-            let mut cursor = Cursor::new(value);
-            let (p, _) = P::deserialize(&mut cursor)?;
+            let (p, _) = P::deserialize(&mut value.as_slice())?;
             Ok(Some(p))
         } else {
             Ok(None)
         }
     }
+
+    /// Removes a parameter by its wire key without decoding it, so unknown
+    /// parameters can be dropped too. Returns the value bytes as
+    /// [`Parameters::get_bytes`] would, with the stored length of Path and
+    /// AuthorizationInfo stripped.
+    pub fn remove_raw(&mut self, key: u64) -> Option<Vec<u8>> {
+        let mut value = self.0.remove(&key)?;
+        if key == ParameterKey::Path as u64 || key == ParameterKey::AuthorizationInfo as u64 {
+            let (size, sl) = usize::deserialize(&mut value.as_slice()).ok()?;
+            if value.len() - sl < size {
+                return None;
+            }
+            value.drain(..sl);
+            value.truncate(size);
+        }
+        Some(value)
+    }
+
+    /// Copies every parameter of `other` into this set, as a relay does when
+    /// rewriting a message it forwards. A key present in both keeps this set's
+    /// value unless `overwrite` is true.
+    pub fn merge(&mut self, other: &Parameters, overwrite: bool) {
+        for (&key, value) in other.0.iter() {
+            if overwrite || !self.0.contains_key(&key) {
+                self.0.insert(key, value.clone());
+            }
+        }
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let diff = a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y));
+    // Keep the compiler from turning the fold into an early exit.
+    core::hint::black_box(diff) == 0
 }
 
 #[cfg(test)]
@@ -117,4 +341,243 @@ mod test {
         );
         Ok(())
     }
+
+    #[test]
+    fn test_params_count() -> Result<()> {
+        let params = Parameters::new();
+        assert_eq!(&params.to_bytes()?[..], &[0x00]);
+
+        let mut params = Parameters::new();
+        params.insert(ParameterKey::Role, Role::PubSub)?;
+        params.insert(ParameterKey::Path, "foo".to_string())?;
+        let expected = [
+            0x02, // 2 parameters
+            0x00, 0x01, 0x03, // role = PubSub
+            0x01, 0x03, 0x66, 0x6f, 0x6f, // path = "foo"
+        ];
+        assert_eq!(params.encoded_size()?, expected.len());
+        assert_eq!(&params.to_bytes()?[..], &expected[..]);
+        assert_eq!(
+            Parameters::deserialize(&mut &expected[..])?,
+            (params, expected.len())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_typed_params() -> Result<()> {
+        let mut params = Parameters::new();
+        params.insert_varint(ParameterKey::DeliveryTimeout, 10_000)?;
+        params.insert_varint(ParameterKey::MaxCacheDuration, 0)?;
+        params.insert(ParameterKey::AuthorizationInfo, "password".to_string())?;
+
+        assert_eq!(
+            params.get_varint(ParameterKey::DeliveryTimeout),
+            Some(10_000)
+        );
+        assert_eq!(params.get_varint(ParameterKey::MaxCacheDuration), Some(0));
+        assert_eq!(params.get_varint(ParameterKey::Role), None);
+        assert_eq!(
+            params.get_bytes(ParameterKey::DeliveryTimeout),
+            Some(&[0x67, 0x10][..])
+        );
+
+        assert_eq!(
+            params.get_string(ParameterKey::AuthorizationInfo),
+            Some("password")
+        );
+        assert_eq!(
+            params.get_bytes(ParameterKey::AuthorizationInfo),
+            Some(&b"password"[..])
+        );
+        // "password" is not a single varint.
+        assert_eq!(params.get_varint(ParameterKey::AuthorizationInfo), None);
+
+        // Values parsed off the wire read back the same way.
+        let mut buf = vec![];
+        params.serialize(&mut buf)?;
+        let (_, parsed, _) =
+            Parameters::deserialize_with_authorization_info(&mut buf.as_slice(), "TEST")?;
+        assert_eq!(
+            parsed.get_varint(ParameterKey::DeliveryTimeout),
+            Some(10_000)
+        );
+        assert_eq!(parsed.get_varint(ParameterKey::MaxCacheDuration), Some(0));
+
+        assert_eq!(ParameterKey::try_from(0x3)?, ParameterKey::DeliveryTimeout);
+        Ok(())
+    }
+
+    #[test]
+    fn test_lazy_string_param() -> Result<()> {
+        let packet: Vec<u8> = vec![
+            0x02, // 2 parameters
+            0x00, 0x01, 0x03, // role = PubSub
+            0x02, 0x02, 0xc3, 0x28, // authorization_info = invalid UTF-8
+        ];
+
+        let (mut params, _) = Parameters::deserialize(&mut packet.as_slice())?;
+        assert_eq!(params.get_varint(ParameterKey::Role), Some(0x03));
+        let authorization_info = params
+            .remove_lazy_string(ParameterKey::AuthorizationInfo)
+            .unwrap();
+        assert_eq!(authorization_info.as_bytes(), &[0xc3, 0x28]);
+        assert!(authorization_info.as_str().is_err());
+        assert_eq!(params.remove_lazy_string(ParameterKey::Path), None);
+
+        let (mut params, _) = Parameters::deserialize(&mut packet.as_slice())?;
+        assert!(params
+            .remove::<String>(ParameterKey::AuthorizationInfo)
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_auth_info_eq_ct() -> Result<()> {
+        let mut params = Parameters::new();
+        assert!(!params.auth_info_eq_ct(b""));
+
+        params.insert(ParameterKey::AuthorizationInfo, "bar".to_string())?;
+        assert!(params.auth_info_eq_ct(b"bar"));
+        assert!(!params.auth_info_eq_ct(b"baz"));
+        assert!(!params.auth_info_eq_ct(b"ba"));
+        assert!(!params.auth_info_eq_ct(b"barr"));
+        assert!(!params.auth_info_eq_ct(b""));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_truncated_params() -> Result<()> {
+        let packet = [
+            0x01, // 1 parameter
+            0x00, 0x01, 0x03, // role = PubSub
+        ];
+        // Running out of input is an unexpected end, never a short buffer,
+        // which is reserved for encoding.
+        assert_eq!(
+            Parameters::deserialize(&mut &packet[..2]),
+            Err(Error::ErrUnexpectedEnd)
+        );
+        assert_eq!(
+            Parameters::deserialize(&mut &packet[..3]),
+            Err(Error::ErrParameterLengthMismatch { key: 0, len: 1 })
+        );
+        assert_eq!(
+            String::deserialize(&mut &[0x03, 0x66, 0x6f][..]),
+            Err(Error::ErrUnexpectedEnd)
+        );
+
+        let mut short = [0u8; 2];
+        assert_eq!(
+            "foo".to_string().serialize(&mut &mut short[..]),
+            Err(Error::ErrBufferTooShort)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_too_many_params() {
+        // 2^62 - 1 parameters and no room for any of them.
+        let packet = [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00];
+        assert_eq!(
+            Parameters::deserialize(&mut &packet[..]),
+            Err(Error::ErrParseError(
+                ErrorCode::ProtocolViolation,
+                "Too many parameters: 4611686018427387903".to_string()
+            ))
+        );
+        // The limit itself is allowed.
+        assert_eq!(
+            Parameters::deserialize(&mut &[0x40, MAX_PARAMETERS as u8][..]),
+            Err(Error::ErrUnexpectedEnd)
+        );
+    }
+
+    #[test]
+    fn test_merge_params() -> Result<()> {
+        let mut params = Parameters::new();
+        params.insert(ParameterKey::AuthorizationInfo, "bar".to_string())?;
+        params.insert_varint(ParameterKey::DeliveryTimeout, 1_000)?;
+
+        let mut relay = Parameters::new();
+        relay.insert_varint(ParameterKey::DeliveryTimeout, 500)?;
+        relay.0.insert(0x20, vec![0xbe, 0xef]);
+
+        let mut kept = params.clone();
+        kept.merge(&relay, false);
+        assert_eq!(kept.get_varint(ParameterKey::DeliveryTimeout), Some(1_000));
+        assert_eq!(kept.0.get(&0x20), Some(&vec![0xbe, 0xef]));
+        assert!(kept.auth_info_eq_ct(b"bar"));
+
+        let mut overridden = params.clone();
+        overridden.merge(&relay, true);
+        assert_eq!(
+            overridden.get_varint(ParameterKey::DeliveryTimeout),
+            Some(500)
+        );
+        assert_eq!(overridden.0.get(&0x20), Some(&vec![0xbe, 0xef]));
+        assert!(overridden.auth_info_eq_ct(b"bar"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_raw_param() -> Result<()> {
+        let mut params = Parameters::new();
+        params.insert_varint(ParameterKey::DeliveryTimeout, 1_000)?;
+        params.0.insert(0x20, vec![0xbe, 0xef]);
+
+        assert_eq!(params.remove_raw(0x20), Some(vec![0xbe, 0xef]));
+        assert_eq!(params.remove_raw(0x20), None);
+        assert!(params
+            .remove_raw(ParameterKey::DeliveryTimeout as u64)
+            .is_some());
+        assert_eq!(params, Parameters::new());
+
+        // The stored length of AUTHORIZATION_INFO is not part of its value.
+        params.insert(ParameterKey::AuthorizationInfo, "bar".to_string())?;
+        assert_eq!(
+            params.remove_raw(ParameterKey::AuthorizationInfo as u64),
+            Some(b"bar".to_vec())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_duplicate_params() -> Result<()> {
+        let known: &[u8] = &[
+            0x02, // 2 parameters
+            0x03, 0x01, 0x05, // delivery_timeout = 5
+            0x03, 0x01, 0x06, // delivery_timeout = 6
+        ];
+        assert_eq!(
+            Parameters::deserialize(&mut &known[..]),
+            Err(Error::ErrDuplicateParameter(
+                ParameterKey::DeliveryTimeout as u64
+            ))
+        );
+        // The policy is per key.
+        let (params, pl) = Parameters::deserialize_with(&mut &known[..], |key| {
+            key == ParameterKey::DeliveryTimeout as u64
+        })?;
+        assert_eq!(pl, known.len());
+        assert_eq!(params.get_varint(ParameterKey::DeliveryTimeout), Some(5));
+
+        let unknown: &[u8] = &[
+            0x03, // 3 parameters
+            0x20, 0x01, 0x05, // unknown
+            0x20, 0x01, 0x06, // unknown again
+            0x03, 0x01, 0x07, // delivery_timeout = 7
+        ];
+        let (params, pl) = Parameters::deserialize(&mut &unknown[..])?;
+        assert_eq!(pl, unknown.len());
+        assert_eq!(params.0.get(&0x20), Some(&vec![0x05]));
+        assert_eq!(params.get_varint(ParameterKey::DeliveryTimeout), Some(7));
+
+        Ok(())
+    }
 }
@@ -1,7 +1,7 @@
 use crate::serde::{Deserializer, Serializer};
 use crate::{Error, Result};
 use bytes::{Buf, BufMut};
-use std::fmt;
+use core::fmt;
 
 /// An integer less than 2^62
 ///
@@ -9,6 +9,8 @@ use std::fmt;
 /// It would be neat if we could express to Rust that the top two bits are available for use as enum
 /// discriminants
 #[derive(Default, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "u64", into = "u64"))]
 pub struct VarInt(pub(crate) u64);
 
 impl VarInt {
@@ -22,6 +24,16 @@ impl VarInt {
         Self(x as u64)
     }
 
+    /// Construct a `VarInt` from a value known to be in range, usable in `const` items
+    ///
+    /// # Panics
+    ///
+    /// Panics if `x` is not less than 2^62; in a `const` context this is a compile error.
+    pub const fn new(x: u64) -> Self {
+        assert!(x < (1 << 62), "VarInt out of range");
+        Self(x)
+    }
+
     /// Succeeds iff `x` < 2^62
     pub fn from_u64(x: u64) -> Result<Self> {
         if x < 2u64.pow(62) {
@@ -40,6 +52,17 @@ impl VarInt {
         Self(x)
     }
 
+    /// Decode a varint that must use the shortest encoding for its value, for
+    /// fields that require the minimal form. Overlong encodings fail with
+    /// `ErrNonCanonicalVarInt`; `deserialize` accepts them.
+    pub fn deserialize_canonical<B: Buf>(r: &mut B) -> Result<(Self, usize)> {
+        let (x, l) = Self::deserialize(r)?;
+        if x.size() != l {
+            return Err(Error::ErrNonCanonicalVarInt);
+        }
+        Ok((x, l))
+    }
+
     /// Extract the integer value
     pub const fn into_inner(self) -> u64 {
         self.0
@@ -89,23 +112,23 @@ impl From<u32> for VarInt {
 impl TryFrom<u64> for VarInt {
     type Error = Error;
     /// Succeeds iff `x` < 2^62
-    fn try_from(x: u64) -> std::result::Result<Self, Self::Error> {
+    fn try_from(x: u64) -> core::result::Result<Self, Self::Error> {
         Self::from_u64(x)
     }
 }
 
-impl std::convert::TryFrom<u128> for VarInt {
+impl core::convert::TryFrom<u128> for VarInt {
     type Error = Error;
     /// Succeeds iff `x` < 2^62
-    fn try_from(x: u128) -> std::result::Result<Self, Self::Error> {
+    fn try_from(x: u128) -> core::result::Result<Self, Self::Error> {
         Self::from_u64(x.try_into().map_err(|_| Error::ErrVarIntBoundsExceeded)?)
     }
 }
 
-impl std::convert::TryFrom<usize> for VarInt {
+impl core::convert::TryFrom<usize> for VarInt {
     type Error = Error;
     /// Succeeds iff `x` < 2^62
-    fn try_from(x: usize) -> std::result::Result<Self, Self::Error> {
+    fn try_from(x: usize) -> core::result::Result<Self, Self::Error> {
         Self::try_from(x as u64)
     }
 }
@@ -197,6 +220,10 @@ impl Serializer for VarInt {
             Err(Error::ErrMalformedVarInt)
         }
     }
+
+    fn encoded_size(&self) -> Result<usize> {
+        Ok(self.size())
+    }
 }
 
 impl Serializer for u64 {
@@ -205,6 +232,10 @@ impl Serializer for u64 {
         let var = VarInt::try_from(*self)?;
         var.serialize(w)
     }
+
+    fn encoded_size(&self) -> Result<usize> {
+        Ok(VarInt::try_from(*self)?.size())
+    }
 }
 
 impl Deserializer for u64 {
@@ -219,6 +250,10 @@ impl Serializer for usize {
         let var = VarInt::try_from(*self)?;
         var.serialize(w)
     }
+
+    fn encoded_size(&self) -> Result<usize> {
+        Ok(VarInt::try_from(*self)?.size())
+    }
 }
 
 impl Deserializer for usize {
@@ -226,3 +261,96 @@ impl Deserializer for usize {
         VarInt::deserialize(r).map(|v| (v.0.into_inner() as usize, v.1))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SUBSCRIBE: VarInt = VarInt::new(3);
+    const LARGEST: VarInt = VarInt::new((1 << 62) - 1);
+
+    #[test]
+    fn test_const_new() {
+        assert_eq!(SUBSCRIBE.into_inner(), 3);
+        assert_eq!(LARGEST, VarInt::MAX);
+        assert_eq!(VarInt::new(1 << 30), VarInt::from_u64(1 << 30).unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "VarInt out of range")]
+    fn test_new_out_of_range() {
+        let _ = VarInt::new(1 << 62);
+    }
+
+    #[test]
+    fn test_encoding_boundaries() -> Result<()> {
+        let cases: [(u64, &[u8]); 8] = [
+            (63, &[0x3f]),
+            (64, &[0x40, 0x40]),
+            (16383, &[0x7f, 0xff]),
+            (16384, &[0x80, 0x00, 0x40, 0x00]),
+            ((1 << 30) - 1, &[0xbf, 0xff, 0xff, 0xff]),
+            (1 << 30, &[0xc0, 0x00, 0x00, 0x00, 0x40, 0x00, 0x00, 0x00]),
+            ((1 << 62) - 1, &[0xff; 8]),
+            (0, &[0x00]),
+        ];
+        for (value, expected) in cases {
+            let varint = VarInt::from_u64(value)?;
+            let mut buf = vec![];
+            assert_eq!(varint.serialize(&mut buf)?, expected.len(), "{}", value);
+            assert_eq!(buf, expected, "{}", value);
+            assert_eq!(varint.size(), expected.len(), "{}", value);
+
+            let (decoded, len) = VarInt::deserialize(&mut buf.as_slice())?;
+            assert_eq!(decoded, varint);
+            assert_eq!(len, expected.len());
+        }
+        assert!(VarInt(1 << 62).serialize(&mut vec![]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_deserialize_canonical() -> Result<()> {
+        let overlong: &[u8] = &[0x40, 0x05]; // 5 in the 2-byte form
+        let (x, l) = VarInt::deserialize(&mut &overlong[..])?;
+        assert_eq!((x.into_inner(), l), (5, 2));
+        assert_eq!(
+            VarInt::deserialize_canonical(&mut &overlong[..]),
+            Err(Error::ErrNonCanonicalVarInt)
+        );
+
+        let minimal: &[u8] = &[0x05];
+        let (x, l) = VarInt::deserialize_canonical(&mut &minimal[..])?;
+        assert_eq!((x.into_inner(), l), (5, 1));
+        let minimal: &[u8] = &[0x40, 0x40];
+        let (x, l) = VarInt::deserialize_canonical(&mut &minimal[..])?;
+        assert_eq!((x.into_inner(), l), (64, 2));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_from() -> Result<()> {
+        let data: &[u8] = &[0x25, 0x40, 0x40, 0xff];
+        let mut r = data;
+        assert_eq!(u64::decode_from(&mut r)?, 0x25);
+        assert_eq!(VarInt::decode_from(&mut r)?.into_inner(), 64);
+        assert_eq!(r, &[0xff]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_chained_buf() -> Result<()> {
+        // 0x7bbd = 15293 split after the first byte, then a one-byte varint.
+        let first: &[u8] = &[0x7b];
+        let second: &[u8] = &[0xbd, 0x25];
+        let mut r = first.chain(second);
+        assert_eq!(VarInt::deserialize(&mut r)?, (VarInt(15293), 2));
+        assert_eq!(VarInt::deserialize(&mut r)?, (VarInt(0x25), 1));
+        assert!(!r.has_remaining());
+
+        Ok(())
+    }
+}
@@ -1,10 +1,16 @@
 use crate::connection::Connection;
 use crate::handler::Handler;
-use crate::message::announce_error::AnnounceErrorReason;
+use crate::message::announce::Announce;
+use crate::message::announce_error::{AnnounceError, AnnounceErrorCode, AnnounceErrorReason};
+use crate::message::announce_ok::AnnounceOk;
 use crate::message::client_setup::ClientSetup;
+use crate::message::go_away::GoAway;
+use crate::message::message_parser::ErrorCode;
 use crate::message::object::ObjectForwardingPreference;
 use crate::message::subscribe::Subscribe;
-use crate::message::{ControlMessage, FullTrackName, Role};
+use crate::message::track_status::TrackStatus;
+use crate::message::track_status_request::TrackStatusRequest;
+use crate::message::{ControlMessage, FullTrackName, Namespace, Role};
 use crate::session::config::{Config, Perspective};
 use crate::session::local_track::LocalTrack;
 use crate::session::remote_track::RemoteTrack;
@@ -14,7 +20,7 @@ use crate::{Error, Result};
 use log::info;
 use retty::transport::Transmit;
 use std::collections::{HashMap, HashSet};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 mod config;
 mod local_track;
@@ -22,8 +28,13 @@ mod remote_track;
 mod stream;
 mod subscribe_window;
 
+/// How long the peer has to close the session after GOAWAY before it is
+/// terminated with GOAWAY_TIMEOUT.
+pub const GOAWAY_TIMEOUT: Duration = Duration::from_secs(10);
+
 // If |error_message| is none, the ANNOUNCE was successful.
-pub type OutgoingAnnounceCallback = fn(track_namespace: String, error: Option<AnnounceErrorReason>);
+pub type OutgoingAnnounceCallback =
+    fn(track_namespace: Namespace, error: Option<AnnounceErrorReason>);
 
 /// Indexed by subscribe_id.
 pub struct ActiveSubscribe {
@@ -62,12 +73,18 @@ pub struct Session {
     next_subscribe_id: u64,
 
     // Indexed by track namespace.
-    pending_outgoing_announces: HashMap<String, OutgoingAnnounceCallback>,
+    pending_outgoing_announces: HashMap<Namespace, OutgoingAnnounceCallback>,
+
+    // Tracks named in a TRACK_STATUS_REQUEST that has not been answered.
+    pending_track_status_requests: HashSet<FullTrackName>,
 
     // The role the peer advertised in its SETUP message. Initialize it to avoid
     // an uninitialized value if no SETUP arrives or it arrives with no Role
     // parameter, and other checks have changed/been disabled.
     peer_role: Role,
+
+    // Set when GOAWAY is sent or received; the session must be closed by then.
+    goaway_deadline: Option<Instant>,
 }
 
 impl Session {
@@ -87,7 +104,9 @@ impl Session {
             active_subscribes: Default::default(),
             next_subscribe_id: 0,
             pending_outgoing_announces: Default::default(),
+            pending_track_status_requests: Default::default(),
             peer_role: Default::default(),
+            goaway_deadline: None,
         }
     }
 
@@ -110,10 +129,171 @@ impl Session {
         }
     }
 
+    /// Registers the bidirectional stream a client opened as the control
+    /// stream. Only servers accept one, and only once.
+    pub fn on_incoming_bidi_stream(&mut self, stream_id: StreamId) -> Result<()> {
+        if self.config.perspective == Perspective::Client || self.control_stream_id.is_some() {
+            return Err(Error::ErrStreamError(
+                ErrorCode::ProtocolViolation,
+                "Bidirectional stream opened by peer".to_string(),
+            ));
+        }
+        let control_stream = StreamState::new(
+            self.config.clone(),
+            stream_id,
+            Some(true),
+            self.conn.transport(),
+        );
+        self.streams.insert(stream_id, control_stream);
+        self.control_stream_id = Some(stream_id);
+        Ok(())
+    }
+
     fn send_control_message(&mut self, control_message: ControlMessage) -> Result<()> {
         let mut control_stream = self.get_control_stream()?;
         control_stream.send_control_message(control_message)
     }
+
+    /// Sends GOAWAY and gives the peer GOAWAY_TIMEOUT from |now| to migrate.
+    pub fn send_go_away(&mut self, new_session_uri: String, now: Instant) -> Result<()> {
+        self.send_control_message(ControlMessage::GoAway(GoAway {
+            new_session_uri: Some(new_session_uri),
+        }))?;
+        self.start_goaway_timer(now);
+        Ok(())
+    }
+
+    /// Sends ANNOUNCE for |track_namespace|. |announce_callback| runs when the
+    /// peer answers with ANNOUNCE_OK or ANNOUNCE_ERROR, or right away if the
+    /// ANNOUNCE cannot be sent.
+    pub fn announce(
+        &mut self,
+        track_namespace: Namespace,
+        announce_callback: OutgoingAnnounceCallback,
+    ) -> Result<()> {
+        if self.peer_role == Role::Publisher {
+            announce_callback(
+                track_namespace,
+                Some(AnnounceErrorReason {
+                    error_code: AnnounceErrorCode::InternalError,
+                    reason_phrase: "ANNOUNCE cannot be sent to Publisher".to_string(),
+                }),
+            );
+            return Ok(());
+        }
+        if self
+            .pending_outgoing_announces
+            .contains_key(&track_namespace)
+        {
+            announce_callback(
+                track_namespace,
+                Some(AnnounceErrorReason {
+                    error_code: AnnounceErrorCode::InternalError,
+                    reason_phrase: "ANNOUNCE message already outstanding for namespace".to_string(),
+                }),
+            );
+            return Ok(());
+        }
+        self.send_control_message(ControlMessage::Announce(Announce {
+            track_namespace: track_namespace.clone(),
+            ..Default::default()
+        }))?;
+        info!(
+            "{:?} Sent ANNOUNCE message for {:?}",
+            self.config.perspective, track_namespace
+        );
+        self.pending_outgoing_announces
+            .insert(track_namespace, announce_callback);
+        Ok(())
+    }
+
+    pub(crate) fn on_announce_ok(&mut self, announce_ok: AnnounceOk) -> Result<()> {
+        let Some(announce_callback) = self
+            .pending_outgoing_announces
+            .remove(&announce_ok.track_namespace)
+        else {
+            return Err(Error::ErrStreamError(
+                ErrorCode::ProtocolViolation,
+                "Received ANNOUNCE_OK for nonexistent announce".to_string(),
+            ));
+        };
+        announce_callback(announce_ok.track_namespace, None);
+        Ok(())
+    }
+
+    pub(crate) fn on_announce_error(&mut self, announce_error: AnnounceError) -> Result<()> {
+        let Some(announce_callback) = self
+            .pending_outgoing_announces
+            .remove(&announce_error.track_namespace)
+        else {
+            return Err(Error::ErrStreamError(
+                ErrorCode::ProtocolViolation,
+                "Received ANNOUNCE_ERROR for nonexistent announce".to_string(),
+            ));
+        };
+        let error_code = announce_error.code().unwrap_or_default();
+        announce_callback(
+            announce_error.track_namespace,
+            Some(AnnounceErrorReason {
+                error_code,
+                reason_phrase: announce_error.reason_phrase,
+            }),
+        );
+        Ok(())
+    }
+
+    /// Sends TRACK_STATUS_REQUEST for |full_track_name|. The peer's TRACK_STATUS
+    /// for the same track answers it; one for any other track is a protocol
+    /// violation.
+    pub fn request_track_status(&mut self, full_track_name: FullTrackName) -> Result<()> {
+        self.send_control_message(ControlMessage::TrackStatusRequest(TrackStatusRequest {
+            track_namespace: full_track_name.track_namespace.clone(),
+            track_name: full_track_name.track_name.clone(),
+        }))?;
+        info!(
+            "{:?} Sent TRACK_STATUS_REQUEST message for {:?}",
+            self.config.perspective, full_track_name
+        );
+        self.pending_track_status_requests.insert(full_track_name);
+        Ok(())
+    }
+
+    pub(crate) fn on_track_status(&mut self, track_status: &TrackStatus) -> Result<()> {
+        let full_track_name = FullTrackName {
+            track_namespace: track_status.track_namespace.clone(),
+            track_name: track_status.track_name.clone(),
+        };
+        if !self.pending_track_status_requests.remove(&full_track_name) {
+            return Err(Error::ErrStreamError(
+                ErrorCode::ProtocolViolation,
+                "Received TRACK_STATUS for nonexistent request".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn on_go_away(&mut self, now: Instant) {
+        self.start_goaway_timer(now);
+    }
+
+    fn start_goaway_timer(&mut self, now: Instant) {
+        // A repeated GOAWAY does not extend the deadline.
+        if self.goaway_deadline.is_none() {
+            self.goaway_deadline = Some(now + GOAWAY_TIMEOUT);
+        }
+    }
+
+    /// Returns a GOAWAY_TIMEOUT error once |now| passes the deadline started by
+    /// a GOAWAY. The caller owns the clock.
+    pub fn check_goaway_timeout(&self, now: Instant) -> Result<()> {
+        match self.goaway_deadline {
+            Some(deadline) if now >= deadline => Err(Error::ErrStreamError(
+                ErrorCode::GoawayTimeout,
+                "Session not closed before GOAWAY timeout".to_string(),
+            )),
+            _ => Ok(()),
+        }
+    }
 }
 
 impl Handler for Session {
@@ -189,3 +369,241 @@ impl Handler for Session {
         todo!()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::message::message_parser::MessageParserEvent;
+    use crate::message::server_setup::ServerSetup;
+    use crate::message::Version;
+    use crate::session::stream::StreamEventIn;
+
+    #[test]
+    fn test_goaway_timeout() -> Result<()> {
+        let mut session = Session::new(
+            Config {
+                perspective: Perspective::Client,
+                ..Default::default()
+            },
+            Connection::QUIC,
+        );
+        let start = Instant::now();
+        session.check_goaway_timeout(start + GOAWAY_TIMEOUT * 2)?;
+
+        session.on_go_away(start);
+        session.check_goaway_timeout(start + GOAWAY_TIMEOUT - Duration::from_millis(1))?;
+        // A second GOAWAY does not restart the timer.
+        session.on_go_away(start + GOAWAY_TIMEOUT / 2);
+        assert_eq!(
+            session.check_goaway_timeout(start + GOAWAY_TIMEOUT),
+            Err(Error::ErrStreamError(
+                ErrorCode::GoawayTimeout,
+                "Session not closed before GOAWAY timeout".to_string(),
+            ))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_goaway_received_on_control_stream() -> Result<()> {
+        let mut session = Session::new(
+            Config {
+                perspective: Perspective::Client,
+                ..Default::default()
+            },
+            Connection::QUIC,
+        );
+        session.transport_active()?;
+        let now = Instant::now();
+        let mut control_stream = session.get_control_stream()?;
+        control_stream.handle_event(StreamEventIn::MessageParserEvent(
+            MessageParserEvent::ControlMessage(ControlMessage::GoAway(GoAway {
+                new_session_uri: Some("".to_string()),
+            })),
+            now,
+        ))?;
+
+        session.check_goaway_timeout(now + GOAWAY_TIMEOUT - Duration::from_millis(1))?;
+        assert!(session.check_goaway_timeout(now + GOAWAY_TIMEOUT).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_setup_from_wrong_perspective() -> Result<()> {
+        let mut server = Session::new(
+            Config {
+                perspective: Perspective::Server,
+                ..Default::default()
+            },
+            Connection::QUIC,
+        );
+        server.on_incoming_bidi_stream(0)?;
+        assert!(server.on_incoming_bidi_stream(4).is_err());
+        let mut control_stream = server.get_control_stream()?;
+        assert_eq!(
+            control_stream.handle_event(StreamEventIn::MessageParserEvent(
+                MessageParserEvent::ControlMessage(ControlMessage::ServerSetup(ServerSetup {
+                    supported_version: Version::Draft04,
+                    role: Some(Role::PubSub),
+                })),
+                Instant::now()
+            )),
+            Err(Error::ErrStreamError(
+                ErrorCode::ProtocolViolation,
+                "Received SERVER_SETUP from client".to_string(),
+            ))
+        );
+
+        let mut client = Session::new(
+            Config {
+                perspective: Perspective::Client,
+                ..Default::default()
+            },
+            Connection::QUIC,
+        );
+        client.transport_active()?;
+        let mut control_stream = client.get_control_stream()?;
+        assert_eq!(
+            control_stream.handle_event(StreamEventIn::MessageParserEvent(
+                MessageParserEvent::ControlMessage(ControlMessage::ClientSetup(ClientSetup {
+                    supported_versions: vec![Version::Draft04],
+                    role: Some(Role::PubSub),
+                    path: Some("/".to_string()),
+                    uses_web_transport: false,
+                })),
+                Instant::now()
+            )),
+            Err(Error::ErrStreamError(
+                ErrorCode::ProtocolViolation,
+                "Received CLIENT_SETUP from server".to_string(),
+            ))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_announce_response_matches_namespace() -> Result<()> {
+        thread_local! {
+            static RESPONSES: std::cell::RefCell<Vec<(Namespace, Option<AnnounceErrorReason>)>> =
+                Default::default();
+        }
+        fn record(track_namespace: Namespace, error: Option<AnnounceErrorReason>) {
+            RESPONSES.with_borrow_mut(|responses| responses.push((track_namespace, error)));
+        }
+
+        let mut session = Session::new(
+            Config {
+                perspective: Perspective::Client,
+                ..Default::default()
+            },
+            Connection::QUIC,
+        );
+        session.transport_active()?;
+        session.announce("foo".into(), record)?;
+        session.announce("bar".into(), record)?;
+        // A second ANNOUNCE for a pending namespace fails locally.
+        session.announce("foo".into(), record)?;
+
+        let mut control_stream = session.get_control_stream()?;
+        control_stream.handle_event(StreamEventIn::MessageParserEvent(
+            MessageParserEvent::ControlMessage(ControlMessage::AnnounceOk(AnnounceOk {
+                track_namespace: "foo".into(),
+            })),
+            Instant::now(),
+        ))?;
+        control_stream.handle_event(StreamEventIn::MessageParserEvent(
+            MessageParserEvent::ControlMessage(ControlMessage::AnnounceError(AnnounceError {
+                track_namespace: "bar".into(),
+                error_code: AnnounceErrorCode::AnnounceNotSupported as u64,
+                reason_phrase: "no".to_string(),
+            })),
+            Instant::now(),
+        ))?;
+        // Neither namespace is pending any more.
+        for message in [
+            ControlMessage::AnnounceOk(AnnounceOk {
+                track_namespace: "bar".into(),
+            }),
+            ControlMessage::AnnounceError(AnnounceError {
+                track_namespace: "foo".into(),
+                error_code: 0,
+                reason_phrase: "".to_string(),
+            }),
+        ] {
+            assert!(matches!(
+                control_stream.handle_event(StreamEventIn::MessageParserEvent(
+                    MessageParserEvent::ControlMessage(message),
+                    Instant::now()
+                )),
+                Err(Error::ErrStreamError(ErrorCode::ProtocolViolation, _))
+            ));
+        }
+
+        RESPONSES.with_borrow(|responses| {
+            assert_eq!(
+                responses,
+                &vec![
+                    (
+                        "foo".into(),
+                        Some(AnnounceErrorReason {
+                            error_code: AnnounceErrorCode::InternalError,
+                            reason_phrase: "ANNOUNCE message already outstanding for namespace"
+                                .to_string(),
+                        })
+                    ),
+                    ("foo".into(), None),
+                    (
+                        "bar".into(),
+                        Some(AnnounceErrorReason {
+                            error_code: AnnounceErrorCode::AnnounceNotSupported,
+                            reason_phrase: "no".to_string(),
+                        })
+                    ),
+                ]
+            )
+        });
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_track_status_matches_request() -> Result<()> {
+        let mut session = Session::new(
+            Config {
+                perspective: Perspective::Client,
+                ..Default::default()
+            },
+            Connection::QUIC,
+        );
+        session.transport_active()?;
+        session.request_track_status(FullTrackName::new("foo".to_string(), "abcd".to_string()))?;
+
+        let track_status = |track_name: &str| {
+            StreamEventIn::MessageParserEvent(
+                MessageParserEvent::ControlMessage(ControlMessage::TrackStatus(TrackStatus {
+                    track_namespace: "foo".into(),
+                    track_name: track_name.to_string(),
+                    ..Default::default()
+                })),
+                Instant::now(),
+            )
+        };
+        let mut control_stream = session.get_control_stream()?;
+        // Unsolicited: no request for this track.
+        assert_eq!(
+            control_stream.handle_event(track_status("efgh")),
+            Err(Error::ErrStreamError(
+                ErrorCode::ProtocolViolation,
+                "Received TRACK_STATUS for nonexistent request".to_string(),
+            ))
+        );
+        control_stream.handle_event(track_status("abcd"))?;
+        // The request has been answered.
+        assert!(control_stream.handle_event(track_status("abcd")).is_err());
+
+        Ok(())
+    }
+}
@@ -24,7 +24,7 @@ use crate::session::remote_track::RemoteTrackOnObjectFragment;
 use crate::session::Session;
 use crate::{Error, Result, StreamId};
 use bytes::{BufMut, Bytes, BytesMut};
-use log::{info, trace};
+use log::{debug, info, trace};
 use retty::transport::{Transmit, TransportContext};
 use std::collections::VecDeque;
 use std::time::Instant;
@@ -33,7 +33,9 @@ pub enum StreamEventIn {
     ResetStreamReceived(u64),
     StopSendingReceived(u64),
     WriteSideInDataRecvState,
-    MessageParserEvent(MessageParserEvent),
+    /// A parser event and the time its data was received. The caller owns
+    /// the clock.
+    MessageParserEvent(MessageParserEvent, Instant),
 }
 
 pub enum StreamEventOut {
@@ -70,8 +72,10 @@ impl StreamState {
         is_control_stream: Option<bool>,
         transport: TransportContext,
     ) -> Self {
+        let mut parser = MessageParser::new(config.use_web_transport);
+        parser.set_version(config.version);
         Self {
-            parser: MessageParser::new(config.use_web_transport),
+            parser,
             config,
             stream_id,
             is_control_stream,
@@ -108,7 +112,11 @@ impl StreamState {
 
     fn send_control_message(&mut self, control_message: ControlMessage) -> Result<()> {
         let mut message = BytesMut::new();
-        let _ = MessageFramer::serialize_control_message(control_message, &mut message)?;
+        let _ = MessageFramer::serialize_control_message_with_version(
+            control_message,
+            self.config.version,
+            &mut message,
+        )?;
         self.wouts.push_back(Transmit {
             now: Instant::now(),
             transport: self.transport,
@@ -117,6 +125,7 @@ impl StreamState {
         Ok(())
     }
 
+    #[allow(clippy::format_in_format_args)]
     fn on_object_message(
         &mut self,
         object_header: ObjectHeader,
@@ -201,21 +210,18 @@ impl StreamState {
                 "Received CLIENT_SETUP from server".to_string(),
             ));
         }
-        if !client_setup
-            .supported_versions
-            .contains(&self.config.version)
-        {
+        let Some(response) = ServerSetup::negotiate(
+            &client_setup.supported_versions,
+            &[self.config.version],
+            Role::PubSub,
+        ) else {
             return Err(Error::ErrStreamError(
                 ErrorCode::ProtocolViolation,
                 format!("Version mismatch: expected {:?}", self.config.version),
             ));
-        }
+        };
         info!("{:?} Received the CLIENT_SETUP message", self.perspective());
         if self.config.perspective == Perspective::Server {
-            let response = ServerSetup {
-                supported_version: self.config.version,
-                role: Some(Role::PubSub),
-            };
             let mut message = BytesMut::new();
             MessageFramer::serialize_control_message(
                 ControlMessage::ServerSetup(response),
@@ -253,7 +259,8 @@ impl StreamState {
                 "Received SERVER_SETUP from client".to_string(),
             ));
         }
-        if server_setup.supported_version != self.config.version {
+        // The client offers only its configured version.
+        if server_setup.validate(&[self.config.version]).is_err() {
             return Err(Error::ErrStreamError(
                 ErrorCode::ProtocolViolation,
                 format!("Version mismatch: expected {:?}", self.config.version),
@@ -411,13 +418,13 @@ impl StreamState {
         Ok(())
     }
 
-    fn on_announce_ok_message(&mut self, _announcee_ok: AnnounceOk) -> Result<()> {
+    fn on_announce_ok_message(&mut self, _announce_ok: &AnnounceOk) -> Result<()> {
         self.check_if_is_control_stream("ANNOUNCE_OK")?;
 
         Ok(())
     }
 
-    fn on_announce_error_message(&mut self, _announce_error: AnnounceError) -> Result<()> {
+    fn on_announce_error_message(&mut self, _announce_error: &AnnounceError) -> Result<()> {
         self.check_if_is_control_stream("ANNOUNCE_ERROR")?;
 
         Ok(())
@@ -444,7 +451,7 @@ impl StreamState {
         Ok(())
     }
 
-    fn on_track_status_message(&mut self, _track_status: TrackStatus) -> Result<()> {
+    fn on_track_status_message(&mut self, _track_status: &TrackStatus) -> Result<()> {
         self.check_if_is_control_stream("TRACK_STATUS")?;
 
         Ok(())
@@ -543,63 +550,90 @@ impl<'a> Handler for Stream<'a> {
                 Ok(())
             }
             StreamEventIn::WriteSideInDataRecvState => Ok(()),
-            StreamEventIn::MessageParserEvent(message_parser_event) => match message_parser_event {
-                MessageParserEvent::ParsingError(error_code, reason) => Err(Error::ErrStreamError(
-                    error_code,
-                    format!("Parse error: {}", reason),
-                )),
-                MessageParserEvent::ObjectMessage(object_header, payload, fin) => {
-                    stream_state.on_object_message(object_header, payload, fin)
-                }
-                MessageParserEvent::ControlMessage(control_message) => match control_message {
-                    ControlMessage::SubscribeUpdate(subscribe_update) => {
-                        stream_state.on_subscribe_update_message(subscribe_update)
-                    }
-                    ControlMessage::Subscribe(subscribe) => {
-                        stream_state.on_subscribe_message(subscribe)
-                    }
-                    ControlMessage::SubscribeOk(subscribe_ok) => {
-                        stream_state.on_subscribe_ok_message(subscribe_ok)
-                    }
-                    ControlMessage::SubscribeError(subscribe_error) => {
-                        stream_state.on_subscribe_error_message(subscribe_error)
-                    }
-                    ControlMessage::Announce(announce) => {
-                        stream_state.on_announce_message(announce)
-                    }
-                    ControlMessage::AnnounceOk(announce_ok) => {
-                        stream_state.on_announce_ok_message(announce_ok)
-                    }
-                    ControlMessage::AnnounceError(announce_error) => {
-                        stream_state.on_announce_error_message(announce_error)
-                    }
-                    ControlMessage::UnAnnounce(unannounce) => {
-                        stream_state.on_unannounce_message(unannounce)
+            StreamEventIn::MessageParserEvent(message_parser_event, now) => {
+                match message_parser_event {
+                    MessageParserEvent::ParsingError(error_code, reason) => Err(
+                        Error::ErrStreamError(error_code, format!("Parse error: {}", reason)),
+                    ),
+                    MessageParserEvent::ObjectMessage(object_header, payload, fin) => {
+                        stream_state.on_object_message(object_header, payload, fin)
                     }
-                    ControlMessage::UnSubscribe(unsubscribe) => {
-                        stream_state.on_unsubscribe_message(unsubscribe)
+                    MessageParserEvent::GroupStart(_) | MessageParserEvent::StreamClosed => Ok(()),
+                    MessageParserEvent::UnknownControlMessage { message_type, len } => {
+                        debug!(
+                            "skipped unknown control message type {:#x} of {} bytes",
+                            message_type, len
+                        );
+                        Ok(())
                     }
-                    ControlMessage::SubscribeDone(subscribe_done) => {
-                        stream_state.on_subscribe_done_message(subscribe_done)
+                    MessageParserEvent::CustomMessage {
+                        message_type,
+                        payload,
+                    } => {
+                        debug!(
+                            "ignored custom message type {:#x} of {} bytes",
+                            message_type,
+                            payload.len()
+                        );
+                        Ok(())
                     }
-                    ControlMessage::AnnounceCancel(announce_cancel) => {
-                        stream_state.on_announce_cancel_message(announce_cancel)
-                    }
-                    ControlMessage::TrackStatusRequest(track_status_request) => {
-                        stream_state.on_track_status_request_message(track_status_request)
-                    }
-                    ControlMessage::TrackStatus(track_status) => {
-                        stream_state.on_track_status_message(track_status)
-                    }
-                    ControlMessage::GoAway(go_away) => stream_state.on_go_away_message(go_away),
-                    ControlMessage::ClientSetup(client_setup) => {
-                        stream_state.on_client_setup_message(client_setup)
-                    }
-                    ControlMessage::ServerSetup(server_setup) => {
-                        stream_state.on_server_setup_message(server_setup)
-                    }
-                },
-            },
+                    MessageParserEvent::ControlMessage(control_message) => match control_message {
+                        ControlMessage::SubscribeUpdate(subscribe_update) => {
+                            stream_state.on_subscribe_update_message(subscribe_update)
+                        }
+                        ControlMessage::Subscribe(subscribe) => {
+                            stream_state.on_subscribe_message(subscribe)
+                        }
+                        ControlMessage::SubscribeOk(subscribe_ok) => {
+                            stream_state.on_subscribe_ok_message(subscribe_ok)
+                        }
+                        ControlMessage::SubscribeError(subscribe_error) => {
+                            stream_state.on_subscribe_error_message(subscribe_error)
+                        }
+                        ControlMessage::Announce(announce) => {
+                            stream_state.on_announce_message(announce)
+                        }
+                        ControlMessage::AnnounceOk(announce_ok) => {
+                            stream_state.on_announce_ok_message(&announce_ok)?;
+                            self.session.on_announce_ok(announce_ok)
+                        }
+                        ControlMessage::AnnounceError(announce_error) => {
+                            stream_state.on_announce_error_message(&announce_error)?;
+                            self.session.on_announce_error(announce_error)
+                        }
+                        ControlMessage::UnAnnounce(unannounce) => {
+                            stream_state.on_unannounce_message(unannounce)
+                        }
+                        ControlMessage::UnSubscribe(unsubscribe) => {
+                            stream_state.on_unsubscribe_message(unsubscribe)
+                        }
+                        ControlMessage::SubscribeDone(subscribe_done) => {
+                            stream_state.on_subscribe_done_message(subscribe_done)
+                        }
+                        ControlMessage::AnnounceCancel(announce_cancel) => {
+                            stream_state.on_announce_cancel_message(announce_cancel)
+                        }
+                        ControlMessage::TrackStatusRequest(track_status_request) => {
+                            stream_state.on_track_status_request_message(track_status_request)
+                        }
+                        ControlMessage::TrackStatus(track_status) => {
+                            stream_state.on_track_status_message(&track_status)?;
+                            self.session.on_track_status(&track_status)
+                        }
+                        ControlMessage::GoAway(go_away) => {
+                            stream_state.on_go_away_message(go_away)?;
+                            self.session.on_go_away(now);
+                            Ok(())
+                        }
+                        ControlMessage::ClientSetup(client_setup) => {
+                            stream_state.on_client_setup_message(client_setup)
+                        }
+                        ControlMessage::ServerSetup(server_setup) => {
+                            stream_state.on_server_setup_message(server_setup)
+                        }
+                    },
+                }
+            }
         }
     }
 
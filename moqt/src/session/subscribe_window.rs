@@ -270,6 +270,7 @@ impl SubscribeWindows {
 }
 
 #[cfg(test)]
+#[allow(clippy::nonminimal_bool)]
 mod test {
     use super::*;
     use crate::Result;
@@ -0,0 +1,47 @@
+use moqt::{
+    ControlMessage, ErrorCode, FilterType, FullSequence, MessageFramer, MessageParser,
+    MessageParserEvent, MessageType, Parameters, Result, Subscribe,
+};
+
+#[test]
+fn test_parse_subscribe() -> Result<()> {
+    let subscribe = Subscribe {
+        subscribe_id: 1,
+        track_alias: 2,
+        track_namespace: "foo".into(),
+        track_name: "abcd".to_string(),
+        filter_type: FilterType::AbsoluteStart(FullSequence::new(4, 1)),
+        authorization_info: Some("bar".into()),
+        parameters: Parameters::new(),
+    };
+    let mut wire = vec![];
+    MessageFramer::serialize_control_message(
+        ControlMessage::Subscribe(subscribe.clone()),
+        &mut wire,
+    )?;
+
+    let mut parser = MessageParser::new(false);
+    parser.process_data(&mut wire.as_slice(), false);
+    match parser.poll_event() {
+        Some(MessageParserEvent::ControlMessage(message)) => {
+            assert_eq!(message.message_type(), MessageType::Subscribe);
+            assert_eq!(message, ControlMessage::Subscribe(subscribe));
+        }
+        _ => panic!("expected SUBSCRIBE"),
+    }
+    assert!(parser.poll_event().is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_parse_unknown_message_type() {
+    let mut parser = MessageParser::new(false);
+    parser.process_data(&mut [0x3fu8].as_slice(), false);
+    match parser.poll_event() {
+        Some(MessageParserEvent::ParsingError(code, _)) => {
+            assert_eq!(code, ErrorCode::ProtocolViolation)
+        }
+        _ => panic!("expected a parsing error"),
+    }
+}
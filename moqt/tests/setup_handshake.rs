@@ -0,0 +1,62 @@
+use moqt::message::client_setup::ClientSetup;
+use moqt::message::message_framer::MessageFramer;
+use moqt::message::message_parser::{MessageParser, MessageParserEvent};
+use moqt::message::server_setup::ServerSetup;
+use moqt::message::{ControlMessage, Role, Version};
+use moqt::Result;
+
+fn parse_one(parser: &mut MessageParser, mut data: &[u8]) -> ControlMessage {
+    parser.process_data(&mut data, false);
+    match parser.poll_event() {
+        Some(MessageParserEvent::ControlMessage(message)) => {
+            assert!(parser.poll_event().is_none());
+            message
+        }
+        Some(MessageParserEvent::ParsingError(code, reason)) => {
+            panic!("parsing error {}: {}", code, reason)
+        }
+        _ => panic!("expected a control message"),
+    }
+}
+
+#[test]
+fn test_setup_handshake() -> Result<()> {
+    let mut client_parser = MessageParser::new(false);
+    let mut server_parser = MessageParser::new(false);
+
+    // Client -> server: CLIENT_SETUP offering two versions.
+    let mut client_setup = ClientSetup::new(false);
+    client_setup.supported_versions = vec![Version::Draft03, Version::Draft04];
+    client_setup.role = Some(Role::PubSub);
+    client_setup.path = Some("/moq".to_string());
+    let mut wire = vec![];
+    MessageFramer::serialize_control_message(
+        ControlMessage::ClientSetup(client_setup.clone()),
+        &mut wire,
+    )?;
+
+    let received = match parse_one(&mut server_parser, &wire) {
+        ControlMessage::ClientSetup(received) => received,
+        message => panic!("unexpected {:?}", message),
+    };
+    assert_eq!(received, client_setup);
+    assert!(received.supported_versions.contains(&Version::Draft04));
+
+    // Server -> client: SERVER_SETUP selecting Draft04.
+    let server_setup = ServerSetup {
+        supported_version: Version::Draft04,
+        role: Some(Role::PubSub),
+    };
+    let mut wire = vec![];
+    MessageFramer::serialize_control_message(
+        ControlMessage::ServerSetup(server_setup.clone()),
+        &mut wire,
+    )?;
+
+    match parse_one(&mut client_parser, &wire) {
+        ControlMessage::ServerSetup(received) => assert_eq!(received, server_setup),
+        message => panic!("unexpected {:?}", message),
+    }
+
+    Ok(())
+}